@@ -0,0 +1,65 @@
+//! Compares the tree-walking interpreter with compiled closures and the
+//! bytecode virtual machine on a formula that is evaluated with changing variables.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parsing_basics::{
+    eval::{compile_with, vm, Env, Interpreter, Value},
+    parser::Parser,
+};
+
+const FORMULA: &str = "a * x ^ 2 + b * x + c - sqrt(abs(x)) * PI / (1 + x * x)";
+
+fn bench_eval(c: &mut Criterion) {
+    let expr = Parser::new(FORMULA).parse_expression(0);
+    let mut env = Env::with_prelude();
+    env.set("a", 3.5);
+    env.set("b", -2);
+    env.set("c", 0.25);
+    env.set("x", 0.0);
+
+    let mut group = c.benchmark_group("eval");
+
+    group.bench_function("tree-walker", |bencher| {
+        let interpreter = Interpreter::new();
+        let mut x = 0.0;
+        bencher.iter(|| {
+            x += 0.001;
+            env.set("x", x);
+            black_box(interpreter.eval(black_box(&expr), &env).unwrap())
+        });
+    });
+
+    group.bench_function("compiled", |bencher| {
+        let compiled = compile_with(&expr, &env);
+        let mut values = compiled.bind(&env).unwrap();
+        let x_slot = compiled.slot("x").unwrap();
+        let mut x = 0.0;
+        bencher.iter(|| {
+            x += 0.001;
+            values[x_slot] = Value::Float(x);
+            black_box(compiled.eval(black_box(&values)).unwrap())
+        });
+    });
+
+    group.bench_function("vm", |bencher| {
+        let chunk = vm::compile(&expr, &env).unwrap();
+        let mut machine = vm::Vm::new();
+        let mut values: Vec<Value> = chunk
+            .slots
+            .iter()
+            .map(|name| env.get(name).cloned().unwrap())
+            .collect();
+        let x_slot = chunk.slots.iter().position(|slot| slot == "x").unwrap();
+        let mut x = 0.0;
+        bencher.iter(|| {
+            x += 0.001;
+            values[x_slot] = Value::Float(x);
+            black_box(machine.run(&chunk, black_box(&values)).unwrap())
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_eval);
+criterion_main!(benches);
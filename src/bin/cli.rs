@@ -0,0 +1,528 @@
+//! The library on the command line.
+//!
+//! Build it with `cargo build --features cli`. With `--json`, every input
+//! produces one line of JSON, in the format of the `serde` feature where the
+//! library has one.
+
+use parsing_basics::{
+    diagnostic,
+    eval::{Env, Interpreter, Value},
+    formatter::{self, FormatConfig},
+    lexer::{Lexer, Span},
+    parser::{ast::Expr, cst, sexpr, Parser},
+    typeck,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+usage: cli <command> [--json] [<file>...]
+
+commands:
+  lex           print the tokens of each input
+  parse         print the syntax tree of each input
+  check         report syntax and type errors, and fail if there are any
+  fmt           format files in place, or print standard input formatted
+  fmt --check   list the inputs that are not formatted, and fail if there are any
+  run           evaluate each input and print its value
+
+Inputs are the given files, or standard input if there are none or one is `-`.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Lex,
+    Parse,
+    Check,
+    Fmt,
+    Run,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Options {
+    command: Command,
+    json: bool,
+    check: bool,
+    files: Vec<String>,
+}
+
+/// An input and where it came from.
+struct Source {
+    /// The file name, or `<stdin>`.
+    name: String,
+    /// Whether `name` is a file that `fmt` can rewrite.
+    is_file: bool,
+    text: String,
+}
+
+/// What a command did with one input.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Report {
+    output: String,
+    failed: bool,
+    /// The new contents of the input, for `fmt`.
+    rewrite: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) if error.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut failed = false;
+    for source in read_sources(&options.files) {
+        let source = match source {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}", error);
+                return ExitCode::from(2);
+            }
+        };
+        let report = run(&options, &source);
+        if !report.output.is_empty() {
+            println!("{}", report.output);
+        }
+        if let Some(text) = report.rewrite {
+            if let Err(error) = fs::write(&source.name, text) {
+                eprintln!("cannot write {}: {}", source.name, error);
+                return ExitCode::from(2);
+            }
+        }
+        failed |= report.failed;
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut command = None;
+    let mut options = Options {
+        command: Command::Lex,
+        json: false,
+        check: false,
+        files: Vec::new(),
+    };
+    for arg in args {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--check" => options.check = true,
+            // No error, just the usage.
+            "-h" | "--help" => return Err(String::new()),
+            "-" => options.files.push(arg),
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ if command.is_some() => options.files.push(arg),
+            name => {
+                command = Some(match name {
+                    "lex" => Command::Lex,
+                    "parse" => Command::Parse,
+                    "check" => Command::Check,
+                    "fmt" => Command::Fmt,
+                    "run" => Command::Run,
+                    _ => return Err(format!("unknown command `{}`", name)),
+                });
+            }
+        }
+    }
+
+    options.command = command.ok_or("missing command")?;
+    if options.check && options.command != Command::Fmt {
+        return Err("`--check` only applies to `fmt`".to_string());
+    }
+    Ok(options)
+}
+
+fn read_sources(files: &[String]) -> Vec<io::Result<Source>> {
+    let read_stdin = || {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(Source {
+            name: "<stdin>".to_string(),
+            is_file: false,
+            text,
+        })
+    };
+    if files.is_empty() {
+        return vec![read_stdin()];
+    }
+    files
+        .iter()
+        .map(|file| match file.as_str() {
+            "-" => read_stdin(),
+            _ => fs::read_to_string(file)
+                .map(|text| Source {
+                    name: file.clone(),
+                    is_file: true,
+                    text,
+                })
+                .map_err(|error| {
+                    io::Error::new(error.kind(), format!("cannot read {}: {}", file, error))
+                }),
+        })
+        .collect()
+}
+
+fn run(options: &Options, source: &Source) -> Report {
+    match options.command {
+        Command::Lex => lex(source, options.json),
+        Command::Parse => parse(source, options.json),
+        Command::Check => check(source, options.json),
+        Command::Fmt => fmt(source, options.json, options.check),
+        Command::Run => eval(source, options.json),
+    }
+}
+
+fn lex(source: &Source, json: bool) -> Report {
+    let tokens = Lexer::new(&source.text).tokenize();
+    let output = if json {
+        serde_json::to_string(&tokens).unwrap()
+    } else {
+        tokens
+            .iter()
+            .map(|token| format!("{:?} `{}`", token, token.text(&source.text)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    Report {
+        output,
+        ..Report::default()
+    }
+}
+
+fn parse(source: &Source, json: bool) -> Report {
+    match expr(source, json) {
+        Ok(expr) if json => Report {
+            output: serde_json::to_string(&expr).unwrap(),
+            ..Report::default()
+        },
+        Ok(expr) => Report {
+            output: sexpr::write(&expr),
+            ..Report::default()
+        },
+        Err(report) => report,
+    }
+}
+
+/// The AST of all of the input, or the report of its syntax errors.
+fn expr(source: &Source, json: bool) -> Result<Expr, Report> {
+    let errors = cst::parse(&source.text).errors;
+    let result = if errors.is_empty() {
+        Parser::new(&source.text).try_parse_expression(0)
+    } else {
+        Err(errors)
+    };
+    result.map_err(|errors| {
+        diagnostics(
+            source,
+            json,
+            errors.into_iter().map(|error| (error.message, error.span)),
+        )
+    })
+}
+
+fn check(source: &Source, json: bool) -> Report {
+    let parse = cst::parse(&source.text);
+    if !parse.errors.is_empty() {
+        return diagnostics(
+            source,
+            json,
+            parse
+                .errors
+                .into_iter()
+                .map(|error| (error.message, error.span)),
+        );
+    }
+    let typing = typeck::check(&parse.root, &source.text, &Env::with_prelude());
+    diagnostics(
+        source,
+        json,
+        typing
+            .errors
+            .into_iter()
+            .map(|error| (error.message, error.span)),
+    )
+}
+
+/// A syntax or type error, as `check` prints it with `--json`.
+#[derive(Serialize)]
+struct Diagnostic<'a> {
+    file: &'a str,
+    message: String,
+    span: Span,
+    line: usize,
+    column: usize,
+}
+
+/// Print `errors`, and fail if there are any.
+fn diagnostics(
+    source: &Source,
+    json: bool,
+    errors: impl Iterator<Item = (String, Span)>,
+) -> Report {
+    let errors: Vec<_> = errors.collect();
+    let failed = !errors.is_empty();
+    let output = if json {
+        let diagnostics: Vec<_> = errors
+            .into_iter()
+            .map(|(message, span)| {
+                let (line, column) = diagnostic::line_col(&source.text, span.start);
+                Diagnostic {
+                    file: &source.name,
+                    message,
+                    span,
+                    line,
+                    column,
+                }
+            })
+            .collect();
+        serde_json::to_string(&diagnostics).unwrap()
+    } else {
+        errors
+            .iter()
+            .map(|(message, span)| {
+                diagnostic::render_in(&source.name, &source.text, *span, message)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    Report {
+        output,
+        failed,
+        rewrite: None,
+    }
+}
+
+fn fmt(source: &Source, json: bool, check: bool) -> Report {
+    if !cst::parse(&source.text).errors.is_empty() {
+        return self::check(source, json);
+    }
+    let formatted = formatter::format(&source.text, FormatConfig::default());
+    let changed = formatted != source.text;
+
+    if check {
+        let output = match (json, changed) {
+            (true, _) => json!({ "file": source.name, "formatted": !changed }).to_string(),
+            (false, true) => format!("{} is not formatted", source.name),
+            (false, false) => String::new(),
+        };
+        Report {
+            output,
+            failed: changed,
+            rewrite: None,
+        }
+    } else if source.is_file {
+        Report {
+            output: String::new(),
+            failed: false,
+            rewrite: changed.then_some(formatted),
+        }
+    } else if json {
+        Report {
+            output: json!({ "file": source.name, "formatted": formatted }).to_string(),
+            ..Report::default()
+        }
+    } else {
+        // `println!` adds the final newline back.
+        let output = formatted.strip_suffix('\n').unwrap_or(&formatted);
+        Report {
+            output: output.to_string(),
+            ..Report::default()
+        }
+    }
+}
+
+fn eval(source: &Source, json: bool) -> Report {
+    let expr = match expr(source, json) {
+        Ok(expr) => expr,
+        Err(report) => return report,
+    };
+    match Interpreter::new().eval(&expr, &Env::with_prelude()) {
+        Ok(value) if json => Report {
+            output: json!({ "value": value_json(&value), "type": value.ty().to_string() })
+                .to_string(),
+            ..Report::default()
+        },
+        Ok(value) => Report {
+            output: value.to_string(),
+            ..Report::default()
+        },
+        Err(error) if json => Report {
+            output: json!({ "error": error.to_string() }).to_string(),
+            failed: true,
+            rewrite: None,
+        },
+        Err(error) => Report {
+            output: format!("error: {}", error),
+            failed: true,
+            rewrite: None,
+        },
+    }
+}
+
+/// Integers beyond ±(2^53 - 1) are strings, like literals in the `serde`
+/// format, since JavaScript reads larger JSON numbers inexactly.
+fn value_json(value: &Value) -> serde_json::Value {
+    const MAX_SAFE: u64 = (1 << 53) - 1;
+    match value {
+        Value::Int(i) if i.unsigned_abs() <= MAX_SAFE => json!(i),
+        Value::Int(i) => json!(i.to_string()),
+        Value::BigInt(i) => json!(i.to_string()),
+        Value::Float(fl) => json!(fl),
+        Value::Str(s) => json!(s),
+        Value::Bool(b) => json!(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Result<Options, String> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    fn stdin(text: &str) -> Source {
+        Source {
+            name: "<stdin>".to_string(),
+            is_file: false,
+            text: text.to_string(),
+        }
+    }
+
+    fn output(args: &str, text: &str) -> (String, bool) {
+        let report = run(&self::args(args).unwrap(), &stdin(text));
+        (report.output, report.failed)
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            args("fmt --check a.expr -"),
+            Ok(Options {
+                command: Command::Fmt,
+                json: false,
+                check: true,
+                files: vec!["a.expr".to_string(), "-".to_string()],
+            })
+        );
+        assert_eq!(args("--json run").map(|options| options.json), Ok(true));
+        assert_eq!(args(""), Err("missing command".to_string()));
+        assert_eq!(args("lint"), Err("unknown command `lint`".to_string()));
+        assert_eq!(
+            args("run --check"),
+            Err("`--check` only applies to `fmt`".to_string())
+        );
+    }
+
+    #[test]
+    fn lex_and_parse() {
+        assert_eq!(
+            output("lex", "x+1"),
+            (
+                "Identifier - <0, 1> `x`\nPlus - <1, 2> `+`\nInt - <2, 3> `1`\nEof - <3, 3> ``"
+                    .to_string(),
+                false
+            )
+        );
+        assert_eq!(
+            output("lex --json", "x"),
+            (
+                r#"[{"kind":"Identifier","span":{"start":0,"end":1}},{"kind":"Eof","span":{"start":1,"end":1}}]"#
+                    .to_string(),
+                false
+            )
+        );
+        assert_eq!(
+            output("parse", "-x * 2"),
+            (
+                "(infix Times (prefix Minus (ident x)) (int 2))".to_string(),
+                false
+            )
+        );
+        assert_eq!(
+            output("parse --json", "f()"),
+            (
+                r#"{"type":"FnCall","value":{"fn_name":"f","args":[]}}"#.to_string(),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn check_fails_on_errors() {
+        assert_eq!(output("check", "sqrt(2) + x"), (String::new(), false));
+        assert_eq!(
+            output("check", "1 +\n\"a\""),
+            (
+                "error: expected `int`, found `string`\n --> <stdin>:2:1\n  |\n2 | \"a\"\n  | ^^^"
+                    .to_string(),
+                true
+            )
+        );
+        assert_eq!(
+            output("check --json", "(1"),
+            (
+                r#"[{"file":"<stdin>","message":"Expected `)`, but found `<EOF>`","span":{"start":2,"end":2},"line":1,"column":3}]"#
+                    .to_string(),
+                true
+            )
+        );
+        assert_eq!(
+            output("check", "256u8"),
+            (
+                "error: integer literal out of range for `u8`: `256u8`\n --> <stdin>:1:1\n  |\n1 | 256u8\n  | ^^^^^"
+                    .to_string(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn fmt_and_run() {
+        assert_eq!(output("fmt", "1+2"), ("1 + 2".to_string(), false));
+        assert_eq!(
+            output("fmt --check", "1+2"),
+            ("<stdin> is not formatted".to_string(), true)
+        );
+        assert_eq!(output("fmt --check", "1 + 2\n"), (String::new(), false));
+
+        let file = Source {
+            is_file: true,
+            ..stdin("1+2")
+        };
+        let report = run(&args("fmt").unwrap(), &file);
+        assert_eq!(report.rewrite, Some("1 + 2\n".to_string()));
+
+        assert_eq!(output("run", "max(2, 3) * 1.5"), ("4.5".to_string(), false));
+        assert_eq!(
+            output("run", "1 / 0"),
+            ("error: division by zero".to_string(), true)
+        );
+        assert_eq!(
+            output("run --json", "\"a\" + \"b\""),
+            (r#"{"type":"string","value":"ab"}"#.to_string(), false)
+        );
+        assert_eq!(
+            output("run --json", "2 ^ 62"),
+            (
+                r#"{"type":"int","value":"4611686018427387904"}"#.to_string(),
+                false
+            )
+        );
+    }
+}
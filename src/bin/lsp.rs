@@ -0,0 +1,492 @@
+//! A language server that speaks JSON-RPC over standard input and output.
+//!
+//! Build it with `cargo build --features lsp`. Documents are synchronized in
+//! full on every change. The server publishes the syntax errors of a
+//! document whenever it is opened or changed, and answers requests for
+//! semantic tokens, document symbols, go-to-definition and formatting.
+//!
+//! Names are resolved against the math prelude with
+//! [`parsing_basics::resolve`]. The grammar has no declarations, so a name
+//! the prelude does not define is introduced where it is first used, and
+//! that is where go-to-definition jumps. Names from the prelude are defined
+//! by the host and have no location to jump to.
+//!
+//! The lexer knows `fn` and `struct`, but the grammar has no items yet.
+//! Document symbols are found in the tokens instead: a keyword followed by
+//! a name is an item, whether the parser accepts what comes after or not.
+
+use parsing_basics::{
+    eval::Env,
+    formatter::{self, FormatConfig},
+    lexer::{Lexer, Span, Token, TokenCategory},
+    parser::cst,
+    resolve::resolve,
+    T,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+/// The semantic token types, the index into this list is what the
+/// server sends for a token.
+const TOKEN_TYPES: [&str; 7] = [
+    "keyword", "number", "string", "comment", "operator", "variable", "function",
+];
+
+/// The `SymbolKind`s of the protocol for the items the server lists.
+const FUNCTION_SYMBOL: u32 = 12;
+const STRUCT_SYMBOL: u32 = 23;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const PARSE_ERROR: i64 = -32700;
+
+fn main() -> ExitCode {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    loop {
+        let outgoing = match read_message(&mut input) {
+            Ok(Some(Ok(message))) => server.handle(&message),
+            Ok(Some(Err(error))) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": error.to_string() },
+            })],
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("cannot read message: {}", error);
+                return ExitCode::FAILURE;
+            }
+        };
+        for message in outgoing {
+            if let Err(error) = write_message(&mut output, &message) {
+                eprintln!("cannot write message: {}", error);
+                return ExitCode::FAILURE;
+            }
+        }
+        if server.exited {
+            break;
+        }
+    }
+
+    // An exit without a shutdown request first is an error.
+    if server.shut_down {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Read one message with its `Content-Length` header, or `None` at the end
+/// of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<serde_json::Result<Value>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing `Content-Length` header",
+        )
+    })?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    /// Handle a request or notification and return the messages to send.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            // A response to a request of ours, which we never make.
+            return Vec::new();
+        };
+        let params = &message["params"];
+
+        match message.get("id") {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, error)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": error },
+                    }),
+                };
+                vec![response]
+            }
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = || self.documents.get(uri).map_or("", String::as_str);
+        Ok(match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "semanticTokensProvider": {
+                        "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                        "full": true,
+                    },
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": { "name": "parsing-basics" },
+            }),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/semanticTokens/full" => json!({ "data": semantic_tokens(text()) }),
+            "textDocument/documentSymbol" => document_symbols(text()),
+            "textDocument/definition" => definition(uri, text(), &params["position"]),
+            "textDocument/formatting" => formatting(text()),
+            _ => {
+                return Err((
+                    METHOD_NOT_FOUND,
+                    format!("method `{}` is not supported", method),
+                ))
+            }
+        })
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                // With full synchronization, the last change is the whole text.
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, "", &[])];
+            }
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        }
+
+        // A change to a document that was never opened has no text to check.
+        let Some(text) = self.documents.get(&uri) else {
+            return Vec::new();
+        };
+        let errors: Vec<_> = cst::parse(text)
+            .errors
+            .into_iter()
+            .map(|error| (error.span, error.message))
+            .collect();
+        vec![publish_diagnostics(&uri, text, &errors)]
+    }
+}
+
+fn publish_diagnostics(uri: &str, text: &str, errors: &[(Span, String)]) -> Value {
+    let lines = LineIndex::new(text);
+    let diagnostics: Vec<_> = errors
+        .iter()
+        .map(|(span, message)| {
+            json!({
+                "range": lines.range(*span),
+                "severity": 1,
+                "source": "parsing-basics",
+                "message": message,
+            })
+        })
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// The tokens of `text` in the relative encoding of the protocol: the line
+/// and start of each token relative to the one before, its length, its type
+/// and no modifiers. A token across lines is only marked on its first line.
+fn semantic_tokens(text: &str) -> Vec<u32> {
+    let lines = LineIndex::new(text);
+    let tokens: Vec<Token> = Lexer::new(text)
+        .filter(|token| !matches!(token.kind, T![ws] | T![EOF]))
+        .collect();
+
+    let mut data = Vec::new();
+    let (mut previous_line, mut previous_start) = (0, 0);
+    for (i, token) in tokens.iter().enumerate() {
+        let ty = match token.kind.category() {
+            TokenCategory::Keyword => 0,
+            TokenCategory::Literal if token.kind == T![string] => 2,
+            TokenCategory::Literal => 1,
+            TokenCategory::Comment => 3,
+            TokenCategory::Operator => 4,
+            // A name followed by a `(` is called.
+            TokenCategory::Identifier
+                if tokens.get(i + 1).is_some_and(|next| next.kind == T!['(']) =>
+            {
+                6
+            }
+            TokenCategory::Identifier => 5,
+            _ => continue,
+        };
+
+        let (line, start) = lines.position(token.span.start);
+        let line_text = token.text(text).lines().next().unwrap_or_default();
+        let length = line_text.encode_utf16().count() as u32;
+        let delta_start = if line == previous_line {
+            start - previous_start
+        } else {
+            start
+        };
+        data.extend([line - previous_line, delta_start, length, ty, 0]);
+        (previous_line, previous_start) = (line, start);
+    }
+    data
+}
+
+/// The `fn` and `struct` items of `text`. An item's range goes from its
+/// keyword to its name, since nothing tells where it ends.
+fn document_symbols(text: &str) -> Value {
+    let lines = LineIndex::new(text);
+    let tokens: Vec<Token> = Lexer::new(text)
+        .filter(|token| !token.kind.is_trivia())
+        .collect();
+
+    let symbols: Vec<_> = tokens
+        .windows(2)
+        .filter_map(|pair| {
+            let [keyword, name] = pair else {
+                return None;
+            };
+            let kind = match (keyword.kind, name.kind) {
+                (T![fn], T![ident]) => FUNCTION_SYMBOL,
+                (T![struct], T![ident]) => STRUCT_SYMBOL,
+                _ => return None,
+            };
+            let item = Span {
+                start: keyword.span.start,
+                end: name.span.end,
+            };
+            Some(json!({
+                "name": name.text(text),
+                "kind": kind,
+                "range": lines.range(item),
+                "selectionRange": lines.range(name.span),
+            }))
+        })
+        .collect();
+    json!(symbols)
+}
+
+/// The location that the name at `position` is defined at, or null if
+/// there is no name there or it comes from the prelude.
+fn definition(uri: &str, text: &str, position: &Value) -> Value {
+    let lines = LineIndex::new(text);
+    let Some(offset) = lines.offset(position) else {
+        return Value::Null;
+    };
+    let root = cst::parse(text).root;
+    let resolution = resolve(&root, text, &Env::with_prelude());
+    let nodes = root.descendants();
+    // The name is the first token of both identifiers and calls.
+    let name = |id: cst::NodeId| {
+        nodes[id.0]
+            .child_tokens()
+            .find(|token| !token.kind.is_trivia())
+            .map(|token| token.span)
+    };
+
+    let Some(uses) = resolution.uses.values().find(|uses| {
+        uses.iter()
+            .filter_map(|id| name(*id))
+            .any(|span| span.start <= offset && offset <= span.end)
+    }) else {
+        return Value::Null;
+    };
+    if resolution.definitions.contains_key(&uses[0]) {
+        return Value::Null;
+    }
+    match name(uses[0]) {
+        Some(span) => json!({ "uri": uri, "range": lines.range(span) }),
+        None => Value::Null,
+    }
+}
+
+/// An edit that replaces the whole text with its formatted version,
+/// or no edits if it is formatted already or has syntax errors.
+fn formatting(text: &str) -> Value {
+    let formatted = formatter::format(text, FormatConfig::default());
+    if formatted == text {
+        return json!([]);
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let whole = Span {
+        start: 0,
+        end: text.len() as u32,
+    };
+    json!([{ "range": LineIndex::new(text).range(whole), "newText": formatted }])
+}
+
+/// Converts byte offsets into the positions of the protocol,
+/// which count lines and UTF-16 code units from zero.
+struct LineIndex<'a> {
+    text: &'a str,
+    /// The offset of the start of each line.
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn position(&self, offset: u32) -> (u32, u32) {
+        let offset = offset as usize;
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let character = self.text[self.starts[line]..offset].encode_utf16().count();
+        (line as u32, character as u32)
+    }
+
+    /// The offset of a position of the protocol, or `None` if it is
+    /// malformed or past the end of its line.
+    #[allow(clippy::cast_possible_truncation)]
+    fn offset(&self, position: &Value) -> Option<u32> {
+        let line = usize::try_from(position["line"].as_u64()?).ok()?;
+        let character = usize::try_from(position["character"].as_u64()?).ok()?;
+        let start = *self.starts.get(line)?;
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.text.len(), |end| end - 1);
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= character {
+                return Some((start + i) as u32);
+            }
+            units += c.len_utf16();
+        }
+        (units >= character).then_some(end as u32)
+    }
+
+    fn range(&self, span: Span) -> Value {
+        let (start_line, start_character) = self.position(span.start);
+        let (end_line, end_character) = self.position(span.end);
+        json!({
+            "start": { "line": start_line, "character": start_character },
+            "end": { "line": end_line, "character": end_character },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let lines = LineIndex::new("\"😀\" +\n  x");
+        assert_eq!(lines.position(0), (0, 0));
+        assert_eq!(lines.position(6), (0, 4));
+        assert_eq!(lines.position(11), (1, 2));
+        let offset = |line: u32, character: u32| {
+            lines.offset(&json!({ "line": line, "character": character }))
+        };
+        assert_eq!(offset(0, 4), Some(6));
+        assert_eq!(offset(1, 3), Some(12));
+        assert_eq!(offset(1, 4), None);
+        assert_eq!(offset(2, 0), None);
+    }
+
+    #[test]
+    fn symbols_are_named_items() {
+        let symbols = document_symbols("fn f(x) = x\nstruct /* c */ S\nfn 1 + f(2)");
+        let names: Vec<_> = symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| (symbol["name"].clone(), symbol["kind"].clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (json!("f"), json!(FUNCTION_SYMBOL)),
+                (json!("S"), json!(STRUCT_SYMBOL))
+            ]
+        );
+        assert_eq!(
+            symbols[1]["range"],
+            json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 16 } })
+        );
+        assert_eq!(
+            symbols[1]["selectionRange"]["start"],
+            json!({ "line": 1, "character": 15 })
+        );
+    }
+
+    #[test]
+    fn semantic_tokens_are_relative() {
+        assert_eq!(
+            semantic_tokens("f(x) // c\n  + 2.5"),
+            [
+                0, 0, 1, 6, 0, // f
+                0, 2, 1, 5, 0, // x
+                0, 3, 4, 3, 0, // comment
+                1, 2, 1, 4, 0, // +
+                0, 2, 3, 1, 0, // 2.5
+            ]
+        );
+    }
+}
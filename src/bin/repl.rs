@@ -0,0 +1,250 @@
+//! An interactive prompt that evaluates one expression per entry.
+//!
+//! An entry with an unclosed `(` or `{` continues on the next line, until
+//! the brackets are balanced or an empty line is entered. Besides
+//! expressions, the prompt understands these commands:
+//!
+//! ```text
+//! :let <name> = <expr>  evaluate <expr> and bind it to <name>
+//! :tokens [<expr>]      show the tokens of <expr>, or of the last entry
+//! :ast [<expr>]         show the syntax tree of <expr>, or of the last entry
+//! :type [<expr>]        show the inferred type of <expr>, or of the last entry
+//! :help                 show this list
+//! :quit                 exit, as does the end of the input
+//! ```
+
+use parsing_basics::{
+    diagnostic,
+    eval::{Env, Interpreter, Value},
+    lexer::{Lexer, Span},
+    parser::{ast::Expr, cst, sexpr, Parser},
+    typeck, T,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Write as _},
+};
+
+const HELP: &str = "\
+:let <name> = <expr>  evaluate <expr> and bind it to <name>
+:tokens [<expr>]      show the tokens of <expr>, or of the last entry
+:ast [<expr>]         show the syntax tree of <expr>, or of the last entry
+:type [<expr>]        show the inferred type of <expr>, or of the last entry
+:help                 show this list
+:quit                 exit, as does the end of the input";
+
+fn main() -> io::Result<()> {
+    let mut session = Session::new();
+    let mut stdout = io::stdout();
+    let mut entry = String::new();
+
+    print!("> ");
+    stdout.flush()?;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !entry.is_empty() {
+            entry.push('\n');
+        }
+        entry.push_str(&line);
+
+        if line.trim().is_empty() || !is_incomplete(&entry) {
+            let entry = std::mem::take(&mut entry);
+            if entry.trim() == ":quit" {
+                return Ok(());
+            }
+            let output = session.run(&entry);
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+        }
+        print!("{}", if entry.is_empty() { "> " } else { ". " });
+        stdout.flush()?;
+    }
+    println!();
+    Ok(())
+}
+
+/// Whether `input` has more opening than closing brackets.
+fn is_incomplete(input: &str) -> bool {
+    let depth = Lexer::new(input).fold(0, |depth, token| match token.kind {
+        T!['('] | T!['{'] => depth + 1,
+        T![')'] | T!['}'] => depth - 1,
+        _ => depth,
+    });
+    depth > 0
+}
+
+/// The state that is kept between entries.
+struct Session {
+    env: Env,
+    interpreter: Interpreter,
+    last: String,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            env: Env::with_prelude(),
+            interpreter: Interpreter::new(),
+            last: String::new(),
+        }
+    }
+
+    /// Run one entry and return what to print for it.
+    fn run(&mut self, entry: &str) -> String {
+        let entry = entry.trim();
+        let (command, rest) = match entry.strip_prefix(':') {
+            Some(command) => command
+                .split_once(char::is_whitespace)
+                .map_or((command, ""), |(command, rest)| (command, rest.trim())),
+            None if entry.is_empty() => return String::new(),
+            None => return self.eval(entry).unwrap_or_else(|error| error),
+        };
+
+        let input = if rest.is_empty() {
+            self.last.clone()
+        } else {
+            rest.to_string()
+        };
+        match command {
+            "let" => self.define(rest),
+            "tokens" => tokens(&input),
+            "ast" => parse(&input).map_or_else(|error| error, |expr| sexpr::write(&expr)),
+            "type" => self.ty(&input),
+            "help" => HELP.to_string(),
+            _ => format!("unknown command `:{}`, see `:help`", command),
+        }
+    }
+
+    fn eval(&mut self, input: &str) -> Result<String, String> {
+        self.last = input.to_string();
+        let value = self.value(input)?;
+        Ok(value.to_string())
+    }
+
+    fn value(&self, input: &str) -> Result<Value, String> {
+        let expr = parse(input)?;
+        self.interpreter
+            .eval(&expr, &self.env)
+            .map_err(|error| format!("error: {}", error))
+    }
+
+    fn define(&mut self, definition: &str) -> String {
+        let Some((name, input)) = definition.split_once('=') else {
+            return "expected `:let <name> = <expr>`".to_string();
+        };
+        let name = name.trim();
+        let tokens: Vec<_> = Lexer::new(name).map(|token| token.kind).collect();
+        if tokens != [T![ident], T![EOF]] {
+            return format!("`{}` is not a variable name", name);
+        }
+        if self.env.is_const(name) {
+            return format!("cannot assign to constant `{}`", name);
+        }
+
+        let input = input.trim();
+        self.last = input.to_string();
+        match self.value(input) {
+            Ok(value) => {
+                let output = format!("{} = {}", name, value);
+                self.env.set(name, value);
+                output
+            }
+            Err(error) => error,
+        }
+    }
+
+    fn ty(&self, input: &str) -> String {
+        let parse = cst::parse(input);
+        if !parse.errors.is_empty() {
+            return render_errors(input, parse.errors.iter().map(|e| (e.span, &e.message)));
+        }
+        let typing = typeck::check(&parse.root, input, &self.env);
+        if !typing.errors.is_empty() {
+            return render_errors(input, typing.errors.iter().map(|e| (e.span, &e.message)));
+        }
+        let mut out = typing
+            .ty
+            .map_or_else(|| "unknown".to_string(), |ty| ty.to_string());
+        for (name, ty) in &typing.variables {
+            write!(out, "\n  {}: {}", name, ty).unwrap();
+        }
+        out
+    }
+}
+
+/// Parse all of `input`, or render why it cannot be parsed.
+fn parse(input: &str) -> Result<Expr, String> {
+    let errors = cst::parse(input).errors;
+    if !errors.is_empty() {
+        return Err(render_errors(
+            input,
+            errors.iter().map(|error| (error.span, &error.message)),
+        ));
+    }
+    Parser::new(input)
+        .try_parse_expression(0)
+        .map_err(|errors| render_errors(input, errors.iter().map(|e| (e.span, &e.message))))
+}
+
+fn render_errors<'a>(input: &str, errors: impl Iterator<Item = (Span, &'a String)>) -> String {
+    errors
+        .map(|(span, message)| diagnostic::render(input, span, message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tokens(input: &str) -> String {
+    Lexer::new(input)
+        .map(|token| format!("{:?} `{}`", token, token.text(input)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continues_unclosed_brackets() {
+        assert!(is_incomplete("min(1,"));
+        assert!(is_incomplete("((1 + 2)"));
+        assert!(!is_incomplete("(1 + 2)"));
+        assert!(!is_incomplete("1 + 2)"));
+        assert!(!is_incomplete("\"(\""));
+    }
+
+    #[test]
+    fn keeps_bindings_between_entries() {
+        let mut session = Session::new();
+        assert_eq!(session.run(":let x = 2 * 3"), "x = 6");
+        assert_eq!(session.run(":let y = x + 0.5"), "y = 6.5");
+        assert_eq!(session.run("x * y"), "39.0");
+        assert_eq!(session.run(":type"), "float");
+        assert_eq!(session.run(":type z > x\n"), "bool\n  z: int");
+        assert_eq!(session.run(":let PI = 3"), "cannot assign to constant `PI`");
+        assert_eq!(session.run(":let 1 = 3"), "`1` is not a variable name");
+    }
+
+    #[test]
+    fn shows_lexer_and_parser_output() {
+        let mut session = Session::new();
+        assert_eq!(session.run("-2!"), "-2");
+        assert_eq!(session.run(":ast"), "(prefix Minus (postfix Bang (int 2)))");
+        assert_eq!(
+            session.run(":tokens f(x)"),
+            "Identifier - <0, 1> `f`\nLParen - <1, 2> `(`\nIdentifier - <2, 3> `x`\nRParen - <3, 4> `)`\nEof - <4, 4> ``"
+        );
+    }
+
+    #[test]
+    fn renders_errors() {
+        let mut session = Session::new();
+        assert_eq!(
+            session.run("1 +"),
+            "error: Unknown start of expression: `<EOF>`\n --> 1:4\n  |\n1 | 1 +\n  |    ^"
+        );
+        assert_eq!(session.run("1 / 0"), "error: division by zero");
+        assert_eq!(session.run(":nope"), "unknown command `:nope`, see `:help`");
+    }
+}
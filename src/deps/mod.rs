@@ -0,0 +1,369 @@
+//! The names an expression reads and the order to evaluate expressions in.
+//!
+//! [`free_variables`] and [`called_functions`] list the identifiers of an
+//! expression with their spans. The AST does not know where its nodes came
+//! from, so their spans are into the expression as [`pretty::print`] writes
+//! it. [`free_variables_in`] and [`called_functions_in`] work on the CST
+//! instead, with spans into the source it was parsed from. The grammar has
+//! no binders, so every variable is free, including the constants of an
+//! [`Env`].
+//!
+//! A [`DependencyGraph`] relates named formulas, like the cells of a
+//! spreadsheet, by the variables they read. Its [`order`] has each formula
+//! after the formulas it reads, or is the [`Cycle`] that prevents that.
+//!
+//! [`Env`]: crate::eval::Env
+//! [`order`]: DependencyGraph::order
+
+use crate::{
+    lexer::Span,
+    parser::{
+        ast::Expr,
+        cst::{self, SyntaxNode},
+        event::NodeKind,
+        pretty,
+    },
+};
+use std::{collections::HashMap, fmt};
+
+/// Every use of a variable in `expr`, from left to right, with its span in
+/// `pretty::print(expr)`.
+#[must_use]
+pub fn free_variables(expr: &Expr) -> Vec<(String, Span)> {
+    let input = pretty::print(expr);
+    free_variables_in(&cst::parse(&input).root, &input)
+}
+
+/// The name of every function call in `expr`, from left to right, with its
+/// span in `pretty::print(expr)`.
+#[must_use]
+pub fn called_functions(expr: &Expr) -> Vec<(String, Span)> {
+    let input = pretty::print(expr);
+    called_functions_in(&cst::parse(&input).root, &input)
+}
+
+/// Every use of a variable in `root`, which was parsed from `input`,
+/// in source order.
+#[must_use]
+pub fn free_variables_in(root: &SyntaxNode, input: &str) -> Vec<(String, Span)> {
+    let mut names = Vec::new();
+    collect(root, input, NodeKind::Ident, &mut names);
+    names
+}
+
+/// The name of every function call in `root`, in source order.
+#[must_use]
+pub fn called_functions_in(root: &SyntaxNode, input: &str) -> Vec<(String, Span)> {
+    let mut names = Vec::new();
+    collect(root, input, NodeKind::FnCall, &mut names);
+    names
+}
+
+fn collect(node: &SyntaxNode, input: &str, kind: NodeKind, names: &mut Vec<(String, Span)>) {
+    if node.kind == kind {
+        // The name is the first token of both identifiers and calls.
+        if let Some(token) = node.child_tokens().find(|token| !token.kind.is_trivia()) {
+            names.push((token.text(input).to_string(), token.span));
+        }
+    }
+    for child in node.child_nodes() {
+        collect(child, input, kind, names);
+    }
+}
+
+/// Named formulas and the variables they read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    names: Vec<String>,
+    indices: HashMap<String, usize>,
+    /// The free variables of each formula.
+    reads: Vec<Vec<(String, Span)>>,
+}
+
+impl DependencyGraph {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the formula `name`, parsed from `input`, or replace it.
+    pub fn insert(&mut self, name: impl Into<String>, root: &SyntaxNode, input: &str) {
+        self.insert_reads(name.into(), free_variables_in(root, input));
+    }
+
+    /// Add the formula `name` or replace it. The spans of its reads are into
+    /// `pretty::print(expr)`.
+    pub fn insert_expr(&mut self, name: impl Into<String>, expr: &Expr) {
+        self.insert_reads(name.into(), free_variables(expr));
+    }
+
+    fn insert_reads(&mut self, name: String, reads: Vec<(String, Span)>) {
+        if let Some(&index) = self.indices.get(&name) {
+            self.reads[index] = reads;
+        } else {
+            self.indices.insert(name.clone(), self.names.len());
+            self.names.push(name);
+            self.reads.push(reads);
+        }
+    }
+
+    /// The variables the formula `name` reads, or `None` if there is no such
+    /// formula. Variables without a formula are inputs of the graph.
+    #[must_use]
+    pub fn reads(&self, name: &str) -> Option<&[(String, Span)]> {
+        self.indices
+            .get(name)
+            .map(|&index| self.reads[index].as_slice())
+    }
+
+    /// The formulas that read `name`, in the order they were inserted.
+    #[must_use]
+    pub fn readers(&self, name: &str) -> Vec<&str> {
+        self.names
+            .iter()
+            .zip(&self.reads)
+            .filter(|(_, reads)| reads.iter().any(|(read, _)| read == name))
+            .map(|(reader, _)| reader.as_str())
+            .collect()
+    }
+
+    /// All formulas, each after the formulas it reads. Otherwise,
+    /// formulas keep the order they were inserted in.
+    ///
+    /// # Errors
+    /// If formulas read each other in a cycle.
+    pub fn order(&self) -> Result<Vec<&str>, Cycle> {
+        let mut states = vec![State::New; self.names.len()];
+        let mut order = Vec::with_capacity(self.names.len());
+        for index in 0..self.names.len() {
+            self.visit(index, &mut states, &mut order)?;
+        }
+        Ok(order
+            .into_iter()
+            .map(|index| self.names[index].as_str())
+            .collect())
+    }
+
+    /// Depth-first search that adds a formula to `order` once all formulas
+    /// it reads are. The stack holds the formulas being visited, each with
+    /// the number of its reads followed so far, so that the last of those
+    /// is the read of the formula above it.
+    fn visit(
+        &self,
+        root: usize,
+        states: &mut [State],
+        order: &mut Vec<usize>,
+    ) -> Result<(), Cycle> {
+        if states[root] != State::New {
+            return Ok(());
+        }
+        states[root] = State::Visiting;
+        let mut stack = vec![(root, 0)];
+
+        while let Some(&(index, followed)) = stack.last() {
+            let Some((name, _)) = self.reads[index].get(followed) else {
+                states[index] = State::Done;
+                order.push(index);
+                stack.pop();
+                continue;
+            };
+            stack.last_mut().unwrap().1 += 1;
+            let Some(&read) = self.indices.get(name) else {
+                continue;
+            };
+            match states[read] {
+                State::Done => {}
+                State::Visiting => {
+                    let start = stack.iter().position(|(step, _)| *step == read).unwrap();
+                    let steps = stack[start..]
+                        .iter()
+                        .map(|&(step, followed)| {
+                            (self.names[step].clone(), self.reads[step][followed - 1].1)
+                        })
+                        .collect();
+                    return Err(Cycle { steps });
+                }
+                State::New => {
+                    states[read] = State::Visiting;
+                    stack.push((read, 0));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    New,
+    Visiting,
+    Done,
+}
+
+/// Formulas that read each other in a circle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    /// Each formula of the cycle with the span of its read of the next
+    /// formula, within its own input. The last one reads the first.
+    pub steps: Vec<(String, Span)>,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dependency cycle: ")?;
+        for (i, (name, span)) in self.steps.iter().enumerate() {
+            let next = &self.steps[(i + 1) % self.steps.len()].0;
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(
+                f,
+                "`{}` reads `{}` at <{}, {}>",
+                name, next, span.start, span.end
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Cycle {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{cst, Parser};
+
+    fn span(start: u32, end: u32) -> Span {
+        Span { start, end }
+    }
+
+    fn graph(formulas: &[(&str, &str)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        for (name, input) in formulas {
+            graph.insert(*name, &cst::parse(input).root, input);
+        }
+        graph
+    }
+
+    #[test]
+    fn names_with_spans() {
+        let input = "f(x, PI * x) + g() / -y!";
+        let root = cst::parse(input).root;
+        assert_eq!(
+            free_variables_in(&root, input),
+            [
+                ("x".to_string(), span(2, 3)),
+                ("PI".to_string(), span(5, 7)),
+                ("x".to_string(), span(10, 11)),
+                ("y".to_string(), span(22, 23)),
+            ]
+        );
+        assert_eq!(
+            called_functions_in(&root, input),
+            [
+                ("f".to_string(), span(0, 1)),
+                ("g".to_string(), span(15, 16))
+            ]
+        );
+    }
+
+    #[test]
+    fn names_in_expressions() {
+        // Printed as `f(x, PI * x) + g() / -y!`.
+        let expr = Parser::new("f( x,PI*x )+g()/(-y!)").parse_expression(0);
+        let names = |names: Vec<(String, Span)>| -> Vec<(String, u32)> {
+            names
+                .into_iter()
+                .map(|(name, span)| (name, span.start))
+                .collect()
+        };
+        assert_eq!(
+            names(free_variables(&expr)),
+            [
+                ("x".to_string(), 2),
+                ("PI".to_string(), 5),
+                ("x".to_string(), 10),
+                ("y".to_string(), 22),
+            ]
+        );
+        assert_eq!(
+            names(called_functions(&expr)),
+            [("f".to_string(), 0), ("g".to_string(), 15)]
+        );
+
+        let mut graph = DependencyGraph::new();
+        graph.insert_expr("b", &Parser::new("a*2").parse_expression(0));
+        graph.insert_expr("a", &Parser::new("(1)+b").parse_expression(0));
+        assert_eq!(
+            graph.order().unwrap_err().to_string(),
+            "dependency cycle: `b` reads `a` at <0, 1>, `a` reads `b` at <4, 5>"
+        );
+    }
+
+    #[test]
+    fn orders_by_dependencies() {
+        let graph = graph(&[
+            ("total", "net + tax"),
+            ("tax", "net * rate"),
+            ("net", "price * count"),
+            ("rate", "0.2"),
+        ]);
+        assert_eq!(graph.order(), Ok(vec!["net", "rate", "tax", "total"]));
+        assert_eq!(
+            graph.reads("tax"),
+            Some(
+                &[
+                    ("net".to_string(), span(0, 3)),
+                    ("rate".to_string(), span(6, 10))
+                ][..]
+            )
+        );
+        assert_eq!(graph.reads("price"), None);
+        assert_eq!(graph.readers("net"), ["total", "tax"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let mut graph = graph(&[("a", "1 + b"), ("b", "c * 2"), ("c", "a"), ("d", "2")]);
+        let cycle = graph.order().unwrap_err();
+        assert_eq!(
+            cycle.steps,
+            [
+                ("a".to_string(), span(4, 5)),
+                ("b".to_string(), span(0, 1)),
+                ("c".to_string(), span(0, 1)),
+            ]
+        );
+        assert_eq!(
+            cycle.to_string(),
+            "dependency cycle: `a` reads `b` at <4, 5>, `b` reads `c` at <0, 1>, \
+             `c` reads `a` at <0, 1>"
+        );
+
+        // Replacing a formula breaks the cycle.
+        graph.insert("c", &cst::parse("d").root, "d");
+        assert_eq!(graph.order(), Ok(vec!["d", "c", "b", "a"]));
+
+        let graph = self::graph(&[("x", "x + 1")]);
+        assert_eq!(
+            graph.order().unwrap_err().steps,
+            [("x".to_string(), span(0, 1))]
+        );
+    }
+
+    #[test]
+    fn long_chains() {
+        let mut graph = DependencyGraph::new();
+        let count = 100_000;
+        for i in 0..count {
+            let input = format!("x{}", i + 1);
+            graph.insert(format!("x{i}"), &cst::parse(&input).root, &input);
+        }
+        let order = graph.order().unwrap();
+        assert_eq!(order.len(), count);
+        assert_eq!(order[0], format!("x{}", count - 1));
+
+        graph.insert(format!("x{count}"), &cst::parse("x0").root, "x0");
+        assert_eq!(graph.order().unwrap_err().steps.len(), count + 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Showing errors in the context of the input they were found in.
+
+use crate::lexer::Span;
+use std::fmt::Write;
+
+/// The 1-based line and column of the byte `offset` in `input`.
+/// Columns count characters, not bytes.
+#[must_use]
+pub fn line_col(input: &str, offset: u32) -> (usize, usize) {
+    let before = &input[..offset as usize];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = before.matches('\n').count() + 1;
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Render `message` with the line of `input` that `span` starts in,
+/// underlining the part of the line that the span covers:
+///
+/// ```text
+/// error: expected `int`, found `string`
+///  --> 1:5
+///   |
+/// 1 | 1 + "a"
+///   |     ^^^
+/// ```
+///
+/// An empty span, like the one of an unexpected end of input,
+/// is underlined with a single `^`.
+#[must_use]
+pub fn render(input: &str, span: Span, message: &str) -> String {
+    render_location(None, input, span, message)
+}
+
+/// Like [`render`], but with the name of the file that `input` was read
+/// from in front of the line and column, as in `--> main.expr:1:5`.
+#[must_use]
+pub fn render_in(file: &str, input: &str, span: Span, message: &str) -> String {
+    render_location(Some(file), input, span, message)
+}
+
+fn render_location(file: Option<&str>, input: &str, span: Span, message: &str) -> String {
+    let (line, column) = line_col(input, span.start);
+    let start = span.start as usize;
+    let line_start = input[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let text = input[line_start..].lines().next().unwrap_or("");
+    let end = (span.end as usize).clamp(start, line_start + text.len());
+    let underlined = input[start..end].chars().count().max(1);
+
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    let mut out = String::new();
+    writeln!(out, "error: {message}").unwrap();
+    write!(out, "{gutter}--> ").unwrap();
+    if let Some(file) = file {
+        write!(out, "{file}:").unwrap();
+    }
+    writeln!(out, "{line}:{column}").unwrap();
+    writeln!(out, "{gutter} |").unwrap();
+    writeln!(out, "{number} | {text}").unwrap();
+    write!(
+        out,
+        "{} | {}{}",
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(underlined)
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_and_columns() {
+        let input = "a +\n  bä + c";
+        assert_eq!(line_col(input, 0), (1, 1));
+        assert_eq!(line_col(input, 3), (1, 4));
+        assert_eq!(line_col(input, 6), (2, 3));
+        assert_eq!(line_col(input, 9), (2, 5));
+    }
+
+    #[test]
+    fn underlines_the_span() {
+        assert_eq!(
+            render(r#"1 + "a""#, Span { start: 4, end: 7 }, "mismatch"),
+            "error: mismatch\n --> 1:5\n  |\n1 | 1 + \"a\"\n  |     ^^^"
+        );
+        // Spans across lines are cut off at the end of the first one.
+        assert_eq!(
+            render("x\n(1 +\n 2", Span { start: 2, end: 9 }, "oops"),
+            "error: oops\n --> 2:1\n  |\n2 | (1 +\n  | ^^^^"
+        );
+        assert_eq!(
+            render_in("a.expr", "f(", Span { start: 2, end: 2 }, "eof"),
+            "error: eof\n --> a.expr:1:3\n  |\n1 | f(\n  |   ^"
+        );
+    }
+}
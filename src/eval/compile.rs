@@ -0,0 +1,339 @@
+//! Compile expressions into closures for repeated evaluation.
+//!
+//! [`compile`] walks the tree once. Variables are numbered in the order
+//! they first appear and read from a slice of values by index, constants are
+//! inlined and the overloads of called functions are looked up in advance.
+//! What remains is a tree of boxed closures that [`CompiledExpr::eval`] calls
+//! without touching a name again.
+//!
+//! Evaluating numbers and booleans does not allocate. Strings are cloned
+//! out of their slots. A call evaluates up to four arguments into an array
+//! on the stack, only calls with more collect them into a `Vec`. Each call
+//! remembers the overload it picked for the types of its last arguments,
+//! so calling it with the same types again does not compare signatures.
+
+use super::{
+    function, infix, literal, postfix, prefix, Env, Function, OverflowPolicy, RuntimeError, Type,
+    Value,
+};
+use crate::{parser::ast::Expr, T};
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The most arguments a compiled call evaluates into an array on the stack.
+const MAX_STACK_ARGS: usize = 4;
+
+type Closure = Box<dyn Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync>;
+
+/// An expression compiled by [`compile`] or [`compile_with`].
+pub struct CompiledExpr {
+    slots: Vec<String>,
+    code: Closure,
+}
+
+impl CompiledExpr {
+    /// The names of the variables, in the order
+    /// in which [`CompiledExpr::eval`] expects their values.
+    #[must_use]
+    pub fn slots(&self) -> &[String] {
+        &self.slots
+    }
+
+    #[must_use]
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot == name)
+    }
+
+    /// Look up the values of all variables in `env`, in slot order.
+    ///
+    /// # Errors
+    /// If `env` does not define one of the variables.
+    pub fn bind(&self, env: &Env) -> Result<Vec<Value>, RuntimeError> {
+        self.slots
+            .iter()
+            .map(|name| {
+                env.get(name)
+                    .cloned()
+                    .ok_or_else(|| RuntimeError::UnboundVariable(name.clone()))
+            })
+            .collect()
+    }
+
+    /// Evaluate the expression with `values[i]` as the value of the
+    /// variable in slot `i`.
+    ///
+    /// # Errors
+    /// If evaluating the expression fails.
+    ///
+    /// # Panics
+    /// If the number of values differs from the number of slots.
+    pub fn eval(&self, values: &[Value]) -> Result<Value, RuntimeError> {
+        assert_eq!(
+            values.len(),
+            self.slots.len(),
+            "expected a value for each of {:?}",
+            self.slots
+        );
+        (self.code)(values)
+    }
+}
+
+impl fmt::Debug for CompiledExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompiledExpr")
+            .field("slots", &self.slots)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compile `expr` without constants or host functions.
+/// Every identifier becomes a variable.
+#[must_use]
+pub fn compile(expr: &Expr) -> CompiledExpr {
+    compile_with(expr, &Env::new())
+}
+
+/// Compile `expr` against the constants and functions of `env`.
+///
+/// Constants are inlined. The variables of `env` are not,
+/// since they are expected to change between evaluations.
+#[must_use]
+pub fn compile_with(expr: &Expr, env: &Env) -> CompiledExpr {
+    compile_with_overflow(expr, env, OverflowPolicy::default())
+}
+
+/// Like [`compile_with`], but integer overflow is handled with `overflow`
+/// instead of being an error.
+#[must_use]
+pub fn compile_with_overflow(expr: &Expr, env: &Env, overflow: OverflowPolicy) -> CompiledExpr {
+    let mut compiler = Compiler {
+        env,
+        overflow,
+        slots: Vec::new(),
+    };
+    let code = compiler.compile(expr);
+    CompiledExpr {
+        slots: compiler.slots,
+        code,
+    }
+}
+
+struct Compiler<'env> {
+    env: &'env Env,
+    overflow: OverflowPolicy,
+    slots: Vec<String>,
+}
+
+impl Compiler<'_> {
+    fn compile(&mut self, expr: &Expr) -> Closure {
+        match expr {
+            Expr::Literal(lit) => match literal(lit, self.overflow) {
+                Ok(value) => Box::new(move |_| Ok(value.clone())),
+                Err(error) => Box::new(move |_| Err(error.clone())),
+            },
+            Expr::Ident(name) if self.env.is_const(name) => {
+                let value = self.env.get(name).cloned().unwrap();
+                Box::new(move |_| Ok(value.clone()))
+            }
+            Expr::Ident(name) => {
+                let slot = self.slot(name);
+                Box::new(move |values| Ok(values[slot].clone()))
+            }
+            Expr::FnCall { fn_name, args } => {
+                let site = CallSite {
+                    name: fn_name.clone(),
+                    overloads: self.env.functions(fn_name).to_vec(),
+                    overflow: self.overflow,
+                    cache: AtomicU64::new(0),
+                };
+                let args: Vec<Closure> = args.iter().map(|arg| self.compile(arg)).collect();
+                match args.len() {
+                    0 => call_on_stack::<0>(site, args),
+                    1 => call_on_stack::<1>(site, args),
+                    2 => call_on_stack::<2>(site, args),
+                    3 => call_on_stack::<3>(site, args),
+                    4 => call_on_stack::<MAX_STACK_ARGS>(site, args),
+                    _ => Box::new(move |values| {
+                        let mut args: Vec<Value> = args
+                            .iter()
+                            .map(|arg| arg(values))
+                            .collect::<Result<_, _>>()?;
+                        site.call(&mut args)
+                    }),
+                }
+            }
+            Expr::PrefixOp { op, expr } => {
+                let (op, overflow) = (*op, self.overflow);
+                let operand = self.compile(expr);
+                Box::new(move |values| prefix(op, operand(values)?, overflow))
+            }
+            Expr::InfixOp { op, lhs, rhs } => {
+                let (op, overflow) = (*op, self.overflow);
+                let lhs = self.compile(lhs);
+                let rhs = self.compile(rhs);
+                match op {
+                    T![&&] => Box::new(move |values| match lhs(values)? {
+                        Value::Bool(false) => Ok(Value::Bool(false)),
+                        lhs => infix(op, &lhs, &rhs(values)?, overflow),
+                    }),
+                    T![||] => Box::new(move |values| match lhs(values)? {
+                        Value::Bool(true) => Ok(Value::Bool(true)),
+                        lhs => infix(op, &lhs, &rhs(values)?, overflow),
+                    }),
+                    _ => Box::new(move |values| infix(op, &lhs(values)?, &rhs(values)?, overflow)),
+                }
+            }
+            Expr::PostfixOp { op, expr } => {
+                let (op, overflow) = (*op, self.overflow);
+                let operand = self.compile(expr);
+                Box::new(move |values| postfix(op, operand(values)?, overflow))
+            }
+        }
+    }
+
+    fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slots.iter().position(|slot| slot == name) {
+            slot
+        } else {
+            self.slots.push(name.to_string());
+            self.slots.len() - 1
+        }
+    }
+}
+
+/// A call with `N` arguments, which are evaluated into an array.
+fn call_on_stack<const N: usize>(site: CallSite, args: Vec<Closure>) -> Closure {
+    Box::new(move |values| {
+        // Placeholders that do not allocate, each is overwritten.
+        let mut buffer: [Value; N] = std::array::from_fn(|_| Value::Bool(false));
+        for (value, arg) in buffer.iter_mut().zip(&args) {
+            *value = arg(values)?;
+        }
+        site.call(&mut buffer)
+    })
+}
+
+/// A call of a host function.
+struct CallSite {
+    name: String,
+    overloads: Vec<Function>,
+    overflow: OverflowPolicy,
+    /// The types of the arguments of the last call, two bits each, followed
+    /// by a byte with the index of the overload picked for them plus one.
+    /// Zero until the first call.
+    cache: AtomicU64,
+}
+
+impl CallSite {
+    fn call(&self, args: &mut [Value]) -> Result<Value, RuntimeError> {
+        let key = type_key(args);
+        let cached = self.cache.load(Ordering::Relaxed);
+        let index = match key {
+            Some(key) if cached != 0 && cached >> 8 == key => (cached & 0xff) as usize - 1,
+            _ => {
+                let index = function::resolve(&self.name, &self.overloads, args)?;
+                if let (Some(key), Ok(tag)) = (key, u8::try_from(index + 1)) {
+                    self.cache
+                        .store(key << 8 | u64::from(tag), Ordering::Relaxed);
+                }
+                index
+            }
+        };
+        function::invoke(&self.name, &self.overloads[index], args, self.overflow)
+    }
+}
+
+/// The types of `args` packed into the low bits,
+/// unless there are too many to fit next to the overload index.
+fn type_key(args: &[Value]) -> Option<u64> {
+    if args.len() > 28 {
+        return None;
+    }
+    Some(args.iter().fold(0, |key, arg| {
+        key << 2
+            | match arg.ty() {
+                Type::Int => 0,
+                Type::Float => 1,
+                Type::Str => 2,
+                Type::Bool => 3,
+            }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::Interpreter, parser::Parser};
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    #[test]
+    fn variables_get_slots_in_order_of_appearance() {
+        let compiled = compile(&parse("b * x + a * x - b"));
+        assert_eq!(compiled.slots(), ["b", "x", "a"]);
+        assert_eq!(compiled.slot("a"), Some(2));
+        assert_eq!(compiled.slot("c"), None);
+
+        let values = [Value::Int(2), Value::Int(10), Value::Int(3)];
+        assert_eq!(compiled.eval(&values), Ok(Value::Int(48)));
+    }
+
+    #[test]
+    fn constants_are_inlined() {
+        let compiled = compile_with(&parse("2 * PI * r"), &Env::with_prelude());
+        assert_eq!(compiled.slots(), ["r"]);
+        assert_eq!(
+            compiled.eval(&[Value::Float(0.5)]),
+            Ok(Value::Float(std::f64::consts::PI))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a value for each of")]
+    fn missing_values_panic() {
+        let _ = compile(&parse("x + y")).eval(&[Value::Int(1)]);
+    }
+
+    /// The compiled closures agree with the tree-walker,
+    /// including on the errors.
+    #[test]
+    fn agrees_with_interpreter() {
+        let mut env = Env::with_prelude();
+        env.set("x", 3);
+        env.set("y", 2.5);
+        env.set("s", "text");
+
+        for input in [
+            "x * x - 2 * x + 1",
+            "x ^ 2 ^ 2 / y",
+            "-x! + abs(-x)",
+            "min(x, y) + max(x, 7) + sqrt(x)",
+            r#"s + "!" == "text!" && !(x < y)"#,
+            "x > 5 && 1",
+            "x < 5 || 1",
+            "x < 5 && 1",
+            "x / 0",
+            "(-x)!",
+            "x + s",
+            "nope(x)",
+            "sqrt(x, y)",
+            "nope(x / 0)",
+            "x ^ 100",
+            "9999999999999999999",
+        ] {
+            let expr = parse(input);
+            let compiled = compile_with(&expr, &env);
+            let values = compiled.bind(&env);
+            let result = values.and_then(|values| compiled.eval(&values));
+            assert_eq!(
+                result,
+                Interpreter::new().eval(&expr, &env),
+                "evaluating `{input}`"
+            );
+        }
+    }
+}
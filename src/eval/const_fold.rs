@@ -0,0 +1,459 @@
+//! Constant folding.
+//!
+//! [`ConstantFolder`] replaces subtrees without variables or calls by the
+//! literal they evaluate to, applies algebraic identities like `x * 1 => x`
+//! and drops operands of `&&` and `||` that cannot change the result.
+//!
+//! The folded tree evaluates exactly like the original one: to the same
+//! value, or to the same error. Subtrees that fail, such as `1 / 0`, are left
+//! alone, and so are results without a literal syntax: booleans, NaN and
+//! infinities. An identity is only applied if the type of its operand is
+//! known, since `x + 0 => x` holds for an integer `x`, but not for `-0.0`
+//! and not for a string, for which `x + 0` is an error. The types of
+//! variables are unknown unless declared with [`ConstantFolder::assume`].
+
+use super::{infix, literal, postfix, prefix, OverflowPolicy, Type, Value};
+use crate::{
+    lexer::TokenKind,
+    parser::{
+        ast::{Expr, Lit},
+        fold::{self, Fold},
+    },
+    T,
+};
+use std::{collections::HashMap, mem};
+
+/// Constants are evaluated like the [`Interpreter`](super::Interpreter)
+/// does by default.
+const OVERFLOW: OverflowPolicy = OverflowPolicy::Error;
+
+/// Fold `expr` without knowing the types of any variables.
+#[must_use]
+pub fn fold_constants(expr: Expr) -> Expr {
+    ConstantFolder::new().fold_expr(expr)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConstantFolder {
+    types: HashMap<String, Type>,
+    /// What is known about the expression folded last. Each node is
+    /// simplified and evaluated with what is known about its children,
+    /// rather than by walking them again.
+    folded: Folded,
+}
+
+/// What is known about a folded expression.
+#[derive(Debug, Clone, Default)]
+struct Folded {
+    /// The value, if the expression has neither variables nor calls
+    /// and evaluates without an error.
+    value: Option<Value>,
+    /// The type of the value the expression evaluates to,
+    /// if it evaluates at all.
+    ty: Option<Type>,
+    /// For `!b`, the type of `b`.
+    negated: Option<Type>,
+}
+
+impl ConstantFolder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare that the variable `name` holds values of type `ty`.
+    pub fn assume(&mut self, name: impl Into<String>, ty: Type) {
+        self.types.insert(name.into(), ty);
+    }
+
+    /// Fold `expr` and take what is known about the result.
+    fn fold(&mut self, expr: Expr) -> (Expr, Folded) {
+        let expr = self.fold_expr(expr);
+        (expr, mem::take(&mut self.folded))
+    }
+
+    /// Keep the result of folding a node for its parent, and replace
+    /// the node by a literal if it is constant.
+    fn finish(&mut self, (expr, folded): (Expr, Folded)) -> Expr {
+        let expr = evaluate(expr, folded.value.as_ref());
+        self.folded = folded;
+        expr
+    }
+
+    fn simplify_prefix_op(op: TokenKind, (expr, operand): (Expr, Folded)) -> (Expr, Folded) {
+        let value = operand
+            .value
+            .clone()
+            .and_then(|value| prefix(op, value, OVERFLOW).ok());
+        match (op, expr) {
+            // `!!b => b` and `+x => x`
+            (T![!], Expr::PrefixOp { op: T![!], expr }) if operand.negated == Some(Type::Bool) => {
+                let folded = Folded {
+                    value,
+                    ty: Some(Type::Bool),
+                    negated: None,
+                };
+                (*expr, folded)
+            }
+            (T![+], expr) if operand.ty.is_some_and(is_number) => (expr, operand),
+            (op, expr) => {
+                let folded = if op == T![!] {
+                    Folded {
+                        value,
+                        ty: Some(Type::Bool),
+                        negated: operand.ty,
+                    }
+                } else {
+                    Folded {
+                        value,
+                        ty: operand.ty.filter(|ty| is_number(*ty)),
+                        negated: None,
+                    }
+                };
+                let expr = Expr::PrefixOp {
+                    op,
+                    expr: Box::new(expr),
+                };
+                (expr, folded)
+            }
+        }
+    }
+
+    fn simplify_infix_op(
+        op: TokenKind,
+        (lhs, lhs_folded): (Expr, Folded),
+        (rhs, rhs_folded): (Expr, Folded),
+    ) -> (Expr, Folded) {
+        let lhs_type = lhs_folded.ty;
+        let rhs_type = rhs_folded.ty;
+
+        match op {
+            // A constant left operand that decides the result is the result.
+            T![&&] if lhs_folded.value == Some(Value::Bool(false)) => return (lhs, lhs_folded),
+            T![||] if lhs_folded.value == Some(Value::Bool(true)) => return (lhs, lhs_folded),
+            // A neutral constant on either side leaves the other operand,
+            // as long as that is known to be a boolean.
+            T![&&] | T![||] => {
+                let neutral = Some(Value::Bool(op == T![&&]));
+                if rhs_type == Some(Type::Bool) && lhs_folded.value == neutral {
+                    return (rhs, rhs_folded);
+                }
+                if lhs_type == Some(Type::Bool) && rhs_folded.value == neutral {
+                    return (lhs, lhs_folded);
+                }
+            }
+            _ => {}
+        }
+
+        // Identities with a neutral literal on the right ...
+        #[allow(clippy::float_cmp)]
+        let right_identity = match (op, &rhs) {
+            (T![+], Expr::Literal(Lit::Int(0))) => lhs_type == Some(Type::Int),
+            (T![-] | T![*] | T![/] | T![^], Expr::Literal(Lit::Int(i))) => {
+                let neutral = i128::from(op != T![-]);
+                *i == neutral && lhs_type.is_some_and(is_number)
+            }
+            (T![-], Expr::Literal(Lit::Float(fl))) => {
+                *fl == 0.0 && fl.is_sign_positive() && lhs_type == Some(Type::Float)
+            }
+            (T![*] | T![/] | T![^], Expr::Literal(Lit::Float(fl))) => {
+                *fl == 1.0 && lhs_type == Some(Type::Float)
+            }
+            (T![+], Expr::Literal(Lit::Str(s))) => s.is_empty() && lhs_type == Some(Type::Str),
+            _ => false,
+        };
+        if right_identity {
+            return (lhs, lhs_folded);
+        }
+
+        // ... and on the left.
+        #[allow(clippy::float_cmp)]
+        let left_identity = match (op, &lhs) {
+            (T![+], Expr::Literal(Lit::Int(0))) => rhs_type == Some(Type::Int),
+            (T![*], Expr::Literal(Lit::Int(1))) => rhs_type.is_some_and(is_number),
+            (T![*], Expr::Literal(Lit::Float(fl))) => *fl == 1.0 && rhs_type == Some(Type::Float),
+            (T![+], Expr::Literal(Lit::Str(s))) => s.is_empty() && rhs_type == Some(Type::Str),
+            _ => false,
+        };
+        if left_identity {
+            return (rhs, rhs_folded);
+        }
+
+        // Like the interpreter, the right operand is only evaluated
+        // if the left one does not decide the result.
+        let value = match (op, lhs_folded.value) {
+            (T![&&], Some(Value::Bool(false))) => Some(Value::Bool(false)),
+            (T![||], Some(Value::Bool(true))) => Some(Value::Bool(true)),
+            (_, Some(lhs)) => rhs_folded
+                .value
+                .and_then(|rhs| infix(op, &lhs, &rhs, OVERFLOW).ok()),
+            (_, None) => None,
+        };
+        let ty = match op {
+            T![==] | T![!=] | T![<] | T![<=] | T![>] | T![>=] | T![&&] | T![||] => Some(Type::Bool),
+            _ => match (lhs_type, rhs_type) {
+                (Some(Type::Int), Some(Type::Int)) => Some(Type::Int),
+                (Some(Type::Str), Some(Type::Str)) if op == T![+] => Some(Type::Str),
+                (Some(lhs), Some(rhs)) if is_number(lhs) && is_number(rhs) => Some(Type::Float),
+                _ => None,
+            },
+        };
+        let expr = Expr::InfixOp {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        let folded = Folded {
+            value,
+            ty,
+            negated: None,
+        };
+        (expr, folded)
+    }
+}
+
+impl Fold for ConstantFolder {
+    fn fold_lit(&mut self, lit: Lit) -> Lit {
+        let ty = match lit {
+            Lit::Int(_) | Lit::BigInt(_) => Type::Int,
+            Lit::Float(_) => Type::Float,
+            Lit::Str(_) => Type::Str,
+        };
+        self.folded = Folded {
+            value: literal(&lit, OVERFLOW).ok(),
+            ty: Some(ty),
+            negated: None,
+        };
+        lit
+    }
+
+    fn fold_ident(&mut self, name: String) -> Expr {
+        self.folded = Folded {
+            ty: self.types.get(&name).copied(),
+            ..Folded::default()
+        };
+        Expr::Ident(name)
+    }
+
+    fn fold_fn_call(&mut self, fn_name: String, args: Vec<Expr>) -> Expr {
+        let expr = fold::walk_fn_call(self, fn_name, args);
+        self.folded = Folded::default();
+        expr
+    }
+
+    fn fold_prefix_op(&mut self, op: TokenKind, expr: Expr) -> Expr {
+        let operand = self.fold(expr);
+        self.finish(Self::simplify_prefix_op(op, operand))
+    }
+
+    fn fold_infix_op(&mut self, op: TokenKind, lhs: Expr, rhs: Expr) -> Expr {
+        let lhs = self.fold(lhs);
+        let rhs = self.fold(rhs);
+        self.finish(Self::simplify_infix_op(op, lhs, rhs))
+    }
+
+    fn fold_postfix_op(&mut self, op: TokenKind, expr: Expr) -> Expr {
+        let (expr, operand) = self.fold(expr);
+        let folded = Folded {
+            value: operand
+                .value
+                .and_then(|value| postfix(op, value, OVERFLOW).ok()),
+            ty: Some(Type::Int),
+            negated: None,
+        };
+        let expr = Expr::PostfixOp {
+            op,
+            expr: Box::new(expr),
+        };
+        self.finish((expr, folded))
+    }
+}
+
+fn is_number(ty: Type) -> bool {
+    matches!(ty, Type::Int | Type::Float)
+}
+
+/// Replace `expr` by a literal, if its `value` is constant
+/// and can be written as one.
+fn evaluate(expr: Expr, value: Option<&Value>) -> Expr {
+    let negative = |lit: Lit| Expr::PrefixOp {
+        op: T![-],
+        expr: Box::new(Expr::Literal(lit)),
+    };
+
+    match value {
+        Some(Value::Int(i)) if *i >= 0 => Expr::Literal(Lit::Int((*i).into())),
+        // `i64::MIN` has no literal, its absolute value is out of range.
+        Some(Value::Int(i)) if *i != i64::MIN => negative(Lit::Int((-i).into())),
+        // Also covers `-0.0`.
+        Some(Value::Float(fl)) if fl.is_finite() && fl.is_sign_negative() => {
+            negative(Lit::Float(-fl))
+        }
+        Some(Value::Float(fl)) if fl.is_finite() => Expr::Literal(Lit::Float(*fl)),
+        Some(Value::Str(s)) => Expr::Literal(Lit::Str(s.clone())),
+        _ => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::{Env, Interpreter},
+        parser::{pretty, Parser},
+    };
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    fn fold(input: &str) -> String {
+        pretty::print(&fold_constants(parse(input)))
+    }
+
+    fn fold_with(folder: &ConstantFolder, input: &str) -> String {
+        pretty::print(&folder.clone().fold_expr(parse(input)))
+    }
+
+    #[test]
+    fn folds_literal_subtrees() {
+        assert_eq!(fold("2.0 / ((3.0 + 4.0) * (5.0 - 6.0)) * 7.0"), "-2.0");
+        assert_eq!(fold("1 + 2 * 3 + x"), "7 + x");
+        assert_eq!(fold("x + 1 + 2"), "x + 1 + 2");
+        assert_eq!(fold("f(2 ^ 10, 3!)"), "f(1024, 6)");
+        assert_eq!(fold("-(2 - 5)"), "3");
+        assert_eq!(fold("2 - 5"), "-3");
+        assert_eq!(fold(r#""a\"" + "\\b""#), r#""a\"\\b""#);
+        assert_eq!(fold("0.0 * -1.0"), "-0.0");
+    }
+
+    #[test]
+    fn keeps_what_fails_or_has_no_literal() {
+        assert_eq!(fold("1 / 0 + 2"), "1 / 0 + 2");
+        assert_eq!(fold("(0 - 1)!"), "(-1)!");
+        assert_eq!(fold("1.0 / 0.0"), "1.0 / 0.0");
+        assert_eq!(fold("0.0 / 0.0"), "0.0 / 0.0");
+        assert_eq!(fold("1 + 2 < 4"), "3 < 4");
+        assert_eq!(fold("-9223372036854775807 - 1"), "-9223372036854775807 - 1");
+        assert_eq!(fold(r#"1 + "a""#), r#"1 + "a""#);
+    }
+
+    #[test]
+    fn identities_need_known_types() {
+        assert_eq!(fold("x * 1 + 0"), "x * 1 + 0");
+
+        let mut folder = ConstantFolder::new();
+        folder.assume("i", Type::Int);
+        folder.assume("f", Type::Float);
+        folder.assume("s", Type::Str);
+        folder.assume("b", Type::Bool);
+
+        assert_eq!(fold_with(&folder, "i * 1 + 0"), "i");
+        assert_eq!(fold_with(&folder, "1 * (0 + i) - 0"), "i");
+        assert_eq!(fold_with(&folder, "f * 1 / 1.0 - 0.0"), "f");
+        assert_eq!(fold_with(&folder, "+i ^ 1"), "i");
+        assert_eq!(fold_with(&folder, r#"s + "" + ("" + s)"#), "s + s");
+        assert_eq!(fold_with(&folder, "!!b"), "b");
+        // `-0.0 + 0` is `0.0`, `-0.0 - -0.0` is `0.0`.
+        assert_eq!(fold_with(&folder, "f + 0"), "f + 0");
+        assert_eq!(fold_with(&folder, "f - -0.0"), "f - -0.0");
+        // `1.0 * i` is a float.
+        assert_eq!(fold_with(&folder, "1.0 * i"), "1.0 * i");
+        assert_eq!(fold_with(&folder, "!!i"), "!!i");
+    }
+
+    #[test]
+    fn short_circuits_constant_operands() {
+        let mut folder = ConstantFolder::new();
+        folder.assume("b", Type::Bool);
+
+        assert_eq!(fold_with(&folder, "1 > 2 && x"), "1 > 2");
+        assert_eq!(fold_with(&folder, "1 < 2 || x"), "1 < 2");
+        assert_eq!(fold_with(&folder, "1 < 2 && b"), "b");
+        assert_eq!(fold_with(&folder, "b || 1 > 2"), "b");
+        assert_eq!(fold_with(&folder, "1 < 2 && x > 0"), "x > 0");
+        // `x` might not be a boolean.
+        assert_eq!(fold_with(&folder, "1 < 2 && x"), "1 < 2 && x");
+        // `b` is evaluated first and might fail.
+        assert_eq!(fold_with(&folder, "b && 1 > 2"), "b && 1 > 2");
+    }
+
+    /// Small deterministic pseudo-random generator, enough to make up trees.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    fn random_source(rng: &mut Lcg, depth: usize) -> String {
+        const LEAVES: [&str; 10] = ["0", "1", "2", "0.0", "1.0", "-0.0", "i", "f", "b", r#""""#];
+        const PREFIX: [&str; 3] = ["+", "-", "!"];
+        const INFIX: [&str; 13] = [
+            "+", "-", "*", "/", "^", "==", "!=", "<", "<=", ">", ">=", "&&", "||",
+        ];
+
+        if depth == 0 || rng.below(3) == 0 {
+            return LEAVES[rng.below(LEAVES.len())].to_string();
+        }
+        match rng.below(5) {
+            0 => format!(
+                "{}({})",
+                PREFIX[rng.below(3)],
+                random_source(rng, depth - 1)
+            ),
+            1 => format!("({})!", random_source(rng, depth - 1)),
+            _ => format!(
+                "({}) {} ({})",
+                random_source(rng, depth - 1),
+                INFIX[rng.below(INFIX.len())],
+                random_source(rng, depth - 1)
+            ),
+        }
+    }
+
+    #[test]
+    fn folding_preserves_results() {
+        let mut folder = ConstantFolder::new();
+        folder.assume("i", Type::Int);
+        folder.assume("f", Type::Float);
+        folder.assume("b", Type::Bool);
+
+        let mut rng = Lcg(7);
+        for (i, f, b) in [(0, -0.0, true), (3, 2.5, false), (-1, f64::NAN, true)] {
+            let mut env = Env::new();
+            env.set("i", i);
+            env.set("f", f);
+            env.set("b", b);
+
+            for _ in 0..2000 {
+                let source = random_source(&mut rng, 4);
+                let expr = parse(&source);
+                let simplified = folder.clone().fold_expr(expr.clone());
+
+                let expected = Interpreter::new().eval(&expr, &env);
+                let actual = Interpreter::new().eval(&simplified, &env);
+                let same = match (&expected, &actual) {
+                    // NaN is not equal to itself, and `-0.0` equals `0.0`.
+                    (Ok(Value::Float(a)), Ok(Value::Float(b))) => {
+                        a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())
+                    }
+                    _ => expected == actual,
+                };
+                assert!(
+                    same,
+                    "`{}` folded to `{}`: {:?} != {:?}",
+                    source,
+                    pretty::print(&simplified),
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+}
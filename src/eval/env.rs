@@ -0,0 +1,91 @@
+use super::{function, Function, OverflowPolicy, RuntimeError, Type, Value};
+use std::collections::HashMap;
+
+/// The variables, constants and host functions an expression is
+/// evaluated with.
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    variables: HashMap<String, Value>,
+    constants: HashMap<String, Value>,
+    /// All overloads registered under a name, in registration order.
+    functions: HashMap<String, Vec<Function>>,
+}
+
+impl Env {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An environment with the math prelude, see [`super::prelude`].
+    #[must_use]
+    pub fn with_prelude() -> Self {
+        let mut env = Self::new();
+        super::prelude::install(&mut env);
+        env
+    }
+
+    /// Bind `name` to `value`, replacing any previous binding.
+    ///
+    /// # Panics
+    /// If `name` is a constant.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        let name = name.into();
+        assert!(
+            !self.constants.contains_key(&name),
+            "cannot assign to constant `{name}`"
+        );
+        self.variables.insert(name, value.into());
+    }
+
+    /// Define a constant, which can neither be reassigned with [`Env::set`]
+    /// nor shadowed by a variable.
+    pub fn define_const(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        let name = name.into();
+        self.variables.remove(&name);
+        self.constants.insert(name, value.into());
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.constants
+            .get(name)
+            .or_else(|| self.variables.get(name))
+    }
+
+    #[must_use]
+    pub fn is_const(&self, name: &str) -> bool {
+        self.constants.contains_key(name)
+    }
+
+    /// Register a host function. Registering several functions under the
+    /// same name overloads it, e.g. `abs` for both `int` and `float`.
+    pub fn define_fn<F>(&mut self, name: impl Into<String>, params: &[Type], ret: Type, body: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync + 'static,
+    {
+        self.functions
+            .entry(name.into())
+            .or_default()
+            .push(Function::new(params, ret, body));
+    }
+
+    /// The overloads registered under `name`, empty if there are none.
+    pub fn functions(&self, name: &str) -> &[Function] {
+        self.functions.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Call the function `name` with `args`.
+    ///
+    /// # Errors
+    /// If no overload of `name` accepts `args`, if the function fails or if
+    /// an integer result does not fit into an `i64`.
+    pub fn call(&self, name: &str, mut args: Vec<Value>) -> Result<Value, RuntimeError> {
+        function::call(
+            name,
+            self.functions(name),
+            &mut args,
+            OverflowPolicy::default(),
+        )
+    }
+}
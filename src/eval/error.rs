@@ -0,0 +1,132 @@
+use super::Type;
+use crate::lexer::TokenKind;
+use std::fmt;
+
+/// Why evaluating an expression failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    UnboundVariable(String),
+    UnknownFunction(String),
+    /// A function was called with a number of arguments
+    /// that none of its overloads takes.
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A function was called with arguments of types
+    /// that none of its overloads accepts.
+    ArgumentTypeMismatch {
+        name: String,
+        args: Vec<Type>,
+    },
+    /// A prefix or postfix operator was applied to a value it is not defined for.
+    UnaryTypeMismatch {
+        op: TokenKind,
+        operand: Type,
+    },
+    /// An infix operator was applied to values it is not defined for.
+    BinaryTypeMismatch {
+        op: TokenKind,
+        lhs: Type,
+        rhs: Type,
+    },
+    /// Integer division by zero. Floats divide to infinity or NaN instead.
+    DivisionByZero,
+    /// The result of an integer operation does not fit into an `i64`.
+    Overflow {
+        op: TokenKind,
+    },
+    /// A host function returned a value of another type than its
+    /// signature declares.
+    ReturnTypeMismatch {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    /// A host function returned an integer that does not fit into an `i64`.
+    FunctionOverflow {
+        name: String,
+    },
+    /// An integer literal does not fit into an `i64`.
+    LiteralOutOfRange(String),
+    /// An integer that does not fit into an `i64` was passed
+    /// to a function that takes an integer.
+    IntArgumentOutOfRange {
+        name: String,
+    },
+    /// An expression exceeds a limit of the bytecode, like the number of
+    /// constants in a chunk. Only reported by [`vm::compile`](super::vm::compile).
+    ChunkLimit {
+        what: &'static str,
+        max: usize,
+    },
+    /// Raising an integer to a negative integer power.
+    NegativeExponent,
+    /// The factorial of a negative integer.
+    NegativeFactorial,
+    /// An error reported by a host function.
+    Host(String),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::UnboundVariable(name) => write!(f, "unbound variable `{name}`"),
+            RuntimeError::UnknownFunction(name) => write!(f, "unknown function `{name}`"),
+            RuntimeError::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{name}` takes {expected} argument(s), but {found} were given"
+            ),
+            RuntimeError::ArgumentTypeMismatch { name, args } => {
+                write!(f, "`{name}` cannot be called with (")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            RuntimeError::UnaryTypeMismatch { op, operand } => {
+                write!(f, "cannot apply `{op}` to {operand}")
+            }
+            RuntimeError::BinaryTypeMismatch { op, lhs, rhs } => {
+                write!(f, "cannot apply `{op}` to {lhs} and {rhs}")
+            }
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::Overflow { op } => write!(f, "integer overflow in `{op}`"),
+            RuntimeError::ReturnTypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(f, "`{name}` should return {expected}, but returned {found}"),
+            RuntimeError::FunctionOverflow { name } => {
+                write!(f, "integer overflow in `{name}`")
+            }
+            RuntimeError::LiteralOutOfRange(literal) => {
+                write!(f, "integer literal `{literal}` is out of range")
+            }
+            RuntimeError::IntArgumentOutOfRange { name } => write!(
+                f,
+                "`{name}` cannot take integers that do not fit into an `i64`"
+            ),
+            RuntimeError::ChunkLimit { what, max } => {
+                write!(f, "a chunk can have at most {max} {what}")
+            }
+            RuntimeError::NegativeExponent => {
+                write!(f, "cannot raise an integer to a negative power")
+            }
+            RuntimeError::NegativeFactorial => {
+                write!(f, "cannot take the factorial of a negative number")
+            }
+            RuntimeError::Host(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -0,0 +1,187 @@
+use super::{OverflowPolicy, RuntimeError, Type, Value};
+use num_traits::ToPrimitive;
+use std::{fmt, sync::Arc};
+
+/// The parameter and return types of a [`Function`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub ret: Type,
+}
+
+impl Signature {
+    /// Whether arguments of type `args` can be passed, either exactly or
+    /// after converting integers to floats.
+    #[must_use]
+    pub fn accepts(&self, args: &[Type]) -> bool {
+        self.params.len() == args.len()
+            && self
+                .params
+                .iter()
+                .zip(args)
+                .all(|(param, arg)| param == arg || (*param == Type::Float && *arg == Type::Int))
+    }
+
+    fn accepts_values(&self, args: &[Value]) -> bool {
+        self.params.len() == args.len()
+            && self.params.iter().zip(args).all(|(param, arg)| {
+                let arg = arg.ty();
+                *param == arg || (*param == Type::Float && arg == Type::Int)
+            })
+    }
+
+    fn matches_exactly(&self, args: &[Value]) -> bool {
+        self.params.len() == args.len()
+            && self
+                .params
+                .iter()
+                .zip(args)
+                .all(|(param, arg)| *param == arg.ty())
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{param}")?;
+        }
+        write!(f, ") -> {}", self.ret)
+    }
+}
+
+type NativeFn = dyn Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync;
+
+/// A function implemented in Rust.
+///
+/// The body is only called with arguments that match the signature,
+/// so it may destructure them without checking the types again. A body
+/// that returns an `int` which does not fit into an `i64` returns it as a
+/// [`Value::BigInt`], and the caller's [`OverflowPolicy`] decides what
+/// becomes of it, as for the integer operators.
+#[derive(Clone)]
+pub struct Function {
+    signature: Signature,
+    body: Arc<NativeFn>,
+}
+
+impl Function {
+    pub fn new<F>(params: &[Type], ret: Type, body: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync + 'static,
+    {
+        Self {
+            signature: Signature {
+                params: params.to_vec(),
+                ret,
+            },
+            body: Arc::new(body),
+        }
+    }
+
+    #[must_use]
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Function{}", self.signature)
+    }
+}
+
+/// Call the overload of `name` that accepts `args`, see [`resolve`] and
+/// [`invoke`].
+pub(crate) fn call(
+    name: &str,
+    overloads: &[Function],
+    args: &mut [Value],
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    let index = resolve(name, overloads, args)?;
+    invoke(name, &overloads[index], args, overflow)
+}
+
+/// The index of the overload of `name` that accepts `args`.
+///
+/// An overload whose parameter types match exactly is preferred over one
+/// that needs integer arguments converted to floats.
+pub(crate) fn resolve(
+    name: &str,
+    overloads: &[Function],
+    args: &[Value],
+) -> Result<usize, RuntimeError> {
+    if overloads.is_empty() {
+        return Err(RuntimeError::UnknownFunction(name.to_string()));
+    }
+    let index = overloads
+        .iter()
+        .position(|function| function.signature.matches_exactly(args))
+        .or_else(|| {
+            overloads
+                .iter()
+                .position(|function| function.signature.accepts_values(args))
+        });
+
+    index.ok_or_else(|| {
+        if overloads
+            .iter()
+            .all(|function| function.signature.params.len() != args.len())
+        {
+            RuntimeError::ArityMismatch {
+                name: name.to_string(),
+                expected: overloads[0].signature.params.len(),
+                found: args.len(),
+            }
+        } else {
+            RuntimeError::ArgumentTypeMismatch {
+                name: name.to_string(),
+                args: args.iter().map(Value::ty).collect(),
+            }
+        }
+    })
+}
+
+/// Call `function`, which accepts `args`, after converting the integers
+/// that are passed for floats in place. Bodies only see integers that fit
+/// into an `i64`, as a [`Value::Int`], and larger integers they return are
+/// handled with `overflow`. A result of another type than the signature
+/// declares is an error.
+pub(crate) fn invoke(
+    name: &str,
+    function: &Function,
+    args: &mut [Value],
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    for (arg, param) in args.iter_mut().zip(&function.signature.params) {
+        match (&*arg, param) {
+            #[allow(clippy::cast_precision_loss)]
+            (Value::Int(i), Type::Float) => *arg = Value::Float(*i as f64),
+            (Value::BigInt(i), Type::Float) => *arg = Value::Float(i.to_f64().unwrap()),
+            (Value::BigInt(_), _) => {
+                return Err(RuntimeError::IntArgumentOutOfRange {
+                    name: name.to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
+    let result = (function.body)(args)?;
+    if result.ty() != function.signature.ret {
+        return Err(RuntimeError::ReturnTypeMismatch {
+            name: name.to_string(),
+            expected: function.signature.ret,
+            found: result.ty(),
+        });
+    }
+    match result {
+        Value::BigInt(i) => super::int(i, overflow).ok_or_else(|| RuntimeError::FunctionOverflow {
+            name: name.to_string(),
+        }),
+        result => Ok(result),
+    }
+}
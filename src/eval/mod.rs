@@ -0,0 +1,611 @@
+//! A tree-walking interpreter for expressions.
+//!
+//! [`Interpreter::eval`] evaluates an [`Expr`] with the variables bound in
+//! an [`Env`]. Integers are `i64` with checked arithmetic, the
+//! [`OverflowPolicy`] decides what happens to results that do not fit.
+//! Mixing an integer with a float converts the integer to a float. `^` is
+//! exponentiation and postfix `!` is the factorial. `&&` and `||`
+//! short-circuit.
+//!
+//! Calls go to host functions registered on the [`Env`], see
+//! [`Env::define_fn`]. [`Env::with_prelude`] provides common math functions
+//! and constants.
+//!
+//! To evaluate the same expression many times, [`compile`] it into a
+//! [`CompiledExpr`] first. The [`vm`] module compiles to bytecode instead.
+//! Both use [`OverflowPolicy::Error`] unless compiled with another policy.
+//! [`fold_constants`] simplifies an expression ahead of either.
+
+mod compile;
+mod const_fold;
+mod env;
+mod error;
+mod function;
+pub mod prelude;
+mod value;
+pub mod vm;
+
+pub use compile::{compile, compile_with, compile_with_overflow, CompiledExpr};
+pub use const_fold::{fold_constants, ConstantFolder};
+pub use env::Env;
+pub use error::RuntimeError;
+pub use function::{Function, Signature};
+pub use value::{Type, Value};
+
+use crate::{
+    lexer::TokenKind,
+    parser::ast::{Expr, Lit},
+    T,
+};
+use num_bigint::{BigInt, Sign};
+use num_traits::{Pow, ToPrimitive, Zero};
+use std::cmp::Ordering;
+
+/// What integer operations do with results that do not fit into an `i64`,
+/// including integer literals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Fail with [`RuntimeError::Overflow`], or
+    /// [`RuntimeError::LiteralOutOfRange`] for literals.
+    #[default]
+    Error,
+    /// Wrap around in two's complement, like `i64::wrapping_add`.
+    Wrap,
+    /// Continue with a [`Value::BigInt`]. Integers are still limited to
+    /// [`MAX_BIG_INT_BITS`], so that `9 ^ 9 ^ 9` fails instead of running
+    /// out of memory.
+    Promote,
+}
+
+/// The number of bits of the largest integer [`OverflowPolicy::Promote`]
+/// creates.
+pub const MAX_BIG_INT_BITS: u64 = 1 << 16;
+
+#[derive(Debug, Clone, Default)]
+pub struct Interpreter {
+    overflow: OverflowPolicy,
+}
+
+impl Interpreter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An interpreter that handles integer overflow with `overflow`.
+    #[must_use]
+    pub fn with_overflow(overflow: OverflowPolicy) -> Self {
+        Self { overflow }
+    }
+
+    /// Evaluate `expr` with the variables, constants and functions of `env`.
+    ///
+    /// # Errors
+    /// If a name is not defined, an operator or function is applied to
+    /// values it does not take, or an integer overflows.
+    pub fn eval(&self, expr: &Expr, env: &Env) -> Result<Value, RuntimeError> {
+        match expr {
+            Expr::Literal(lit) => literal(lit, self.overflow),
+            Expr::Ident(name) => env
+                .get(name)
+                .cloned()
+                .ok_or_else(|| RuntimeError::UnboundVariable(name.clone())),
+            Expr::FnCall { fn_name, args } => {
+                let mut args: Vec<Value> = args
+                    .iter()
+                    .map(|arg| self.eval(arg, env))
+                    .collect::<Result<_, _>>()?;
+                function::call(fn_name, env.functions(fn_name), &mut args, self.overflow)
+            }
+            Expr::PrefixOp { op, expr } => prefix(*op, self.eval(expr, env)?, self.overflow),
+            Expr::InfixOp { op, lhs, rhs } => {
+                let lhs = self.eval(lhs, env)?;
+                match (op, &lhs) {
+                    (T![&&], Value::Bool(false)) => return Ok(Value::Bool(false)),
+                    (T![||], Value::Bool(true)) => return Ok(Value::Bool(true)),
+                    _ => {}
+                }
+                infix(*op, &lhs, &self.eval(rhs, env)?, self.overflow)
+            }
+            Expr::PostfixOp { op, expr } => postfix(*op, self.eval(expr, env)?, self.overflow),
+        }
+    }
+}
+
+fn literal(lit: &Lit, overflow: OverflowPolicy) -> Result<Value, RuntimeError> {
+    let out_of_range = |i: &dyn ToString| RuntimeError::LiteralOutOfRange(i.to_string());
+    Ok(match lit {
+        Lit::Int(i) => match i64::try_from(*i) {
+            Ok(i) => Value::Int(i),
+            Err(_) => int(BigInt::from(*i), overflow).ok_or_else(|| out_of_range(i))?,
+        },
+        Lit::BigInt(i) => int(i.clone(), overflow).ok_or_else(|| out_of_range(i))?,
+        Lit::Float(fl) => Value::Float(*fl),
+        Lit::Str(s) => Value::Str(s.clone()),
+    })
+}
+
+/// The integer `i` after applying `overflow`, or `None` if it overflows.
+fn int(i: BigInt, overflow: OverflowPolicy) -> Option<Value> {
+    if let Some(i) = i.to_i64() {
+        return Some(Value::Int(i));
+    }
+    match overflow {
+        OverflowPolicy::Error => None,
+        OverflowPolicy::Wrap => Some(Value::Int(wrap(&i))),
+        OverflowPolicy::Promote => (i.bits() <= MAX_BIG_INT_BITS).then_some(Value::BigInt(i)),
+    }
+}
+
+/// The low 64 bits of `i` in two's complement.
+#[allow(clippy::cast_possible_wrap)]
+fn wrap(i: &BigInt) -> i64 {
+    let low = i.iter_u64_digits().next().unwrap_or(0);
+    match i.sign() {
+        Sign::Minus => low.wrapping_neg() as i64,
+        Sign::NoSign | Sign::Plus => low as i64,
+    }
+}
+
+pub(crate) fn prefix(
+    op: TokenKind,
+    operand: Value,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    match (op, operand) {
+        (T![+], Value::Int(i)) => Ok(Value::Int(i)),
+        (T![+], Value::BigInt(i)) => Ok(Value::BigInt(i)),
+        (T![+], Value::Float(fl)) => Ok(Value::Float(fl)),
+        (T![-], Value::Int(i)) => match i.checked_neg() {
+            Some(i) => Ok(Value::Int(i)),
+            None => int(-BigInt::from(i), overflow).ok_or(RuntimeError::Overflow { op }),
+        },
+        (T![-], Value::BigInt(i)) => int(-i, overflow).ok_or(RuntimeError::Overflow { op }),
+        (T![-], Value::Float(fl)) => Ok(Value::Float(-fl)),
+        (T![!], Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (op, operand) => Err(RuntimeError::UnaryTypeMismatch {
+            op,
+            operand: operand.ty(),
+        }),
+    }
+}
+
+pub(crate) fn infix(
+    op: TokenKind,
+    lhs: &Value,
+    rhs: &Value,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    let mismatch = |lhs: &Value, rhs: &Value| RuntimeError::BinaryTypeMismatch {
+        op,
+        lhs: lhs.ty(),
+        rhs: rhs.ty(),
+    };
+
+    match op {
+        T![+] | T![-] | T![*] | T![/] | T![^] => match (lhs, rhs) {
+            (Value::Int(a), Value::Int(b)) => int_arithmetic(op, *a, *b, overflow),
+            (Value::Str(a), Value::Str(b)) if op == T![+] => Ok(Value::Str(format!("{a}{b}"))),
+            _ => match (as_big_int(lhs), as_big_int(rhs)) {
+                (Some(a), Some(b)) => big_int_arithmetic(op, &a, &b, overflow),
+                _ => match (as_float(lhs), as_float(rhs)) {
+                    (Some(a), Some(b)) => Ok(Value::Float(float_arithmetic(op, a, b))),
+                    _ => Err(mismatch(lhs, rhs)),
+                },
+            },
+        },
+        T![==] | T![!=] | T![<] | T![<=] | T![>] | T![>=] => {
+            let ordering = match (lhs, rhs) {
+                (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+                (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+                (Value::Bool(a), Value::Bool(b)) if matches!(op, T![==] | T![!=]) => Some(a.cmp(b)),
+                _ => match (as_big_int(lhs), as_big_int(rhs)) {
+                    (Some(a), Some(b)) => Some(a.cmp(&b)),
+                    _ => match (as_float(lhs), as_float(rhs)) {
+                        // `None` if either side is NaN.
+                        (Some(a), Some(b)) => a.partial_cmp(&b),
+                        _ => return Err(mismatch(lhs, rhs)),
+                    },
+                },
+            };
+            Ok(Value::Bool(match op {
+                T![==] => ordering == Some(Ordering::Equal),
+                T![!=] => ordering != Some(Ordering::Equal),
+                T![<] => ordering == Some(Ordering::Less),
+                T![<=] => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                T![>] => ordering == Some(Ordering::Greater),
+                _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            }))
+        }
+        // The short-circuiting cases are handled by the caller.
+        T![&&] | T![||] => match (lhs, rhs) {
+            (Value::Bool(_), Value::Bool(b)) => Ok(Value::Bool(*b)),
+            _ => Err(mismatch(lhs, rhs)),
+        },
+        _ => Err(mismatch(lhs, rhs)),
+    }
+}
+
+pub(crate) fn postfix(
+    op: TokenKind,
+    operand: Value,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    match (op, operand) {
+        (T![!], Value::Int(n)) => factorial(n, overflow),
+        (T![!], Value::BigInt(n)) if n.sign() == Sign::Minus => {
+            Err(RuntimeError::NegativeFactorial)
+        }
+        // See `factorial`.
+        (T![!], Value::BigInt(_)) if overflow == OverflowPolicy::Wrap => Ok(Value::Int(0)),
+        (T![!], Value::BigInt(_)) => Err(RuntimeError::Overflow { op }),
+        (op, operand) => Err(RuntimeError::UnaryTypeMismatch {
+            op,
+            operand: operand.ty(),
+        }),
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        #[allow(clippy::cast_precision_loss)]
+        Value::Int(i) => Some(*i as f64),
+        Value::BigInt(i) => i.to_f64(),
+        Value::Float(fl) => Some(*fl),
+        Value::Str(_) | Value::Bool(_) => None,
+    }
+}
+
+fn as_big_int(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Int(i) => Some(BigInt::from(*i)),
+        Value::BigInt(i) => Some(i.clone()),
+        Value::Float(_) | Value::Str(_) | Value::Bool(_) => None,
+    }
+}
+
+fn int_arithmetic(
+    op: TokenKind,
+    a: i64,
+    b: i64,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    let result = match op {
+        T![+] => a.checked_add(b),
+        T![-] => a.checked_sub(b),
+        T![*] => a.checked_mul(b),
+        T![/] if b == 0 => return Err(RuntimeError::DivisionByZero),
+        T![/] => a.checked_div(b),
+        T![^] if b < 0 => return Err(RuntimeError::NegativeExponent),
+        T![^] => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        _ => unreachable!("`{}` is not an arithmetic operator", op),
+    };
+    match result {
+        Some(result) => Ok(Value::Int(result)),
+        None => big_int_arithmetic(op, &BigInt::from(a), &BigInt::from(b), overflow),
+    }
+}
+
+fn big_int_arithmetic(
+    op: TokenKind,
+    a: &BigInt,
+    b: &BigInt,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    let result = match op {
+        T![+] => a + b,
+        T![-] => a - b,
+        T![*] => a * b,
+        T![/] if b.is_zero() => return Err(RuntimeError::DivisionByZero),
+        // Rounds towards zero, like `i64` division.
+        T![/] => a / b,
+        T![^] if b.sign() == Sign::Minus => return Err(RuntimeError::NegativeExponent),
+        T![^] => return big_int_pow(a, b, overflow),
+        _ => unreachable!("`{}` is not an arithmetic operator", op),
+    };
+    int(result, overflow).ok_or(RuntimeError::Overflow { op })
+}
+
+fn big_int_pow(
+    base: &BigInt,
+    exp: &BigInt,
+    overflow: OverflowPolicy,
+) -> Result<Value, RuntimeError> {
+    let overflow_error = RuntimeError::Overflow { op: T![^] };
+    // The powers of 0, 1 and -1 only depend on whether the exponent is zero
+    // and on its parity, however large it is.
+    let exp = if base.bits() <= 1 && *exp > BigInt::from(1) {
+        BigInt::from(2) - exp % 2
+    } else {
+        exp.clone()
+    };
+    if overflow == OverflowPolicy::Wrap {
+        let base = wrap(base);
+        // Even numbers to the power of 64 are multiples of 2^64, and odd
+        // ones to the power of 2^62 are 1 modulo 2^64, so larger exponents
+        // can be reduced.
+        let exp = if base % 2 == 0 {
+            exp.min(BigInt::from(64))
+        } else {
+            exp % (1_u64 << 62)
+        };
+        return Ok(Value::Int(wrapping_pow(base, exp.to_u64().unwrap())));
+    }
+    let exp = exp.to_u64().ok_or_else(|| overflow_error.clone())?;
+    // The result has more bits than that, don't compute it to find out
+    // that it overflows.
+    if base.bits().saturating_sub(1).saturating_mul(exp) > MAX_BIG_INT_BITS {
+        return Err(overflow_error);
+    }
+    let exp = u32::try_from(exp).map_err(|_| overflow_error.clone())?;
+    int(Pow::pow(base, exp), overflow).ok_or(overflow_error)
+}
+
+/// `base ^ exp` with wrapping multiplication, for exponents beyond `u32`.
+fn wrapping_pow(mut base: i64, mut exp: u64) -> i64 {
+    let mut result = 1_i64;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    result
+}
+
+fn float_arithmetic(op: TokenKind, a: f64, b: f64) -> f64 {
+    match op {
+        T![+] => a + b,
+        T![-] => a - b,
+        T![*] => a * b,
+        T![/] => a / b,
+        T![^] => a.powf(b),
+        _ => unreachable!("`{}` is not an arithmetic operator", op),
+    }
+}
+
+fn factorial(n: i64, overflow: OverflowPolicy) -> Result<Value, RuntimeError> {
+    if n < 0 {
+        return Err(RuntimeError::NegativeFactorial);
+    }
+    if let Some(product) = (2..=n).try_fold(1_i64, i64::checked_mul) {
+        return Ok(Value::Int(product));
+    }
+    let overflow_error = RuntimeError::Overflow { op: T![!] };
+    match overflow {
+        OverflowPolicy::Error => Err(overflow_error),
+        // `66!` is a multiple of 2^64, and so are all larger factorials,
+        // which makes them wrap to 0.
+        OverflowPolicy::Wrap => Ok(Value::Int((2..=n.min(66)).fold(1, i64::wrapping_mul))),
+        OverflowPolicy::Promote => {
+            let mut product = BigInt::from(1);
+            for i in 2..=n {
+                product *= i;
+                if product.bits() > MAX_BIG_INT_BITS {
+                    return Err(overflow_error);
+                }
+            }
+            Ok(Value::BigInt(product))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn eval_with(input: &str, env: &Env) -> Result<Value, RuntimeError> {
+        let expr = Parser::new(input).parse_expression(0);
+        Interpreter::new().eval(&expr, env)
+    }
+
+    fn eval(input: &str) -> Result<Value, RuntimeError> {
+        eval_with(input, &Env::new())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(Value::Int(7)));
+        assert_eq!(eval("7 / 2"), Ok(Value::Int(3)));
+        assert_eq!(eval("-7 / 2"), Ok(Value::Int(-3)));
+        assert_eq!(eval("1 - 2 - 3"), Ok(Value::Int(-4)));
+        assert_eq!(eval("+-3"), Ok(Value::Int(-3)));
+        assert_eq!(eval("1.5 * 2"), Ok(Value::Float(3.0)));
+        assert_eq!(eval("1 / 2.0"), Ok(Value::Float(0.5)));
+        assert_eq!(eval(r#""a" + "b""#), Ok(Value::Str("ab".to_string())));
+        assert_eq!(
+            eval(r#""say \"hi\"""#),
+            Ok(Value::Str(r#"say "hi""#.to_string()))
+        );
+    }
+
+    #[test]
+    fn exponentiation_is_right_associative() {
+        assert_eq!(eval("2 ^ 3 ^ 2"), Ok(Value::Int(512)));
+        assert_eq!(eval("(2 ^ 3) ^ 2"), Ok(Value::Int(64)));
+        assert_eq!(eval("-2 ^ 2"), Ok(Value::Int(4)));
+        assert_eq!(eval("2.0 ^ -1"), Ok(Value::Float(0.5)));
+    }
+
+    #[test]
+    fn factorial() {
+        assert_eq!(eval("0!"), Ok(Value::Int(1)));
+        assert_eq!(eval("5!"), Ok(Value::Int(120)));
+        assert_eq!(eval("3!!"), Ok(Value::Int(720)));
+        assert_eq!(eval("-3!"), Ok(Value::Int(-6)));
+        assert_eq!(eval("(-3)!"), Err(RuntimeError::NegativeFactorial));
+        assert_eq!(eval("21!"), Err(RuntimeError::Overflow { op: T![!] }));
+        assert_eq!(
+            eval("2.0!"),
+            Err(RuntimeError::UnaryTypeMismatch {
+                op: T![!],
+                operand: Type::Float
+            })
+        );
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        assert_eq!(eval("1 < 2 && 2.5 >= 2"), Ok(Value::Bool(true)));
+        assert_eq!(eval(r#""a" < "b""#), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 == 1.0"), Ok(Value::Bool(true)));
+        assert_eq!(eval("!(1 != 2) || 3 <= 2"), Ok(Value::Bool(false)));
+        assert_eq!(eval("(1 < 2) == (3 < 4)"), Ok(Value::Bool(true)));
+        assert_eq!(eval("0.0 / 0.0 == 0.0 / 0.0"), Ok(Value::Bool(false)));
+        assert_eq!(eval("0.0 / 0.0 != 0.0 / 0.0"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn logic_short_circuits() {
+        // The right-hand sides would fail with an unbound variable.
+        assert_eq!(eval("1 > 2 && x"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 < 2 || x"), Ok(Value::Bool(true)));
+        assert_eq!(
+            eval("1 < 2 && x"),
+            Err(RuntimeError::UnboundVariable("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn variables() {
+        let mut env = Env::new();
+        env.set("x", 4);
+        env.set("name", "world");
+        assert_eq!(eval_with("x * x + 1", &env), Ok(Value::Int(17)));
+        assert_eq!(
+            eval_with(r#""hello " + name"#, &env),
+            Ok(Value::Str("hello world".to_string()))
+        );
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert_eq!(eval("1 / 0"), Err(RuntimeError::DivisionByZero));
+        assert_eq!(eval("1.0 / 0"), Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(eval("2 ^ -1"), Err(RuntimeError::NegativeExponent));
+        assert_eq!(
+            eval("9223372036854775807 + 1"),
+            Err(RuntimeError::Overflow { op: T![+] })
+        );
+        assert_eq!(
+            eval("9223372036854775808"),
+            Err(RuntimeError::LiteralOutOfRange(
+                "9223372036854775808".to_string()
+            ))
+        );
+        assert_eq!(
+            eval(r#"1 + "a""#),
+            Err(RuntimeError::BinaryTypeMismatch {
+                op: T![+],
+                lhs: Type::Int,
+                rhs: Type::Str
+            })
+        );
+        assert_eq!(
+            eval("1 && 2 < 3"),
+            Err(RuntimeError::BinaryTypeMismatch {
+                op: T![&&],
+                lhs: Type::Int,
+                rhs: Type::Bool
+            })
+        );
+        assert_eq!(
+            eval("-(1 < 2)"),
+            Err(RuntimeError::UnaryTypeMismatch {
+                op: T![-],
+                operand: Type::Bool
+            })
+        );
+        assert_eq!(
+            eval("f(1)"),
+            Err(RuntimeError::UnknownFunction("f".to_string()))
+        );
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let eval = |input: &str, overflow| {
+            let expr = Parser::new(input).parse_expression(0);
+            Interpreter::with_overflow(overflow).eval(&expr, &Env::new())
+        };
+        let big = |digits: &str| Value::BigInt(digits.parse().unwrap());
+
+        let wrap = |input| eval(input, OverflowPolicy::Wrap);
+        assert_eq!(wrap("9223372036854775807 + 1"), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            wrap("-(0 - 9223372036854775807 - 1)"),
+            Ok(Value::Int(i64::MIN))
+        );
+        assert_eq!(wrap("18446744073709551617"), Ok(Value::Int(1)));
+        assert_eq!(wrap("2 ^ 64"), Ok(Value::Int(0)));
+        // Odd numbers to the power of 2^62 are 1 modulo 2^64.
+        assert_eq!(wrap("3 ^ 4611686018427387904"), Ok(Value::Int(1)));
+        assert_eq!(
+            wrap("21!"),
+            Ok(Value::Int((2..=21).fold(1, i64::wrapping_mul)))
+        );
+        assert_eq!(wrap("100!"), Ok(Value::Int(0)));
+        // Only variables can hold exponents beyond `u64` when wrapping.
+        let mut env = Env::new();
+        env.set("huge", (BigInt::from(1) << 70) + 5);
+        let wrap_huge = |input: &str| {
+            let expr = Parser::new(input).parse_expression(0);
+            Interpreter::with_overflow(OverflowPolicy::Wrap).eval(&expr, &env)
+        };
+        assert_eq!(wrap_huge("3 ^ huge"), Ok(Value::Int(3_i64.pow(5))));
+        assert_eq!(wrap_huge("(-3) ^ huge"), Ok(Value::Int(-(3_i64.pow(5)))));
+        assert_eq!(wrap_huge("2 ^ huge"), Ok(Value::Int(0)));
+        assert_eq!(wrap_huge("0 ^ huge + 1 ^ huge"), Ok(Value::Int(1)));
+        for overflow in [OverflowPolicy::Error, OverflowPolicy::Promote] {
+            let eval_huge = |input: &str| {
+                let expr = Parser::new(input).parse_expression(0);
+                Interpreter::with_overflow(overflow).eval(&expr, &env)
+            };
+            assert_eq!(eval_huge("0 ^ huge"), Ok(Value::Int(0)));
+            assert_eq!(eval_huge("(-1) ^ huge"), Ok(Value::Int(-1)));
+        }
+
+        let promote = |input| eval(input, OverflowPolicy::Promote);
+        assert_eq!(
+            promote("9223372036854775807 + 1"),
+            Ok(big("9223372036854775808"))
+        );
+        assert_eq!(promote("2 ^ 100 / 2 ^ 90"), Ok(Value::Int(1024)));
+        assert_eq!(promote("25!"), Ok(big("15511210043330985984000000")));
+        assert_eq!(
+            promote("100000000000000000000000000000000000000000 > 1.0e40"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(promote("1 ^ 100000000000000000000001"), Ok(Value::Int(1)));
+        assert_eq!(promote("(-1) ^ (2 ^ 100 + 1)"), Ok(Value::Int(-1)));
+        assert_eq!(
+            promote("9 ^ 9 ^ 9"),
+            Err(RuntimeError::Overflow { op: T![^] })
+        );
+        assert_eq!(
+            promote("(2 ^ 64)!"),
+            Err(RuntimeError::Overflow { op: T![!] })
+        );
+
+        let mut env = Env::with_prelude();
+        env.set("x", BigInt::from(u64::MAX));
+        let expr = Parser::new("sqrt(x) + abs(x)").parse_expression(0);
+        assert_eq!(
+            Interpreter::new().eval(&expr, &env),
+            Err(RuntimeError::IntArgumentOutOfRange {
+                name: "abs".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(
+            eval(r#"1 - "a""#).unwrap_err().to_string(),
+            "cannot apply `-` to int and string"
+        );
+        assert_eq!(eval("1 / 0").unwrap_err().to_string(), "division by zero");
+    }
+}
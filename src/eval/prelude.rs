@@ -0,0 +1,237 @@
+//! Common math functions and constants.
+//!
+//! | name | signatures |
+//! |------|------------|
+//! | `sin`, `cos`, `tan`, `asin`, `acos`, `atan` | `(float) -> float` |
+//! | `sqrt`, `exp`, `ln`, `log10`, `log2` | `(float) -> float` |
+//! | `floor`, `ceil`, `round` | `(float) -> float` |
+//! | `atan2`, `pow` | `(float, float) -> float` |
+//! | `abs` | `(int) -> int`, `(float) -> float` |
+//! | `min`, `max` | `(int, int) -> int`, `(float, float) -> float` |
+//!
+//! The constants are `PI`, `E`, `TAU`, `true` and `false`.
+//!
+//! Integer arguments are converted for `float` parameters. Functions of
+//! floats follow IEEE semantics, so `sqrt(-1)` is NaN rather than an error.
+//! `abs` of the smallest `int` overflows like its negation, following the
+//! [`OverflowPolicy`](super::OverflowPolicy) of the evaluation.
+
+use super::{Env, Type, Value};
+use num_bigint::BigInt;
+use std::f64::consts;
+
+type Unary = fn(f64) -> f64;
+type Binary = fn(f64, f64) -> f64;
+
+/// Add the prelude's functions and constants to `env`.
+pub fn install(env: &mut Env) {
+    env.define_const("PI", consts::PI);
+    env.define_const("E", consts::E);
+    env.define_const("TAU", consts::TAU);
+    env.define_const("true", true);
+    env.define_const("false", false);
+
+    let unary: [(&str, Unary); 14] = [
+        ("sin", f64::sin),
+        ("cos", f64::cos),
+        ("tan", f64::tan),
+        ("asin", f64::asin),
+        ("acos", f64::acos),
+        ("atan", f64::atan),
+        ("sqrt", f64::sqrt),
+        ("exp", f64::exp),
+        ("ln", f64::ln),
+        ("log10", f64::log10),
+        ("log2", f64::log2),
+        ("floor", f64::floor),
+        ("ceil", f64::ceil),
+        ("round", f64::round),
+    ];
+    for (name, f) in unary {
+        env.define_fn(name, &[Type::Float], Type::Float, move |args| {
+            Ok(Value::Float(f(float(&args[0]))))
+        });
+    }
+
+    let binary: [(&str, Binary); 4] = [
+        ("atan2", f64::atan2),
+        ("pow", f64::powf),
+        ("min", f64::min),
+        ("max", f64::max),
+    ];
+    for (name, f) in binary {
+        env.define_fn(
+            name,
+            &[Type::Float, Type::Float],
+            Type::Float,
+            move |args| Ok(Value::Float(f(float(&args[0]), float(&args[1])))),
+        );
+    }
+
+    env.define_fn("abs", &[Type::Int], Type::Int, |args| {
+        let i = int(&args[0]);
+        Ok(i.checked_abs()
+            .map_or_else(|| Value::BigInt(-BigInt::from(i)), Value::Int))
+    });
+    env.define_fn("abs", &[Type::Float], Type::Float, |args| {
+        Ok(Value::Float(float(&args[0]).abs()))
+    });
+    env.define_fn("min", &[Type::Int, Type::Int], Type::Int, |args| {
+        Ok(Value::Int(int(&args[0]).min(int(&args[1]))))
+    });
+    env.define_fn("max", &[Type::Int, Type::Int], Type::Int, |args| {
+        Ok(Value::Int(int(&args[0]).max(int(&args[1]))))
+    });
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Float(fl) => *fl,
+        _ => unreachable!("argument does not match the signature"),
+    }
+}
+
+fn int(value: &Value) -> i64 {
+    match value {
+        Value::Int(i) => *i,
+        _ => unreachable!("argument does not match the signature"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::{Interpreter, OverflowPolicy, RuntimeError},
+        parser::Parser,
+    };
+
+    fn eval_with(input: &str, env: &Env) -> Result<Value, RuntimeError> {
+        let expr = Parser::new(input).parse_expression(0);
+        Interpreter::new().eval(&expr, env)
+    }
+
+    fn eval(input: &str) -> Result<Value, RuntimeError> {
+        eval_with(input, &Env::with_prelude())
+    }
+
+    #[test]
+    fn math_functions() {
+        assert_eq!(eval("sqrt(16)"), Ok(Value::Float(4.0)));
+        assert_eq!(eval("cos(0)"), Ok(Value::Float(1.0)));
+        assert_eq!(eval("pow(2, 10)"), Ok(Value::Float(1024.0)));
+        assert_eq!(eval("floor(-2.5)"), Ok(Value::Float(-3.0)));
+        assert_eq!(eval("ln(E)"), Ok(Value::Float(1.0)));
+        assert_eq!(eval("sin(PI / 2)"), Ok(Value::Float(1.0)));
+        assert_eq!(eval("TAU == 2 * PI"), Ok(Value::Bool(true)));
+        assert_eq!(eval("true && !false"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn overloads_prefer_exact_matches() {
+        assert_eq!(eval("abs(-3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("abs(-3.5)"), Ok(Value::Float(3.5)));
+        assert_eq!(eval("min(2, 3)"), Ok(Value::Int(2)));
+        assert_eq!(eval("max(2, 3.5)"), Ok(Value::Float(3.5)));
+        assert_eq!(
+            eval("min(test + 4, sin(2 * PI))"),
+            Err(RuntimeError::UnboundVariable("test".to_string()))
+        );
+
+        let mut env = Env::with_prelude();
+        env.set("test", -10);
+        assert_eq!(
+            eval_with("min(test + 4, sin(2 * PI))", &env),
+            Ok(Value::Float(-6.0))
+        );
+    }
+
+    #[test]
+    fn call_errors() {
+        assert_eq!(
+            eval("sqrt(1, 2)"),
+            Err(RuntimeError::ArityMismatch {
+                name: "sqrt".to_string(),
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            eval(r#"max("a", 1)"#),
+            Err(RuntimeError::ArgumentTypeMismatch {
+                name: "max".to_string(),
+                args: vec![Type::Str, Type::Int]
+            })
+        );
+        assert_eq!(
+            eval("abs(-9223372036854775807 - 1)"),
+            Err(RuntimeError::FunctionOverflow {
+                name: "abs".to_string()
+            })
+        );
+        assert_eq!(
+            eval(r#"max("a", 1)"#).unwrap_err().to_string(),
+            "`max` cannot be called with (string, int)"
+        );
+    }
+
+    #[test]
+    fn abs_follows_the_overflow_policy() {
+        let expr = Parser::new("abs(-9223372036854775807 - 1)").parse_expression(0);
+        let eval =
+            |overflow| Interpreter::with_overflow(overflow).eval(&expr, &Env::with_prelude());
+        assert_eq!(
+            eval(OverflowPolicy::Error).unwrap_err().to_string(),
+            "integer overflow in `abs`"
+        );
+        assert_eq!(eval(OverflowPolicy::Wrap), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            eval(OverflowPolicy::Promote),
+            Ok(Value::BigInt(BigInt::from(i64::MAX) + 1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot assign to constant `PI`")]
+    fn constants_cannot_be_assigned() {
+        Env::with_prelude().set("PI", 3);
+    }
+
+    #[test]
+    fn host_functions() {
+        let mut env = Env::new();
+        env.define_fn("greet", &[Type::Str], Type::Str, |args| match &args[0] {
+            Value::Str(name) if name.is_empty() => {
+                Err(RuntimeError::Host("nobody to greet".to_string()))
+            }
+            name => Ok(Value::Str(format!("hello {name}"))),
+        });
+        assert_eq!(
+            eval_with(r#"greet("you")"#, &env),
+            Ok(Value::Str("hello you".to_string()))
+        );
+        assert_eq!(
+            eval_with(r#"greet("")"#, &env),
+            Err(RuntimeError::Host("nobody to greet".to_string()))
+        );
+        assert_eq!(
+            env.functions("greet")[0].signature().to_string(),
+            "(string) -> string"
+        );
+
+        env.define_fn("len", &[Type::Str], Type::Int, |_| Ok(Value::Float(4.0)));
+        let error = eval_with(r#"len("four")"#, &env).unwrap_err();
+        assert_eq!(
+            error,
+            RuntimeError::ReturnTypeMismatch {
+                name: "len".to_string(),
+                expected: Type::Int,
+                found: Type::Float,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "`len` should return int, but returned float"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::fmt;
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    /// An integer that does not fit into an `i64`. Integers that do are
+    /// always an [`Int`](Value::Int).
+    BigInt(BigInt),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+/// The type of a [`Value`].
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Type {
+    Int,
+    Float,
+    Str,
+    Bool,
+}
+
+impl Value {
+    #[must_use]
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Int(_) | Value::BigInt(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Str(_) => Type::Str,
+            Value::Bool(_) => Type::Bool,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{i}"),
+            Value::BigInt(i) => write!(f, "{i}"),
+            // `Debug` keeps the `.0` of whole numbers.
+            Value::Float(fl) => write!(f, "{fl:?}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Type::Int => "int",
+                Type::Float => "float",
+                Type::Str => "string",
+                Type::Bool => "bool",
+            }
+        )
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(i: BigInt) -> Self {
+        match i.to_i64() {
+            Some(i) => Value::Int(i),
+            None => Value::BigInt(i),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(fl: f64) -> Self {
+        Value::Float(fl)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
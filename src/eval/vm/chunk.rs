@@ -0,0 +1,102 @@
+use crate::eval::{Function, OverflowPolicy, Value};
+use std::fmt::Write;
+
+/// A single instruction of the stack machine.
+///
+/// Operators pop their operands and push the result. The right operand of
+/// an infix operator is on top of the left one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Push a value from the constant pool.
+    Constant(u16),
+    /// Push the value of a variable.
+    Load(u16),
+    Plus,
+    Neg,
+    Not,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Factorial,
+    /// Continue at the given instruction if the top of the stack is `false`,
+    /// leaving it there.
+    JumpIfFalse(u16),
+    /// Continue at the given instruction if the top of the stack is `true`,
+    /// leaving it there.
+    JumpIfTrue(u16),
+    /// Pop `argc` arguments and push the result of calling a host function.
+    Call {
+        function: u16,
+        argc: u8,
+    },
+    /// Stop and return the top of the stack.
+    Return,
+}
+
+/// Compiled bytecode together with the tables its instructions refer to.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    /// The names of the variables, indexed by [`Op::Load`].
+    pub slots: Vec<String>,
+    /// The called functions with their overloads, indexed by [`Op::Call`].
+    /// A function that was unknown at compile time has no overloads.
+    pub functions: Vec<(String, Vec<Function>)>,
+    /// What the integer operations do on overflow. The constants
+    /// were computed with it as well.
+    pub overflow: OverflowPolicy,
+}
+
+impl Chunk {
+    /// A listing of the instructions, one per line, with constants,
+    /// variables and functions resolved in a trailing comment.
+    ///
+    /// # Panics
+    /// If the bytecode is malformed.
+    #[must_use]
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        for (i, op) in self.code.iter().enumerate() {
+            write!(out, "{i:04} ").unwrap();
+            match op {
+                Op::Constant(index) => {
+                    let value = &self.constants[usize::from(*index)];
+                    match value {
+                        Value::Str(s) => write!(out, "constant {index:<5} ; {s:?}"),
+                        value => write!(out, "constant {index:<5} ; {value}"),
+                    }
+                }
+                Op::Load(slot) => write!(
+                    out,
+                    "load     {:<5} ; {}",
+                    slot,
+                    self.slots[usize::from(*slot)]
+                ),
+                Op::JumpIfFalse(target) => write!(out, "jump-if-false {target:04}"),
+                Op::JumpIfTrue(target) => write!(out, "jump-if-true  {target:04}"),
+                Op::Call { function, argc } => write!(
+                    out,
+                    "call     {:<5} ; {}/{}",
+                    function,
+                    self.functions[usize::from(*function)].0,
+                    argc
+                ),
+                op => write!(out, "{}", format!("{op:?}").to_lowercase()),
+            }
+            .unwrap();
+            out.push('\n');
+        }
+        out
+    }
+}
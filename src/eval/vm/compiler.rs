@@ -0,0 +1,185 @@
+use super::{Chunk, Op};
+use crate::{
+    eval::{literal, Env, OverflowPolicy, RuntimeError, Value},
+    parser::ast::Expr,
+    T,
+};
+
+/// Compile `expr` into bytecode, resolving constants
+/// and functions against `env`.
+///
+/// # Errors
+/// An integer literal that does not fit into an `i64` is reported here
+/// rather than when the chunk runs, and so is a [`RuntimeError::ChunkLimit`]
+/// if the expression needs more than `u16::MAX` constants, variables or
+/// functions, jumps past that many instructions, or passes more than
+/// `u8::MAX` arguments to a call. Calls to unknown functions fail at
+/// runtime, since they may never be reached.
+pub fn compile(expr: &Expr, env: &Env) -> Result<Chunk, RuntimeError> {
+    compile_with_overflow(expr, env, OverflowPolicy::default())
+}
+
+/// Like [`compile`], but the chunk handles integer overflow with `overflow`
+/// instead of failing.
+///
+/// # Errors
+/// Like [`compile`], except for integer literals that `overflow` handles.
+pub fn compile_with_overflow(
+    expr: &Expr,
+    env: &Env,
+    overflow: OverflowPolicy,
+) -> Result<Chunk, RuntimeError> {
+    let mut compiler = Compiler {
+        env,
+        chunk: Chunk {
+            overflow,
+            ..Chunk::default()
+        },
+    };
+    compiler.expr(expr)?;
+    compiler.chunk.code.push(Op::Return);
+    Ok(compiler.chunk)
+}
+
+struct Compiler<'env> {
+    env: &'env Env,
+    chunk: Chunk,
+}
+
+impl Compiler<'_> {
+    fn expr(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+        match expr {
+            Expr::Literal(lit) => {
+                let value = literal(lit, self.chunk.overflow)?;
+                self.constant(value)?;
+            }
+            Expr::Ident(name) if self.env.is_const(name) => {
+                self.constant(self.env.get(name).cloned().unwrap())?;
+            }
+            Expr::Ident(name) => {
+                let slot = if let Some(slot) = self.chunk.slots.iter().position(|slot| slot == name)
+                {
+                    slot
+                } else {
+                    self.chunk.slots.push(name.clone());
+                    self.chunk.slots.len() - 1
+                };
+                self.emit(Op::Load(index(slot, "variables")?));
+            }
+            Expr::FnCall { fn_name, args } => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                let function = if let Some(function) = self
+                    .chunk
+                    .functions
+                    .iter()
+                    .position(|(name, _)| name == fn_name)
+                {
+                    function
+                } else {
+                    let overloads = self.env.functions(fn_name).to_vec();
+                    self.chunk.functions.push((fn_name.clone(), overloads));
+                    self.chunk.functions.len() - 1
+                };
+                let argc = u8::try_from(args.len()).map_err(|_| RuntimeError::ChunkLimit {
+                    what: "arguments in a call",
+                    max: u8::MAX.into(),
+                })?;
+                self.emit(Op::Call {
+                    function: index(function, "functions")?,
+                    argc,
+                });
+            }
+            Expr::PrefixOp { op, expr } => {
+                self.expr(expr)?;
+                self.emit(match op {
+                    T![+] => Op::Plus,
+                    T![-] => Op::Neg,
+                    T![!] => Op::Not,
+                    _ => unreachable!("`{}` is not a prefix operator", op),
+                });
+            }
+            Expr::InfixOp { op, lhs, rhs } => {
+                self.expr(lhs)?;
+                // Skip the right-hand side if the left one decides the result.
+                let jump = match op {
+                    T![&&] => Some(self.emit(Op::JumpIfFalse(0))),
+                    T![||] => Some(self.emit(Op::JumpIfTrue(0))),
+                    _ => None,
+                };
+                self.expr(rhs)?;
+                self.emit(match op {
+                    T![+] => Op::Add,
+                    T![-] => Op::Sub,
+                    T![*] => Op::Mul,
+                    T![/] => Op::Div,
+                    T![^] => Op::Pow,
+                    T![==] => Op::Eq,
+                    T![!=] => Op::Ne,
+                    T![<] => Op::Lt,
+                    T![<=] => Op::Le,
+                    T![>] => Op::Gt,
+                    T![>=] => Op::Ge,
+                    T![&&] => Op::And,
+                    T![||] => Op::Or,
+                    _ => unreachable!("`{}` is not an infix operator", op),
+                });
+                if let Some(jump) = jump {
+                    let target = index(self.chunk.code.len(), "instructions")?;
+                    self.chunk.code[jump] = match self.chunk.code[jump] {
+                        Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+                        _ => Op::JumpIfTrue(target),
+                    };
+                }
+            }
+            Expr::PostfixOp { op, expr } => {
+                self.expr(expr)?;
+                self.emit(match op {
+                    T![!] => Op::Factorial,
+                    _ => unreachable!("`{}` is not a postfix operator", op),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Append `op` and return its position.
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    /// Push `value`, sharing a pool entry with an identical constant.
+    fn constant(&mut self, value: Value) -> Result<(), RuntimeError> {
+        let position = self
+            .chunk
+            .constants
+            .iter()
+            .position(|c| identical(c, &value));
+        let constant = position.unwrap_or_else(|| {
+            self.chunk.constants.push(value);
+            self.chunk.constants.len() - 1
+        });
+        self.emit(Op::Constant(index(constant, "constants")?));
+        Ok(())
+    }
+}
+
+/// Whether `a` and `b` are the same value. Unlike `==`, this tells `0.0`
+/// from `-0.0`, which divide to infinities of different signs, and finds
+/// NaN.
+fn identical(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        _ => a == b,
+    }
+}
+
+/// The operand for the `i`th of `what`.
+fn index(i: usize, what: &'static str) -> Result<u16, RuntimeError> {
+    u16::try_from(i).map_err(|_| RuntimeError::ChunkLimit {
+        what,
+        max: usize::from(u16::MAX) + 1,
+    })
+}
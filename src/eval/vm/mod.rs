@@ -0,0 +1,334 @@
+//! A bytecode compiler and a stack-based virtual machine.
+//!
+//! [`compile`] turns an expression into a [`Chunk`]: a flat list of
+//! [`Op`]s with a constant pool and tables of the variables and functions
+//! they refer to. [`Vm::run`] executes a chunk with the values of its
+//! variables, and [`Chunk::disassemble`] lists it for debugging. A chunk
+//! handles integer overflow with the policy it was compiled with, see
+//! [`compile_with_overflow`].
+//!
+//! The grammar only has expressions so far, so a chunk is a single straight
+//! sequence with forward jumps for `&&` and `||`, and calls only go to host
+//! functions. There are no statements, loops or user-defined functions to
+//! compile, and therefore no call frames.
+
+mod chunk;
+mod compiler;
+
+pub use chunk::{Chunk, Op};
+pub use compiler::{compile, compile_with_overflow};
+
+use super::{function, infix, postfix, prefix, OverflowPolicy, RuntimeError, Value};
+use crate::{lexer::TokenKind, T};
+
+/// Executes chunks. The stack is kept between runs to reuse its allocation.
+#[derive(Debug, Default)]
+pub struct Vm {
+    stack: Vec<Value>,
+}
+
+impl Vm {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `chunk` with `values[i]` as the value of the variable in slot `i`.
+    ///
+    /// # Errors
+    /// If evaluating the expression fails, like with the
+    /// [`Interpreter`](super::Interpreter).
+    ///
+    /// # Panics
+    /// If the number of values differs from the number of slots,
+    /// or if the bytecode is malformed.
+    pub fn run(&mut self, chunk: &Chunk, values: &[Value]) -> Result<Value, RuntimeError> {
+        assert_eq!(
+            values.len(),
+            chunk.slots.len(),
+            "expected a value for each of {:?}",
+            chunk.slots
+        );
+        self.stack.clear();
+
+        let overflow = chunk.overflow;
+        let mut ip = 0;
+        loop {
+            let op = chunk.code[ip];
+            ip += 1;
+
+            let result = match op {
+                Op::Constant(index) => chunk.constants[usize::from(index)].clone(),
+                Op::Load(slot) => values[usize::from(slot)].clone(),
+                Op::Plus => prefix(T![+], self.pop(), overflow)?,
+                Op::Neg => prefix(T![-], self.pop(), overflow)?,
+                Op::Not => prefix(T![!], self.pop(), overflow)?,
+                Op::Add => self.infix(T![+], overflow)?,
+                Op::Sub => self.infix(T![-], overflow)?,
+                Op::Mul => self.infix(T![*], overflow)?,
+                Op::Div => self.infix(T![/], overflow)?,
+                Op::Pow => self.infix(T![^], overflow)?,
+                Op::Eq => self.infix(T![==], overflow)?,
+                Op::Ne => self.infix(T![!=], overflow)?,
+                Op::Lt => self.infix(T![<], overflow)?,
+                Op::Le => self.infix(T![<=], overflow)?,
+                Op::Gt => self.infix(T![>], overflow)?,
+                Op::Ge => self.infix(T![>=], overflow)?,
+                Op::And => self.infix(T![&&], overflow)?,
+                Op::Or => self.infix(T![||], overflow)?,
+                Op::Factorial => postfix(T![!], self.pop(), overflow)?,
+                Op::JumpIfFalse(target) => {
+                    if self.stack.last() == Some(&Value::Bool(false)) {
+                        ip = usize::from(target);
+                    }
+                    continue;
+                }
+                Op::JumpIfTrue(target) => {
+                    if self.stack.last() == Some(&Value::Bool(true)) {
+                        ip = usize::from(target);
+                    }
+                    continue;
+                }
+                Op::Call { function, argc } => {
+                    let (name, overloads) = &chunk.functions[usize::from(function)];
+                    let start = self.stack.len() - usize::from(argc);
+                    let result =
+                        function::call(name, overloads, &mut self.stack[start..], overflow);
+                    self.stack.truncate(start);
+                    result?
+                }
+                Op::Return => return Ok(self.pop()),
+            };
+            self.stack.push(result);
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn infix(&mut self, op: TokenKind, overflow: OverflowPolicy) -> Result<Value, RuntimeError> {
+        let rhs = self.pop();
+        let lhs = self.pop();
+        infix(op, &lhs, &rhs, overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::{self, Env, Interpreter},
+        parser::{
+            ast::{Expr, Lit},
+            Parser,
+        },
+    };
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    #[test]
+    fn disassemble() {
+        let chunk = compile(
+            &parse(r#"x > 0 && sqrt(x) != 2 * PI || name == "x" + "y""#),
+            &Env::with_prelude(),
+        )
+        .unwrap();
+        assert_eq!(
+            chunk.disassemble(),
+            unindent::unindent(
+                r#"
+                0000 load     0     ; x
+                0001 constant 0     ; 0
+                0002 gt
+                0003 jump-if-false 0011
+                0004 load     0     ; x
+                0005 call     0     ; sqrt/1
+                0006 constant 1     ; 2
+                0007 constant 2     ; 3.141592653589793
+                0008 mul
+                0009 ne
+                0010 and
+                0011 jump-if-true  0018
+                0012 load     1     ; name
+                0013 constant 3     ; "x"
+                0014 constant 4     ; "y"
+                0015 add
+                0016 eq
+                0017 or
+                0018 return
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn constants_are_shared() {
+        let chunk = compile(&parse("1 + x * 1 - 1.5 / 1.5"), &Env::new()).unwrap();
+        assert_eq!(chunk.constants, [Value::Int(1), Value::Float(1.5)]);
+    }
+
+    #[test]
+    fn signed_zeros_are_separate_constants() {
+        let mut env = Env::new();
+        env.define_const("NEG_ZERO", -0.0);
+        let chunk = compile(&parse("1 / 0.0 > 0 && 1 / NEG_ZERO < 0"), &env).unwrap();
+        assert_eq!(chunk.constants.len(), 4);
+        assert_eq!(Vm::new().run(&chunk, &[]), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn limits_are_errors() {
+        // A balanced sum of ones, so that compiling does not recurse
+        // too deeply. It has twice as many instructions, minus one.
+        fn ones(n: usize) -> Expr {
+            if n == 1 {
+                return Expr::Literal(Lit::Int(1));
+            }
+            Expr::InfixOp {
+                op: T![+],
+                lhs: Box::new(ones(n / 2)),
+                rhs: Box::new(ones(n - n / 2)),
+            }
+        }
+
+        let args = vec!["0"; 256].join(", ");
+        assert_eq!(
+            compile(&parse(&format!("f({args})")), &Env::new()).unwrap_err(),
+            RuntimeError::ChunkLimit {
+                what: "arguments in a call",
+                max: 255
+            }
+        );
+
+        let skip = |n| Expr::InfixOp {
+            op: T![||],
+            lhs: Box::new(Expr::Ident("x".to_string())),
+            rhs: Box::new(ones(n)),
+        };
+        // The `||` jumps to the instruction after its own `or`.
+        assert!(compile(&skip(32766), &Env::new()).is_ok());
+        assert_eq!(
+            compile(&skip(32767), &Env::new()).unwrap_err().to_string(),
+            "a chunk can have at most 65536 instructions"
+        );
+    }
+
+    #[test]
+    fn literal_errors_are_reported_when_compiling() {
+        assert_eq!(
+            compile(&parse("x + 9999999999999999999"), &Env::new()).unwrap_err(),
+            RuntimeError::LiteralOutOfRange("9999999999999999999".to_string())
+        );
+    }
+
+    /// The virtual machine agrees with the tree-walker,
+    /// including on the errors.
+    #[test]
+    fn agrees_with_interpreter() {
+        let mut env = Env::with_prelude();
+        env.set("x", 3);
+        env.set("y", 2.5);
+        env.set("s", "text");
+
+        let mut vm = Vm::new();
+        for input in [
+            "x * x - 2 * x + 1",
+            "x ^ 2 ^ 2 / y",
+            "-x! + abs(-x) + +x",
+            "min(x, y) + max(x, 7) + sqrt(x)",
+            r#"s + "!" == "text!" && !(x < y)"#,
+            "x > 5 && 1",
+            "x < 5 || 1",
+            "x < 5 && 1",
+            "x > 5 && 1 || y >= 2.5 && x <= 3",
+            "x / 0",
+            "(-x)!",
+            "x + s",
+            "nope(x)",
+            "nope(x / 0)",
+            "sqrt(x, y)",
+            "x ^ 100",
+        ] {
+            let expr = parse(input);
+            let chunk = compile(&expr, &env).unwrap();
+            let values: Vec<Value> = chunk
+                .slots
+                .iter()
+                .map(|slot| env.get(slot).cloned().unwrap())
+                .collect();
+            assert_eq!(
+                vm.run(&chunk, &values),
+                Interpreter::new().eval(&expr, &env),
+                "evaluating `{input}`"
+            );
+        }
+    }
+
+    /// All three engines handle overflow the same way under each policy.
+    #[test]
+    fn engines_agree_under_every_overflow_policy() {
+        let mut env = Env::with_prelude();
+        env.set("big", i64::MAX);
+        env.set("x", 3);
+
+        let mut vm = Vm::new();
+        for overflow in [
+            OverflowPolicy::Error,
+            OverflowPolicy::Wrap,
+            OverflowPolicy::Promote,
+        ] {
+            for input in [
+                "big + 1",
+                "-(0 - big - 1)",
+                "big * big - x",
+                "2 ^ 64 + x ^ 40",
+                "25! / 24!",
+                "100!",
+                "(2 ^ 64)!",
+                "9 ^ 9 ^ 9",
+                "9223372036854775808 - 1",
+                "18446744073709551617",
+                "big + 1 > 1.0e18 && min(big + x, 0.5) < 1",
+                "abs(-big - 1)",
+            ] {
+                let expr = parse(input);
+                let expected = Interpreter::with_overflow(overflow).eval(&expr, &env);
+
+                let compiled = eval::compile_with_overflow(&expr, &env, overflow);
+                let values = compiled.bind(&env).unwrap();
+                assert_eq!(
+                    compiled.eval(&values),
+                    expected,
+                    "compiling `{input}` under {overflow:?}"
+                );
+
+                let result = compile_with_overflow(&expr, &env, overflow).and_then(|chunk| {
+                    let values: Vec<Value> = chunk
+                        .slots
+                        .iter()
+                        .map(|slot| env.get(slot).cloned().unwrap())
+                        .collect();
+                    vm.run(&chunk, &values)
+                });
+                assert_eq!(result, expected, "running `{input}` under {overflow:?}");
+            }
+        }
+
+        let run = |overflow| {
+            let chunk = compile_with_overflow(&parse("big + 1"), &env, overflow).unwrap();
+            Vm::new().run(&chunk, &[Value::Int(i64::MAX)])
+        };
+        assert_eq!(
+            run(OverflowPolicy::Error),
+            Err(RuntimeError::Overflow { op: T![+] })
+        );
+        assert_eq!(run(OverflowPolicy::Wrap), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            run(OverflowPolicy::Promote),
+            Ok(Value::BigInt(num_bigint::BigInt::from(i64::MAX) + 1))
+        );
+    }
+}
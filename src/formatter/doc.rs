@@ -0,0 +1,227 @@
+//! A small document algebra in the style of Wadler's "prettier printer".
+//!
+//! A [`Doc`] describes text together with the places where it may be broken
+//! into multiple lines. A [`Doc::Group`] is printed on a single line if it
+//! fits into the remaining width, otherwise all of its own line breaks are
+//! taken. Nested groups decide for themselves.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    Nil,
+    Text(String),
+    /// A space if the enclosing group fits on one line, a newline otherwise.
+    Line,
+    /// Nothing if the enclosing group fits on one line, a newline otherwise.
+    SoftLine,
+    /// Always a newline. Forces all enclosing groups to break.
+    HardLine,
+    /// Forces all enclosing groups to break, without printing anything.
+    BreakParent,
+    Concat(Vec<Doc>),
+    /// Indents every newline inside by one level.
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+    /// Prints the first document if the enclosing group is broken,
+    /// and the second one if it is not.
+    IfBreak(Box<Doc>, Box<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    #[must_use]
+    pub fn nest(doc: Doc) -> Doc {
+        Doc::Nest(Box::new(doc))
+    }
+
+    #[must_use]
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+
+    #[must_use]
+    pub fn if_break(broken: Doc, flat: Doc) -> Doc {
+        Doc::IfBreak(Box::new(broken), Box::new(flat))
+    }
+
+    /// Lay the document out into lines of at most `max_width` characters
+    /// where possible, indenting by `indent` spaces per level.
+    #[must_use]
+    pub fn render(&self, max_width: usize, indent: usize) -> String {
+        let mut printer = Printer {
+            out: String::new(),
+            column: 0,
+            max_width,
+            indent,
+        };
+        printer.print(self);
+        printer.out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+struct Printer {
+    out: String,
+    column: usize,
+    max_width: usize,
+    indent: usize,
+}
+
+impl Printer {
+    fn print(&mut self, doc: &Doc) {
+        // Work items are popped from the back.
+        let mut stack = vec![(0, Mode::Break, doc)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Nil | Doc::BreakParent => {}
+                Doc::Text(text) => {
+                    self.out.push_str(text);
+                    self.column += text.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    self.out.push(' ');
+                    self.column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => self.newline(indent),
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+                Doc::Nest(doc) => stack.push((indent + self.indent, mode, doc)),
+                Doc::Group(doc) => {
+                    #[allow(clippy::cast_possible_wrap)]
+                    let remaining = self.max_width as isize - self.column as isize;
+                    let mode = if mode == Mode::Flat || fits(remaining, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::IfBreak(broken, flat) => {
+                    stack.push((
+                        indent,
+                        mode,
+                        if mode == Mode::Break { broken } else { flat },
+                    ));
+                }
+            }
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(indent));
+        self.column = indent;
+    }
+}
+
+/// Whether `doc` fits into `remaining` columns when printed flat, together
+/// with whatever follows it on the same line.
+#[allow(clippy::cast_possible_wrap)]
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    // `true` marks parts of `doc`, which must be flat.
+    let mut stack = vec![(Mode::Flat, true, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, must_be_flat, doc) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, false, doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::HardLine => return !must_be_flat,
+            Doc::BreakParent if must_be_flat => return false,
+            Doc::Nil | Doc::BreakParent => {}
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, must_be_flat, doc)));
+            }
+            Doc::Nest(doc) | Doc::Group(doc) => stack.push((mode, must_be_flat, doc)),
+            Doc::IfBreak(broken, flat) => {
+                stack.push((
+                    mode,
+                    must_be_flat,
+                    if mode == Mode::Break { broken } else { flat },
+                ));
+            }
+        }
+
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[&str]) -> Doc {
+        let mut inner = vec![Doc::SoftLine];
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                inner.push(Doc::text(","));
+                inner.push(Doc::Line);
+            }
+            inner.push(Doc::text(*arg));
+        }
+        inner.push(Doc::if_break(Doc::text(","), Doc::Nil));
+        Doc::group(Doc::Concat(vec![
+            Doc::text(format!("{name}(")),
+            Doc::nest(Doc::Concat(inner)),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]))
+    }
+
+    #[test]
+    fn group_stays_flat_if_it_fits() {
+        let doc = call("f", &["a", "b"]);
+        assert_eq!(doc.render(80, 4), "f(a, b)");
+    }
+
+    #[test]
+    fn group_breaks_if_too_wide() {
+        let doc = call("f", &["aaaa", "bbbb"]);
+        assert_eq!(doc.render(10, 4), "f(\n    aaaa,\n    bbbb,\n)");
+    }
+
+    #[test]
+    fn text_after_group_counts() {
+        // `f(a, b)` alone has 7 characters, but the `;` needs an 8th.
+        let doc = Doc::Concat(vec![call("f", &["a", "b"]), Doc::text(";")]);
+        assert_eq!(doc.render(7, 2), "f(\n  a,\n  b,\n);");
+    }
+
+    #[test]
+    fn hard_line_breaks_enclosing_groups() {
+        let doc = Doc::group(Doc::Concat(vec![
+            Doc::text("a"),
+            Doc::Line,
+            Doc::group(Doc::Concat(vec![
+                Doc::text("b"),
+                Doc::HardLine,
+                Doc::text("c"),
+            ])),
+        ]));
+        assert_eq!(doc.render(80, 4), "a\nb\nc");
+    }
+}
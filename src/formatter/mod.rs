@@ -0,0 +1,554 @@
+//! A canonical code formatter.
+//!
+//! The formatter works on the lossless CST, so that comments survive.
+//! It turns the tree into a [`Doc`] and lets the document algebra decide
+//! where lines need to be broken to stay within the configured width.
+
+mod doc;
+
+pub use doc::Doc;
+
+use crate::{
+    lexer::{Token, TokenCategory},
+    parser::{
+        cst::{self, SyntaxElement, SyntaxNode},
+        event::NodeKind,
+        Operator,
+    },
+    T,
+};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// The width lines are broken at, where possible.
+    pub max_width: usize,
+    /// Spaces per indentation level.
+    pub indent: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            max_width: 100,
+            indent: 4,
+        }
+    }
+}
+
+/// Format `source` in the canonical style.
+///
+/// Input with syntax errors is returned unchanged.
+/// Formatting the output again does not change it any further.
+#[must_use]
+pub fn format(source: &str, config: FormatConfig) -> String {
+    let parse = cst::parse(source);
+    if !parse.errors.is_empty() {
+        return source.to_string();
+    }
+
+    let formatter = Formatter::new(source, &parse.root);
+    formatter
+        .root(&parse.root)
+        .render(config.max_width, config.indent)
+}
+
+struct Formatter<'input> {
+    source: &'input str,
+    /// Starts of the comments that are on a line of their own,
+    /// as opposed to following some code on the same line.
+    own_line_comments: HashSet<u32>,
+    /// Starts of the comments that nothing but whitespace follows on their
+    /// line, which includes all line comments.
+    line_ending_comments: HashSet<u32>,
+}
+
+#[derive(Clone, Copy)]
+struct Comment<'input> {
+    text: &'input str,
+    own_line: bool,
+    /// The comment ends its line, so a line break must follow it.
+    ends_line: bool,
+}
+
+/// Where the output is after some comments.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum After {
+    /// At the start of a line.
+    LineStart,
+    /// Behind code or a block comment, on the same line.
+    Code,
+    /// Behind a comment that ends the line.
+    LineEnd,
+}
+
+/// A significant child of a node, together with the comments in front of it.
+struct Item<'node, 'input> {
+    element: &'node SyntaxElement,
+    comments: Vec<Comment<'input>>,
+}
+
+impl<'input> Formatter<'input> {
+    fn new(source: &'input str, root: &SyntaxNode) -> Self {
+        let mut own_line_comments = HashSet::new();
+        let mut line_ending_comments = HashSet::new();
+        let mut at_line_start = true;
+        // The previous comment, if nothing but spaces followed it so far.
+        let mut open_comment = None;
+
+        for token in root.tokens() {
+            let text = token.text(source);
+            match token.kind.category() {
+                TokenCategory::Comment => {
+                    if at_line_start {
+                        own_line_comments.insert(token.span.start);
+                    }
+                    // Line comments include their line break.
+                    at_line_start = text.ends_with('\n');
+                    if at_line_start {
+                        line_ending_comments.insert(token.span.start);
+                        open_comment = None;
+                    } else {
+                        open_comment = Some(token.span.start);
+                    }
+                }
+                TokenCategory::Whitespace if text.contains('\n') => {
+                    at_line_start = true;
+                    line_ending_comments.extend(open_comment.take());
+                }
+                TokenCategory::Whitespace => {}
+                TokenCategory::Eof => line_ending_comments.extend(open_comment.take()),
+                _ => {
+                    at_line_start = false;
+                    open_comment = None;
+                }
+            }
+        }
+
+        Self {
+            source,
+            own_line_comments,
+            line_ending_comments,
+        }
+    }
+
+    fn comment(&self, token: Token) -> Comment<'input> {
+        Comment {
+            text: token.text(self.source).trim_end(),
+            own_line: self.own_line_comments.contains(&token.span.start),
+            ends_line: self.line_ending_comments.contains(&token.span.start),
+        }
+    }
+
+    /// Split the children of `node` into its significant items,
+    /// and the comments after the last of them.
+    fn items<'node>(
+        &self,
+        node: &'node SyntaxNode,
+    ) -> (Vec<Item<'node, 'input>>, Vec<Comment<'input>>) {
+        let mut items = Vec::new();
+        let mut comments = Vec::new();
+
+        for child in &node.children {
+            match child {
+                SyntaxElement::Token(token) if token.kind == T![ws] => {}
+                SyntaxElement::Token(token) if token.kind.is_trivia() => {
+                    comments.push(self.comment(*token));
+                }
+                _ => items.push(Item {
+                    element: child,
+                    comments: std::mem::take(&mut comments),
+                }),
+            }
+        }
+
+        (items, comments)
+    }
+
+    fn root(&self, root: &SyntaxNode) -> Doc {
+        let (items, dangling) = self.items(root);
+        let mut docs = Vec::new();
+
+        for item in &items {
+            // Nothing precedes the expression.
+            if !item.comments.is_empty() {
+                let after = push_comments(&mut docs, &item.comments, After::LineStart);
+                docs.push(before_code(after));
+            }
+            docs.push(self.element(item.element));
+        }
+        let start = if items.is_empty() {
+            After::LineStart
+        } else {
+            After::Code
+        };
+        push_comments(&mut docs, &dangling, start);
+        if !docs.is_empty() {
+            docs.push(Doc::HardLine);
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn element(&self, element: &SyntaxElement) -> Doc {
+        match element {
+            SyntaxElement::Node(node) => self.node(node),
+            SyntaxElement::Token(token) => Doc::text(token.text(self.source)),
+        }
+    }
+
+    fn node(&self, node: &SyntaxNode) -> Doc {
+        match node.kind {
+            NodeKind::FnCall => self.fn_call(node),
+            NodeKind::Paren => self.paren(node),
+            NodeKind::InfixOp => self.infix_chain(node),
+            NodeKind::Root => self.root(node),
+            NodeKind::Literal
+            | NodeKind::Ident
+            | NodeKind::PrefixOp
+            | NodeKind::PostfixOp
+            | NodeKind::Error => {
+                // No line breaks in here, just tokens and operands.
+                let (items, dangling) = self.items(node);
+                let mut docs = Vec::new();
+                for item in &items {
+                    docs.push(Self::inline_comments(&item.comments));
+                    docs.push(self.element(item.element));
+                }
+                docs.push(Self::inline_comments(&dangling));
+                Doc::Concat(docs)
+            }
+        }
+    }
+
+    /// `name(arg, arg)`, or one argument per line if they don't fit.
+    fn fn_call(&self, node: &SyntaxNode) -> Doc {
+        let (items, _) = self.items(node);
+        let (name, rest) = items.split_first().expect("calls start with a name");
+        let (l_paren, rest) = rest.split_first().expect("calls have a `(`");
+        let (r_paren, args) = rest.split_last().expect("calls have a `)`");
+
+        let mut inner = Vec::new();
+        let mut first = true;
+        for item in args {
+            if matches!(item.element, SyntaxElement::Token(t) if t.kind == T![,]) {
+                // Comments in front of a comma stay in front of it. The comma
+                // itself follows once it is known whether it is the last one.
+                if push_comments(&mut inner, &item.comments, After::Code) == After::LineEnd {
+                    inner.push(Doc::HardLine);
+                }
+                continue;
+            }
+
+            if !first {
+                inner.push(Doc::text(","));
+            }
+            inner.push(Self::separated(
+                &item.comments,
+                if first { Doc::SoftLine } else { Doc::Line },
+                self.element(item.element),
+            ));
+            first = false;
+        }
+        if !first {
+            inner.push(Doc::if_break(Doc::text(","), Doc::Nil));
+        }
+        inner.push(Self::closing_comments(&r_paren.comments));
+
+        Doc::group(Doc::Concat(vec![
+            self.element(name.element),
+            Self::inline_comments(&l_paren.comments),
+            Doc::text("("),
+            Doc::nest(Doc::Concat(inner)),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]))
+    }
+
+    /// `(expr)`, with the expression on its own line if it doesn't fit.
+    fn paren(&self, node: &SyntaxNode) -> Doc {
+        let (items, _) = self.items(node);
+        let (r_paren, rest) = items.split_last().expect("parentheses are closed");
+        let inner = rest.get(1).map_or(Doc::Nil, |item| {
+            Self::separated(&item.comments, Doc::SoftLine, self.element(item.element))
+        });
+
+        Doc::group(Doc::Concat(vec![
+            Doc::text("("),
+            Doc::nest(Doc::Concat(vec![
+                inner,
+                Self::closing_comments(&r_paren.comments),
+            ])),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]))
+    }
+
+    /// A chain of infix operations with the same precedence, like `a + b - c`
+    /// or `a ^ b ^ c`, with one operator per line if it doesn't fit.
+    fn infix_chain(&self, node: &SyntaxNode) -> Doc {
+        let mut chain = Vec::new();
+        self.flatten_chain(node, &mut chain);
+
+        // Operands and operators alternate, starting with an operand.
+        let mut chain = chain.into_iter();
+        let head = chain.next().expect("chains start with an operand");
+        let mut tail = Vec::new();
+        while let (Some(op), Some(operand)) = (chain.next(), chain.next()) {
+            tail.push(Self::separated(
+                &op.comments,
+                Doc::Line,
+                self.element(op.element),
+            ));
+            if operand.comments.is_empty() {
+                tail.push(Doc::text(" "));
+            } else {
+                tail.push(Self::inline_comments(&operand.comments));
+            }
+            tail.push(self.element(operand.element));
+        }
+
+        Doc::group(Doc::Concat(vec![
+            Self::inline_comments(&head.comments),
+            self.element(head.element),
+            Doc::nest(Doc::Concat(tail)),
+        ]))
+    }
+
+    /// Collect the operands and operators of `node`, descending into
+    /// operands that are operations with the same binding power.
+    fn flatten_chain<'node>(&self, node: &'node SyntaxNode, chain: &mut Vec<Item<'node, 'input>>) {
+        let (items, _) = self.items(node);
+        for item in items {
+            match item.element {
+                SyntaxElement::Node(operand)
+                    if operand.kind == NodeKind::InfixOp
+                        && binding_power(operand) == binding_power(node)
+                        && item.comments.is_empty() =>
+                {
+                    self.flatten_chain(operand, chain);
+                }
+                _ => chain.push(item),
+            }
+        }
+    }
+
+    /// `doc` behind the line break `separator`. Comments that trail the
+    /// previous code stay on its line, the others go in front of `doc`.
+    fn separated(comments: &[Comment], separator: Doc, doc: Doc) -> Doc {
+        // Up to the first comment that ends the line, unless one of them
+        // starts a line of its own.
+        let trailing = comments
+            .iter()
+            .position(|comment| comment.own_line || comment.ends_line)
+            .filter(|&i| !comments[i].own_line)
+            .map_or(0, |i| i + 1);
+
+        let mut docs = Vec::new();
+        push_comments(&mut docs, &comments[..trailing], After::Code);
+        docs.push(separator);
+        let after = push_comments(&mut docs, &comments[trailing..], After::LineStart);
+        docs.extend([before_code(after), doc]);
+        Doc::Concat(docs)
+    }
+
+    /// Comments in front of a closing parenthesis.
+    fn closing_comments(comments: &[Comment]) -> Doc {
+        let mut docs = Vec::new();
+        push_comments(&mut docs, comments, After::Code);
+        Doc::Concat(docs)
+    }
+
+    /// Comments at a place where the code has no line break of its own.
+    fn inline_comments(comments: &[Comment]) -> Doc {
+        if comments.is_empty() {
+            return Doc::Nil;
+        }
+        let mut docs = Vec::new();
+        let after = push_comments(&mut docs, comments, After::Code);
+        docs.push(before_code(after));
+        Doc::Concat(docs)
+    }
+}
+
+/// Push `comments` in source order, starting `after` what is already there.
+/// Comments that started a line in the source start one again, the others
+/// stay on the line of whatever precedes them.
+fn push_comments(docs: &mut Vec<Doc>, comments: &[Comment], mut after: After) -> After {
+    for comment in comments {
+        match after {
+            After::LineStart => {}
+            After::Code if !comment.own_line => docs.push(Doc::text(" ")),
+            After::Code | After::LineEnd => docs.push(Doc::HardLine),
+        }
+        docs.push(Doc::text(comment.text));
+        after = if comment.ends_line {
+            docs.push(Doc::BreakParent);
+            After::LineEnd
+        } else {
+            After::Code
+        };
+    }
+    after
+}
+
+/// What separates comments from the code that follows them.
+fn before_code(after: After) -> Doc {
+    match after {
+        After::LineStart => Doc::Nil,
+        After::Code => Doc::text(" "),
+        After::LineEnd => Doc::HardLine,
+    }
+}
+
+/// Operators that bind equally strongly form one chain.
+fn binding_power(node: &SyntaxNode) -> Option<(u8, u8)> {
+    node.child_tokens()
+        .find(|token| !token.kind.is_trivia())
+        .and_then(|op| op.kind.infix_binding_power())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str, max_width: usize) -> String {
+        format(
+            source,
+            FormatConfig {
+                max_width,
+                indent: 4,
+            },
+        )
+    }
+
+    #[test]
+    fn canonical_spacing() {
+        assert_eq!(
+            fmt("min ( test+4 , sin(2*PI ))", 100),
+            "min(test + 4, sin(2 * PI))\n"
+        );
+        assert_eq!(fmt("- x !  ==( a)", 100), "-x! == (a)\n");
+    }
+
+    #[test]
+    fn breaks_long_calls() {
+        assert_eq!(
+            fmt("min(first_argument, second_argument + 1)", 30),
+            "min(\n    first_argument,\n    second_argument + 1,\n)\n"
+        );
+    }
+
+    #[test]
+    fn breaks_long_chains() {
+        assert_eq!(
+            fmt("alpha + beta - gamma * delta + epsilon", 20),
+            "alpha\n    + beta\n    - gamma * delta\n    + epsilon\n"
+        );
+        assert_eq!(
+            fmt("is_visible && width > minimum_width || force", 40),
+            "is_visible && width > minimum_width\n    || force\n"
+        );
+    }
+
+    #[test]
+    fn nested_groups_break_from_the_outside() {
+        assert_eq!(
+            fmt("outer(inner(aaaa, bbbb), cccc)", 26),
+            "outer(\n    inner(aaaa, bbbb),\n    cccc,\n)\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        assert_eq!(
+            fmt("// leading\nf(a, // first\n  b)\n// trailing\n", 100),
+            "// leading\nf(\n    a, // first\n    b,\n)\n// trailing\n"
+        );
+        assert_eq!(fmt("a // after a\n + b", 100), "a // after a\n    + b\n");
+        assert_eq!(fmt("(\n// inside\nx)", 100), "(\n    // inside\n    x\n)\n");
+        assert_eq!(fmt("a /* b */ + c", 100), "a /* b */ + c\n");
+        assert_eq!(fmt("a + /* b */ c", 100), "a + /* b */ c\n");
+        assert_eq!(
+            fmt("/* a */ f( /* b */ x /* c */)", 100),
+            "/* a */ f(/* b */ x /* c */)\n"
+        );
+        assert_eq!(fmt("-/* a */x", 100), "- /* a */ x\n");
+        assert_eq!(fmt("(\n/* a */ x)", 100), "(/* a */ x)\n");
+        assert_eq!(
+            fmt("a /* b */ // c\n + d", 100),
+            "a /* b */ // c\n    + d\n"
+        );
+        assert_eq!(fmt("/// doc\nx // end", 100), "/// doc\nx // end\n");
+        assert_eq!(
+            fmt("a + /* c */ // l\nb", 100),
+            "a\n    + /* c */ // l\n    b\n"
+        );
+        assert_eq!(fmt("-/* a */ // b\nx", 100), "- /* a */ // b\nx\n");
+        assert_eq!(
+            fmt("a + // x\n// y\nb", 100),
+            "a\n    + // x\n    // y\n    b\n"
+        );
+        assert_eq!(fmt("f(a /* c */, b)", 100), "f(a /* c */, b)\n");
+        assert_eq!(
+            fmt("f(a // c\n, /* d */ b)", 100),
+            "f(\n    a // c\n    ,\n    /* d */ b,\n)\n"
+        );
+        assert_eq!(
+            fmt("f(a /* c */, // l\nb)", 100),
+            "f(\n    a /* c */, // l\n    b,\n)\n"
+        );
+    }
+
+    #[test]
+    fn invalid_input_is_unchanged() {
+        assert_eq!(fmt("f(a,, b", 100), "f(a,, b");
+    }
+
+    #[test]
+    fn idempotent() {
+        let sources = [
+            "",
+            "// only a comment\n",
+            "42",
+            "min ( test + 4 , sin(2*PI ))",
+            "2.0 / ((3.0 + 4.0) * (5.0 - 6.0)) * 7.0",
+            "f(a, // first\n b // second\n , c)",
+            "f(a\n// own line\n, b)",
+            "f( // open\n)",
+            "a + // after plus\n b",
+            "a\n// before plus\n+ b",
+            "- // after minus\n x",
+            "x // before bang\n!",
+            "(a // inside\n)",
+            "(\n// own\na)",
+            "g(h(i(j(k(l(m(n(o(p(q(r(s(t(u(v(w(x(y(z)))))))))))))))))))",
+            "aaaaaaaaaa * bbbbbbbbbb + cccccccccc * dddddddddd - eeeeeeeeee / ffffffffff",
+            "a ^ b ^ c ^ d ^ eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+            "x // trailing at the end\n",
+            "x // no newline at the end",
+            "f(a /* inline */, /* nested /* block */ */ b)",
+            "a /* before plus */ + /* after plus */ b * /* c */ c",
+            "/* first */ -/* a */x /* last */",
+            "//! inner doc\n/// doc\nx",
+            "a + /* c */ // l\nb",
+            "-/* a */ // b\nx",
+            "a + // x\n// y\nb",
+            "f(a /* c */, // l\nb /* d */\n// e\n, c)",
+        ];
+
+        for source in sources {
+            for width in [10, 40, 100] {
+                let once = fmt(source, width);
+                let twice = fmt(&once, width);
+                assert_eq!(once, twice, "formatting `{source}` at width {width}");
+                if cst::parse(source).errors.is_empty() {
+                    assert!(
+                        cst::parse(&once).errors.is_empty(),
+                        "`{source}` formatted to invalid `{once}`"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Highlighting source code as HTML or for a terminal.
+//!
+//! Tokens are classified by their kind, and names by where the parser
+//! put them: the name of a call is a function. The grammar has no types or
+//! fields, so there is nothing to refine further. Input with syntax errors
+//! is highlighted as well, with the tokens the lexer could not make sense of
+//! marked as errors.
+
+use crate::{
+    lexer::{Span, Token, TokenCategory, TokenKind},
+    parser::{
+        cst::{self, SyntaxElement, SyntaxNode},
+        event::NodeKind,
+    },
+    T,
+};
+
+/// What a token is, as far as highlighting is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Keyword,
+    /// Numbers and strings.
+    Literal,
+    Operator,
+    /// Brackets and separators like `,` and `;`.
+    Punctuation,
+    Comment,
+    Identifier,
+    /// The name of a called function.
+    Function,
+    Error,
+}
+
+impl Category {
+    /// The CSS class of tokens in this category, like `hl-keyword`.
+    #[must_use]
+    pub fn class(self) -> &'static str {
+        match self {
+            Category::Keyword => "hl-keyword",
+            Category::Literal => "hl-literal",
+            Category::Operator => "hl-operator",
+            Category::Punctuation => "hl-punctuation",
+            Category::Comment => "hl-comment",
+            Category::Identifier => "hl-identifier",
+            Category::Function => "hl-function",
+            Category::Error => "hl-error",
+        }
+    }
+
+    /// The parameters of the ANSI escape sequence that styles tokens in
+    /// this category, or `None` for plain text.
+    #[must_use]
+    pub fn ansi(self) -> Option<&'static str> {
+        match self {
+            Category::Keyword => Some("35"),
+            Category::Literal => Some("32"),
+            Category::Operator => Some("36"),
+            Category::Comment => Some("90"),
+            Category::Function => Some("34"),
+            Category::Error => Some("4;31"),
+            Category::Punctuation | Category::Identifier => None,
+        }
+    }
+}
+
+/// All tokens of `input` in source order with their category, or `None`
+/// for whitespace and the end of the input. The tokens cover the whole input.
+#[must_use]
+pub fn classify(input: &str) -> Vec<(Token, Option<Category>)> {
+    let mut tokens = Vec::new();
+    collect(&cst::parse(input).root, &mut tokens);
+    tokens
+}
+
+fn collect(node: &SyntaxNode, tokens: &mut Vec<(Token, Option<Category>)>) {
+    for (i, child) in node.children.iter().enumerate() {
+        match child {
+            SyntaxElement::Node(child) => collect(child, tokens),
+            SyntaxElement::Token(token) => {
+                let category = match token.kind {
+                    // The name comes first in a call, trivia in front of it
+                    // belongs to the parent.
+                    T![ident] if node.kind == NodeKind::FnCall && i == 0 => {
+                        Some(Category::Function)
+                    }
+                    kind => category(kind),
+                };
+                tokens.push((*token, category));
+            }
+        }
+    }
+}
+
+fn category(kind: TokenKind) -> Option<Category> {
+    Some(match kind.category() {
+        TokenCategory::Keyword => Category::Keyword,
+        TokenCategory::Literal => Category::Literal,
+        TokenCategory::Operator => Category::Operator,
+        TokenCategory::Bracket | TokenCategory::Punctuation => Category::Punctuation,
+        TokenCategory::Identifier => Category::Identifier,
+        TokenCategory::Comment => Category::Comment,
+        TokenCategory::Error => Category::Error,
+        TokenCategory::Whitespace | TokenCategory::Eof => return None,
+    })
+}
+
+/// Highlight `input` as HTML: each token that has a category is wrapped in a
+/// `<span>` with the [class](Category::class) of its category. The result
+/// is meant to go into a `<pre>` element, and all text in it is escaped.
+#[must_use]
+pub fn html(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() * 2);
+    for (token, category) in classify(input) {
+        let text = escape_html(&input[token.span]);
+        match category {
+            Some(category) => {
+                output.push_str("<span class=\"");
+                output.push_str(category.class());
+                output.push_str("\">");
+                output.push_str(&text);
+                output.push_str("</span>");
+            }
+            None => output.push_str(&text),
+        }
+    }
+    output
+}
+
+/// Highlight `input` for a terminal with ANSI escape sequences.
+///
+/// Control characters in the input other than tabs and line breaks, which
+/// could only be part of error tokens, strings or comments, are replaced by
+/// their visible symbols, like `␛` for an escape. They would otherwise be
+/// interpreted by the terminal.
+#[must_use]
+pub fn ansi(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() * 2);
+    for (token, category) in classify(input) {
+        let text = escape_control(&input[token.span]);
+        match category.and_then(Category::ansi) {
+            Some(style) => {
+                output.push_str("\x1b[");
+                output.push_str(style);
+                output.push('m');
+                output.push_str(&text);
+                output.push_str("\x1b[0m");
+            }
+            None => output.push_str(&text),
+        }
+    }
+    output
+}
+
+/// The parts of `input` that are styled by [`ansi`], with their categories.
+/// Useful to check highlighting without comparing escape sequences.
+#[must_use]
+pub fn styled_spans(input: &str) -> Vec<(Span, Category)> {
+    classify(input)
+        .into_iter()
+        .filter_map(|(token, category)| Some((token.span, category?)))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_control(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\r' | '\t' => c,
+            // The control pictures block has a symbol for each of them.
+            '\0'..='\x1f' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+            '\x7f' => '\u{2421}',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_tokens_in_context() {
+        let categories: Vec<_> = styled_spans("max(x, 2) // y\n")
+            .into_iter()
+            .map(|(_, category)| category)
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                Category::Function,
+                Category::Punctuation,
+                Category::Identifier,
+                Category::Punctuation,
+                Category::Literal,
+                Category::Punctuation,
+                Category::Comment,
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_html() {
+        assert_eq!(
+            html("a < \"b&c\""),
+            "<span class=\"hl-identifier\">a</span> \
+             <span class=\"hl-operator\">&lt;</span> \
+             <span class=\"hl-literal\">&quot;b&amp;c&quot;</span>"
+        );
+    }
+
+    #[test]
+    fn renders_ansi() {
+        assert_eq!(
+            ansi("f(1) + x"),
+            "\x1b[34mf\x1b[0m(\x1b[32m1\x1b[0m) \x1b[36m+\x1b[0m x"
+        );
+    }
+
+    #[test]
+    fn marks_error_tokens() {
+        let input = "1 + \x1b#";
+        let spans = styled_spans(input);
+        assert_eq!(spans[2], (Span { start: 4, end: 6 }, Category::Error));
+        assert_eq!(
+            ansi(input),
+            "\x1b[32m1\x1b[0m \x1b[36m+\x1b[0m \x1b[4;31m\u{241b}#\x1b[0m"
+        );
+        assert!(html("#").contains("<span class=\"hl-error\">#</span>"));
+    }
+
+    #[test]
+    fn keeps_line_breaks() {
+        assert_eq!(
+            ansi("1 +\r\n\t2"),
+            "\x1b[32m1\x1b[0m \x1b[36m+\x1b[0m\r\n\t\x1b[32m2\x1b[0m"
+        );
+    }
+}
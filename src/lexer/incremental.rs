@@ -0,0 +1,184 @@
+//! Lexing only the part of the input that an edit affected.
+//!
+//! The token at some position only depends on the input from that position
+//! on. [`relex`] therefore starts lexing at the first token that could have
+//! looked into the edited text, and stops as soon as a token starts where an
+//! old token started, past the edit. The tokens from there on are the old
+//! ones, moved by the change in length.
+//!
+//! Most tokens look at most [`LOOKAHEAD`] characters past their end: a float
+//! like `1` has to rule out continuing as `1e+5`. Error tokens and
+//! unterminated comments are the exception. The lexer tried to match a
+//! string or block comment up to the end of the input for them, so any later
+//! edit can change them.
+
+use super::{Lexer, Span, Token};
+use crate::T;
+use std::ops::Range;
+
+/// How far past its end the lexer may have looked to decide on a token.
+const LOOKAHEAD: u32 = 3;
+
+/// A change of the input: `range` is replaced with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: impl Into<Span>, text: impl Into<String>) -> Self {
+        Self {
+            range: range.into(),
+            text: text.into(),
+        }
+    }
+
+    /// The `input` after the edit.
+    #[must_use]
+    pub fn apply(&self, input: &str) -> String {
+        let mut output = input.to_string();
+        output.replace_range(Range::<usize>::from(self.range), &self.text);
+        output
+    }
+
+    /// Where an offset of the old input ends up, for an offset behind the edit.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn shift(&self, offset: u32) -> u32 {
+        offset + self.text.len() as u32 - (self.range.end - self.range.start)
+    }
+}
+
+/// The result of [`relex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relexed {
+    /// The tokens of the whole edited input.
+    pub tokens: Vec<Token>,
+    /// The old tokens that were replaced, as indices into the old tokens.
+    pub removed: Range<usize>,
+    /// The new tokens that replaced them, as indices into `tokens`.
+    pub inserted: Range<usize>,
+}
+
+/// Update `tokens`, the result of lexing some input, for `edit`. `input` is
+/// the text after the edit. The tokens are the same as if all of `input`
+/// was lexed again.
+#[must_use]
+pub fn relex(tokens: &[Token], input: &str, edit: &TextEdit) -> Relexed {
+    let affected = |token: &Token| {
+        matches!(token.kind, T![error] | T![unterminated_comment])
+            || token.span.end + LOOKAHEAD > edit.range.start
+    };
+    let first = tokens.iter().position(affected).unwrap_or(tokens.len());
+
+    let mut lexer = Lexer::new(input);
+    lexer.position = tokens.get(first).map_or(0, |token| token.span.start);
+    #[allow(clippy::cast_possible_truncation)]
+    let edit_end = edit.range.start + edit.text.len() as u32;
+
+    let mut old = first;
+    let mut inserted = Vec::new();
+    for token in lexer {
+        if token.span.start >= edit_end {
+            // Old tokens in the edited range can never line up again.
+            while old < tokens.len()
+                && (tokens[old].span.start < edit.range.end
+                    || edit.shift(tokens[old].span.start) < token.span.start)
+            {
+                old += 1;
+            }
+            if old < tokens.len() && edit.shift(tokens[old].span.start) == token.span.start {
+                break;
+            }
+        }
+        inserted.push(token);
+    }
+    if inserted.last().is_some_and(|token| token.kind == T![EOF]) {
+        old = tokens.len();
+    }
+
+    let mut relexed = Vec::with_capacity(first + inserted.len() + tokens.len() - old);
+    relexed.extend_from_slice(&tokens[..first]);
+    relexed.extend_from_slice(&inserted);
+    relexed.extend(tokens[old..].iter().map(|token| Token {
+        kind: token.kind,
+        span: Span {
+            start: edit.shift(token.span.start),
+            end: edit.shift(token.span.end),
+        },
+    }));
+    Relexed {
+        tokens: relexed,
+        removed: first..old,
+        inserted: first..first + inserted.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, edit: &TextEdit) -> Relexed {
+        let tokens = Lexer::new(input).tokenize();
+        let edited = edit.apply(input);
+        let relexed = relex(&tokens, &edited, edit);
+        assert_eq!(
+            relexed.tokens,
+            Lexer::new(&edited).tokenize(),
+            "{input:?} after {edit:?}"
+        );
+        relexed
+    }
+
+    #[test]
+    fn relexes_only_around_the_edit() {
+        let input = "a + b * c + d";
+        // `b` becomes `bee`. Lexing starts at the space in front of the `+`,
+        // which is close enough to `b` to have looked at it.
+        let relexed = check(input, &TextEdit::new(4..5, "bee"));
+        assert_eq!(relexed.removed, 1..5);
+        assert_eq!(relexed.inserted, 1..5);
+
+        // Appending to an identifier relexes the tokens that could see it.
+        let relexed = check(input, &TextEdit::new(13..13, "ef"));
+        assert_eq!(relexed.removed, 10..13);
+    }
+
+    #[test]
+    fn tokens_that_looked_ahead() {
+        check("1 + 2", &TextEdit::new(1..1, ".5"));
+        check("1e 5", &TextEdit::new(2..3, "+"));
+        check("x = =", &TextEdit::new(3..4, ""));
+        check("\"abc + d", &TextEdit::new(8..8, "\""));
+        check("a / / b\n", &TextEdit::new(3..4, ""));
+        check("f(x) // note", &TextEdit::new(12..12, "\n"));
+        check("1 /* a */ + /* b", &TextEdit::new(16..16, " */"));
+        check("1 /* a /* b */ + c", &TextEdit::new(7..9, ""));
+        check("\"a\" + \"b\"", &TextEdit::new(0..1, ""));
+    }
+
+    #[test]
+    fn agrees_with_lexing_everything() {
+        const PIECES: [&str; 19] = [
+            "a", "1", ".", "e", "+", "-", "\"", "/", "*", "\n", " ", "=", "!", "&", "(", "if",
+            "\\", "_", "0x",
+        ];
+        let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+        let mut below = |n: usize| {
+            rng = rng
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (rng >> 33) as usize % n
+        };
+
+        for _ in 0..2000 {
+            let input: String = (0..below(20))
+                .map(|_| PIECES[below(PIECES.len())])
+                .collect();
+            let start = below(input.len() + 1);
+            let end = start + below(input.len() - start + 1);
+            let text: String = (0..below(4)).map(|_| PIECES[below(PIECES.len())]).collect();
+            check(&input, &TextEdit::new(start..end, text));
+        }
+    }
+}
@@ -1,7 +1,9 @@
+mod incremental;
 mod rules;
 mod token;
 
-pub use token::{Span, Token, TokenKind};
+pub use incremental::{relex, Relexed, TextEdit};
+pub use token::{Span, Token, TokenCategory, TokenKind, UnknownTokenKind};
 
 use crate::T;
 use rules::{get_rules, unambiguous_single_char, Rule};
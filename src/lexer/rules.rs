@@ -8,7 +8,26 @@ pub(crate) struct Rule {
     pub matches: fn(&str) -> Option<u32>,
 }
 
+/// All rules, where earlier rules win against later ones that match the
+/// same length.
 pub(crate) fn get_rules() -> Vec<Rule> {
+    let mut rules = operator_rules();
+    rules.extend(keyword_rules());
+    rules.push(Rule {
+        kind: T![string],
+        matches: move |input| match_regex(input, &STRING_REGEX),
+    });
+    rules.extend(comment_rules());
+    rules.extend(numeric_rules());
+    rules.push(Rule {
+        kind: T![ident],
+        matches: |input| match_regex(input, &IDENTIFIER_REGEX),
+    });
+    rules
+}
+
+/// Operators and punctuation that `unambiguous_single_char` does not cover.
+fn operator_rules() -> Vec<Rule> {
     vec![
         Rule {
             kind: T![!],
@@ -58,6 +77,11 @@ pub(crate) fn get_rules() -> Vec<Rule> {
             kind: T![>=],
             matches: |input| match_two_chars(input, '>', '='),
         },
+    ]
+}
+
+fn keyword_rules() -> Vec<Rule> {
+    vec![
         Rule {
             kind: T![let],
             matches: |input| match_keyword(input, "let"),
@@ -78,31 +102,51 @@ pub(crate) fn get_rules() -> Vec<Rule> {
             kind: T![else],
             matches: |input| match_keyword(input, "else"),
         },
+    ]
+}
+
+fn comment_rules() -> Vec<Rule> {
+    vec![
+        // Doc comments come first, to win against the line comment of
+        // the same length.
+        Rule {
+            kind: T![doc_comment],
+            matches: move |input| match_regex(input, &DOC_COMMENT_REGEX),
+        },
         Rule {
-            kind: T![string],
-            matches: move |input| match_regex(input, &STRING_REGEX),
+            kind: T![inner_doc_comment],
+            matches: move |input| match_regex(input, &INNER_DOC_COMMENT_REGEX),
         },
         Rule {
             kind: T![comment],
             matches: move |input| match_regex(input, &COMMENT_REGEX),
         },
         Rule {
-            kind: T![int],
-            matches: |input| {
-                input
-                    .char_indices()
-                    .take_while(|(_, c)| c.is_ascii_digit())
-                    .last()
-                    .map(|(pos, _)| pos as u32 + 1)
+            kind: T![block_comment],
+            matches: |input| match match_block_comment(input)? {
+                (len, true) => Some(len),
+                (_, false) => None,
             },
         },
         Rule {
-            kind: T![float],
-            matches: |input| match_regex(input, &FLOAT_REGEX),
+            kind: T![unterminated_comment],
+            matches: |input| match match_block_comment(input)? {
+                (_, true) => None,
+                (len, false) => Some(len),
+            },
+        },
+    ]
+}
+
+fn numeric_rules() -> Vec<Rule> {
+    vec![
+        Rule {
+            kind: T![int],
+            matches: |input| match_regex(input, &INT_REGEX),
         },
         Rule {
-            kind: T![ident],
-            matches: |input| match_regex(input, &IDENTIFIER_REGEX),
+            kind: T![float],
+            matches: |input| match_regex(input, &FLOAT_REGEX),
         },
     ]
 }
@@ -153,15 +197,49 @@ fn match_keyword(input: &str, keyword: &str) -> Option<u32> {
     input.starts_with(keyword).then(|| keyword.len() as u32)
 }
 
+/// The length of the block comment at the start of `input`, and whether it
+/// is closed. Block comments nest, so `/* /* */` is not closed yet.
+#[allow(clippy::cast_possible_truncation)]
+fn match_block_comment(input: &str) -> Option<(u32, bool)> {
+    if !input.starts_with("/*") {
+        return None;
+    }
+    let mut depth = 0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        if rest.starts_with("/*") {
+            depth += 1;
+            rest = &rest[2..];
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            rest = &rest[2..];
+            if depth == 0 {
+                return Some(((input.len() - rest.len()) as u32, true));
+            }
+        } else {
+            rest = &rest[rest.chars().next().unwrap().len_utf8()..];
+        }
+    }
+    Some((input.len() as u32, false))
+}
+
 fn match_regex(input: &str, r: &Regex) -> Option<u32> {
     r.find(input).map(|regex_match| regex_match.end() as u32)
 }
 
 lazy_static! {
     static ref STRING_REGEX: Regex = Regex::new(r#"^"((\\"|\\\\)|[^\\"])*""#).unwrap();
-    static ref COMMENT_REGEX: Regex = Regex::new(r#"^//[^\n]*\n"#).unwrap();
-    static ref FLOAT_REGEX: Regex =
-        Regex::new(r#"^((\d+(\.\d+)?)|(\.\d+))([Ee](\+|-)?\d+)?"#).unwrap();
+    static ref COMMENT_REGEX: Regex = Regex::new(r"^//[^\n]*\n?").unwrap();
+    static ref DOC_COMMENT_REGEX: Regex = Regex::new(r"^///([^/\n][^\n]*)?\n?").unwrap();
+    static ref INNER_DOC_COMMENT_REGEX: Regex = Regex::new(r"^//![^\n]*\n?").unwrap();
+    // Numbers take any digits and suffix, the parser reports the invalid
+    // ones. A suffix cannot start with an `e`, which would be an exponent.
+    static ref INT_REGEX: Regex =
+        Regex::new(r"^(0[xob][\dA-Za-z_]*|\d[\d_]*([A-DF-Za-df-z_][A-Za-z\d_]*)?)").unwrap();
+    static ref FLOAT_REGEX: Regex = Regex::new(
+        r"^((\d[\d_]*(\.\d[\d_]*)?)|(\.\d[\d_]*))([Ee](\+|-)?\d[\d_]*)?([A-DF-Za-df-z_][A-Za-z\d_]*)?"
+    )
+    .unwrap();
     static ref IDENTIFIER_REGEX: Regex = Regex::new(r##"^([A-Za-z]|_)([A-Za-z]|_|\d)*"##).unwrap();
 }
 
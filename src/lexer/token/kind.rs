@@ -1,55 +1,161 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
-pub enum TokenKind {
+/// Declares [`TokenKind`] and everything derived from its table: each kind
+/// with its text, its category and, for opening and closing brackets, the
+/// bracket that matches it.
+macro_rules! token_kinds {
+    (@matching) => {
+        None
+    };
+    (@matching $matching:ident) => {
+        Some(TokenKind::$matching)
+    };
+    ($($(#[$attr:meta])* $kind:ident = $text:literal, $category:ident $(, $matching:ident)?;)*) => {
+        /// With the `serde` feature, a kind is (de)serialized
+        /// as the name of its variant, like `"Plus"` or `"KeywordLet"`.
+        #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum TokenKind {
+            $($(#[$attr])* $kind,)*
+        }
+
+        impl TokenKind {
+            /// Every kind, in the order they are declared in.
+            pub const ALL: &'static [TokenKind] = &[$(TokenKind::$kind),*];
+
+            /// The text of the kind, as it is displayed.
+            pub fn text(self) -> &'static str {
+                match self {
+                    $(TokenKind::$kind => $text,)*
+                }
+            }
+
+            pub fn category(self) -> TokenCategory {
+                match self {
+                    $(TokenKind::$kind => TokenCategory::$category,)*
+                }
+            }
+
+            /// The bracket that closes an opening bracket or opens a closing
+            /// one, like `)` for `(`. `<` and `>` are operators, not brackets.
+            pub fn matching_bracket(self) -> Option<TokenKind> {
+                match self {
+                    $(TokenKind::$kind => token_kinds!(@matching $($matching)?),)*
+                }
+            }
+        }
+    };
+}
+
+token_kinds! {
     // Single characters
-    Plus,
-    Minus,
-    Times,
-    Slash,
-    Pow,
-    Eq,
-    Dot,
-    Comma,
-    Underscore,
-    Bang,
-    Ampersand,
-    Bar,
-    Colon,
-    SemiColon,
-    // Brackets
-    LAngle,
-    RAngle,
-    LSquare,
-    RSquare,
-    LBrace,
-    RBrace,
-    LParen,
-    RParen,
+    Plus = "+", Operator;
+    Minus = "-", Operator;
+    Times = "*", Operator;
+    Slash = "/", Operator;
+    Pow = "^", Operator;
+    Eq = "=", Operator;
+    Dot = ".", Punctuation;
+    Comma = ",", Punctuation;
+    Underscore = "_", Punctuation;
+    Bang = "!", Operator;
+    Ampersand = "&", Operator;
+    Bar = "|", Operator;
+    Colon = ":", Punctuation;
+    SemiColon = ";", Punctuation;
+    // Brackets, though `<` and `>` are used to compare
+    LAngle = "<", Operator;
+    RAngle = ">", Operator;
+    LSquare = "[", Bracket, RSquare;
+    RSquare = "]", Bracket, LSquare;
+    LBrace = "{", Bracket, RBrace;
+    RBrace = "}", Bracket, LBrace;
+    LParen = "(", Bracket, RParen;
+    RParen = ")", Bracket, LParen;
     // Multiple characters
-    String,
-    Comment,
-    Int,
-    Float,
-    Identifier,
-    KeywordLet,
-    KeywordFn,
-    KeywordStruct,
-    KeywordIf,
-    KeywordElse,
+    String = "String", Literal;
+    Comment = "// Comment", Comment;
+    BlockComment = "/* Comment */", Comment;
+    /// A block comment that is never closed and takes the rest of the input.
+    UnterminatedComment = "/* Comment", Comment;
+    DocComment = "/// Comment", Comment;
+    InnerDocComment = "//! Comment", Comment;
+    Int = "Int", Literal;
+    Float = "Float", Literal;
+    Identifier = "Identifier", Identifier;
+    KeywordLet = "let", Keyword;
+    KeywordFn = "fn", Keyword;
+    KeywordStruct = "struct", Keyword;
+    KeywordIf = "if", Keyword;
+    KeywordElse = "else", Keyword;
     // Operators
-    And,
-    Or,
-    Eqq,
-    Neq,
-    Geq,
-    Leq,
-    // Misc,
-    Error,
+    And = "&&", Operator;
+    Or = "||", Operator;
+    Eqq = "==", Operator;
+    Neq = "!=", Operator;
+    Geq = ">=", Operator;
+    Leq = "<=", Operator;
+    // Misc
+    Error = "<?>", Error;
+    Whitespace = "<WS>", Whitespace;
+    Eof = "<EOF>", Eof;
+}
+
+/// The broad kinds of tokens, see [`TokenKind::category`].
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum TokenCategory {
+    Keyword,
+    /// Numbers and strings.
+    Literal,
+    Operator,
+    /// `(`, `)`, `[`, `]`, `{` and `}`.
+    Bracket,
+    /// Separators like `,` and `;`.
+    Punctuation,
+    Identifier,
+    Comment,
     Whitespace,
+    /// Input the lexer could not make sense of.
+    Error,
     Eof,
 }
 
+impl TokenKind {
+    #[must_use]
+    pub fn is_keyword(self) -> bool {
+        self.category() == TokenCategory::Keyword
+    }
+
+    #[must_use]
+    pub fn is_literal(self) -> bool {
+        self.category() == TokenCategory::Literal
+    }
+
+    #[must_use]
+    pub fn is_operator(self) -> bool {
+        self.category() == TokenCategory::Operator
+    }
+
+    #[must_use]
+    pub fn is_bracket(self) -> bool {
+        self.category() == TokenCategory::Bracket
+    }
+
+    /// Whitespace and comments, which the parser skips.
+    #[must_use]
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self.category(),
+            TokenCategory::Whitespace | TokenCategory::Comment
+        )
+    }
+}
+
+/// The [`TokenKind`] of a token written out, like `T![+]` or `T![let]`.
+///
+/// The arms are written by hand rather than generated by `token_kinds!`:
+/// the crate could not import a macro exported from a macro expansion.
+/// A test checks that every kind is reachable.
 #[macro_export]
 macro_rules! T {
     [+] => {
@@ -124,6 +230,18 @@ macro_rules! T {
     [comment] => {
         $crate::lexer::TokenKind::Comment
     };
+    [block_comment] => {
+        $crate::lexer::TokenKind::BlockComment
+    };
+    [unterminated_comment] => {
+        $crate::lexer::TokenKind::UnterminatedComment
+    };
+    [doc_comment] => {
+        $crate::lexer::TokenKind::DocComment
+    };
+    [inner_doc_comment] => {
+        $crate::lexer::TokenKind::InnerDocComment
+    };
     [int] => {
         $crate::lexer::TokenKind::Int
     };
@@ -179,63 +297,39 @@ macro_rules! T {
 
 impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                // Single characters
-                T![+] => "+",
-                T![-] => "-",
-                T![*] => "*",
-                T![/] => "/",
-                T![^] => "^",
-                T![=] => "=",
-                T![.] => ".",
-                T![,] => ",",
-                T![_] => "_",
-                T![!] => "!",
-                T![&] => "&",
-                T![|] => "|",
-                T![:] => ":",
-                T![;] => ";",
-                // Brackets
-                T![<] => "<",
-                T![>] => ">",
-                T!['['] => "[",
-                T![']'] => "]",
-                T!['{'] => "{",
-                T!['}'] => "}",
-                T!['('] => "(",
-                T![')'] => ")",
-                // Multiple characters
-                T![string] => "String",
-                T![comment] => "// Comment",
-                T![int] => "Int",
-                T![float] => "Float",
-                T![ident] => "Identifier",
-                T![let] => "let",
-                T![fn] => "fn",
-                T![struct] => "struct",
-                T![if] => "if",
-                T![else] => "else",
-                // Operators
-                T![&&] => "&&",
-                T![||] => "||",
-                T![==] => "==",
-                T![!=] => "!=",
-                T![>=] => ">=",
-                T![<=] => "<=",
-                // Misc
-                T![error] => "<?>",
-                T![ws] => "<WS>",
-                T![EOF] => "<EOF>",
-            }
-        )
+        write!(f, "{}", self.text())
+    }
+}
+
+/// The error of parsing a [`TokenKind`] from a text that no kind displays as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTokenKind(pub String);
+
+impl fmt::Display for UnknownTokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown token kind `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownTokenKind {}
+
+/// Parses the text a kind is displayed as, like `"<="` or `"Identifier"`.
+impl FromStr for TokenKind {
+    type Err = UnknownTokenKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TokenKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.text() == s)
+            .ok_or_else(|| UnknownTokenKind(s.to_string()))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn token_kind_display() {
         assert_eq!(T![+].to_string(), "+");
@@ -246,4 +340,97 @@ mod tests {
         assert_eq!(T![ws].to_string(), "<WS>");
         assert_eq!(T![EOF].to_string(), "<EOF>");
     }
+
+    /// Fails to compile if a kind has no arm in `T!`.
+    #[test]
+    fn every_kind_is_reachable_through_the_macro() {
+        for kind in TokenKind::ALL {
+            let name = match kind {
+                T![+] => "Plus",
+                T![-] => "Minus",
+                T![*] => "Times",
+                T![/] => "Slash",
+                T![^] => "Pow",
+                T![=] => "Eq",
+                T![.] => "Dot",
+                T![,] => "Comma",
+                T![_] => "Underscore",
+                T![!] => "Bang",
+                T![&] => "Ampersand",
+                T![|] => "Bar",
+                T![:] => "Colon",
+                T![;] => "SemiColon",
+                T![<] => "LAngle",
+                T![>] => "RAngle",
+                T!['['] => "LSquare",
+                T![']'] => "RSquare",
+                T!['{'] => "LBrace",
+                T!['}'] => "RBrace",
+                T!['('] => "LParen",
+                T![')'] => "RParen",
+                T![string] => "String",
+                T![comment] => "Comment",
+                T![block_comment] => "BlockComment",
+                T![unterminated_comment] => "UnterminatedComment",
+                T![doc_comment] => "DocComment",
+                T![inner_doc_comment] => "InnerDocComment",
+                T![int] => "Int",
+                T![float] => "Float",
+                T![ident] => "Identifier",
+                T![let] => "KeywordLet",
+                T![fn] => "KeywordFn",
+                T![struct] => "KeywordStruct",
+                T![if] => "KeywordIf",
+                T![else] => "KeywordElse",
+                T![&&] => "And",
+                T![||] => "Or",
+                T![==] => "Eqq",
+                T![!=] => "Neq",
+                T![>=] => "Geq",
+                T![<=] => "Leq",
+                T![error] => "Error",
+                T![ws] => "Whitespace",
+                T![EOF] => "Eof",
+            };
+            assert_eq!(format!("{kind:?}"), name);
+        }
+    }
+
+    #[test]
+    fn token_kind_from_str() {
+        for kind in TokenKind::ALL {
+            assert_eq!(kind.to_string().parse(), Ok(*kind));
+        }
+        assert_eq!("<=".parse(), Ok(T![<=]));
+        assert_eq!(
+            "<>".parse::<TokenKind>(),
+            Err(UnknownTokenKind("<>".to_string()))
+        );
+    }
+
+    #[test]
+    fn token_kind_categories() {
+        assert!(T![let].is_keyword());
+        assert!(T![float].is_literal() && T![string].is_literal());
+        assert!(T![&&].is_operator() && T![<].is_operator());
+        assert!(T![ws].is_trivia() && T![comment].is_trivia());
+        assert!(T![block_comment].is_trivia() && T![doc_comment].is_trivia());
+        assert!(T![unterminated_comment].is_trivia());
+        assert!(!T![ident].is_trivia());
+        assert_eq!(T![,].category(), TokenCategory::Punctuation);
+        assert_eq!(T![error].category(), TokenCategory::Error);
+    }
+
+    #[test]
+    fn matching_brackets() {
+        assert_eq!(T!['('].matching_bracket(), Some(T![')']));
+        assert_eq!(T!['}'].matching_bracket(), Some(T!['{']));
+        assert_eq!(T![<].matching_bracket(), None);
+        for kind in TokenKind::ALL {
+            assert_eq!(kind.is_bracket(), kind.matching_bracket().is_some());
+            if let Some(matching) = kind.matching_bracket() {
+                assert_eq!(matching.matching_bracket(), Some(*kind));
+            }
+        }
+    }
 }
@@ -1,12 +1,15 @@
 mod kind;
 mod span;
 
-pub use kind::TokenKind;
+pub use kind::{TokenCategory, TokenKind, UnknownTokenKind};
 pub use span::Span;
 
 use std::fmt;
 
+/// With the `serde` feature, a token is (de)serialized
+/// as `{ "kind": "Identifier", "span": { "start": 4, "end": 5 } }`.
 #[derive(Eq, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
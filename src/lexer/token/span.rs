@@ -1,6 +1,9 @@
 use std::ops::{Index, Range};
 
+/// With the `serde` feature, a span is (de)serialized
+/// as `{ "start": 0, "end": 2 }`.
 #[derive(Eq, PartialEq, Clone, Copy, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     // inclusive
     pub start: u32,
@@ -8,5 +8,13 @@
     clippy::style
 )]
 
+pub mod deps;
+pub mod diagnostic;
+pub mod eval;
+pub mod formatter;
+pub mod highlight;
 pub mod lexer;
 pub mod parser;
+pub mod resolve;
+pub mod symbolic;
+pub mod typeck;
@@ -1,7 +1,25 @@
 use crate::lexer::TokenKind;
+use num_bigint::BigInt;
 use std::fmt;
 
+/// With the `serde` feature, an expression is (de)serialized as an object
+/// with the variant name in `"type"` and its contents in `"value"`:
+///
+/// ```text
+/// { "type": "Ident", "value": "x" }
+/// { "type": "FnCall", "value": { "fn_name": "f", "args": [ ... ] } }
+/// { "type": "InfixOp", "value": { "op": "Plus", "lhs": { ... }, "rhs": { ... } } }
+/// ```
+///
+/// Operators are `TokenKind`s. The AST does not record spans. Serialize
+/// the [`cst::Parse`](super::cst::Parse) of the same input to get the span
+/// of every node and token.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
 pub enum Expr {
     Literal(Lit),
     Ident(String),
@@ -43,17 +61,106 @@ impl fmt::Display for Expr {
     }
 }
 
+/// With the `serde` feature, a literal is (de)serialized
+/// like an expression, e.g. as `{ "type": "Float", "value": 2.5 }`.
+/// A `BigInt` is a string of decimal digits, and so is an `Int` beyond
+/// ±(2^53 - 1), since JavaScript reads larger JSON numbers inexactly:
+///
+/// ```text
+/// { "type": "Int", "value": 42 }
+/// { "type": "Int", "value": "9223372036854775808" }
+/// { "type": "BigInt", "value": "-340282366920938463463374607431768211456" }
+/// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
 pub enum Lit {
-    Int(usize),
+    #[cfg_attr(feature = "serde", serde(with = "exact_int"))]
+    Int(i128),
+    /// An integer literal too large for an `i128`.
+    #[cfg_attr(feature = "serde", serde(with = "decimal"))]
+    BigInt(BigInt),
     Float(f64),
+    /// The value of a string literal, with its escapes resolved.
     Str(String),
 }
 
+/// (De)serializes a big integer as a string of decimal digits.
+#[cfg(feature = "serde")]
+mod decimal {
+    use num_bigint::BigInt;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(i: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(i)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// (De)serializes an integer as a number if every JSON reader gets it
+/// exactly, and as a string of decimal digits otherwise.
+#[cfg(feature = "serde")]
+mod exact_int {
+    use serde::{de, Deserializer, Serializer};
+    use std::fmt;
+
+    /// The largest integer that a double, and so JavaScript, holds exactly.
+    const MAX_SAFE: u128 = (1 << 53) - 1;
+
+    pub fn serialize<S: Serializer>(i: &i128, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(*i) {
+            Ok(small) if i.unsigned_abs() <= MAX_SAFE => serializer.serialize_i64(small),
+            _ => serializer.collect_str(i),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i128, D::Error> {
+        deserializer.deserialize_any(Visitor)
+    }
+
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = i128;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an integer, or a string of decimal digits")
+        }
+
+        fn visit_i64<E: de::Error>(self, i: i64) -> Result<i128, E> {
+            Ok(i.into())
+        }
+
+        fn visit_u64<E: de::Error>(self, i: u64) -> Result<i128, E> {
+            Ok(i.into())
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<i128, E> {
+            s.parse().map_err(E::custom)
+        }
+    }
+}
+
+impl From<BigInt> for Lit {
+    /// An `Int` if `i` fits into an `i128`, a `BigInt` otherwise.
+    fn from(i: BigInt) -> Self {
+        i128::try_from(&i).map_or(Lit::BigInt(i), Lit::Int)
+    }
+}
+
 impl fmt::Display for Lit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Lit::Int(i) => write!(f, "{}", i),
+            Lit::BigInt(i) => write!(f, "{i}"),
             Lit::Float(fl) => write!(f, "{}", fl),
             Lit::Str(s) => write!(f, r#""{}""#, s),
         }
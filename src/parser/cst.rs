@@ -0,0 +1,298 @@
+//! A lossless concrete syntax tree.
+//!
+//! Unlike the AST, the CST keeps every token of the input, including
+//! whitespace, comments and anything the parser could not make sense of.
+//! Concatenating the text of all its tokens gives back the original input.
+
+use super::{
+    event::{self, NodeKind, Sink},
+    ParseError, Parser,
+};
+use crate::{
+    lexer::{Lexer, Span, Token},
+    T,
+};
+
+/// Identifies a node by its position in a pre-order walk of the tree, so
+/// the root is `NodeId(0)`. Later passes key their side tables by it, and
+/// look the node up with `nodes[id.0]` in the [`SyntaxNode::descendants`]
+/// of the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub usize);
+
+/// With the `serde` feature, a node is (de)serialized with its kind, its
+/// span and its children, which are tagged like the AST:
+///
+/// ```text
+/// {
+///     "kind": "Paren",
+///     "span": { "start": 0, "end": 3 },
+///     "children": [
+///         { "type": "Token", "value": { "kind": "LParen", "span": { ... } } },
+///         { "type": "Node", "value": { "kind": "Ident", ... } },
+///         ...
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub span: Span,
+    pub children: Vec<SyntaxElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+impl SyntaxElement {
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span,
+            SyntaxElement::Token(token) => token.span,
+        }
+    }
+}
+
+/// The result of parsing an input into a CST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parse {
+    pub root: SyntaxNode,
+    pub errors: Vec<ParseError>,
+}
+
+/// Parse all of `input` into a lossless tree, rooted at a `Root` node.
+#[must_use]
+pub fn parse(input: &str) -> Parse {
+    let events = Parser::new(input).source_events();
+    let mut builder = CstBuilder::new(input);
+    event::process(events, &mut builder);
+    builder.finish()
+}
+
+impl SyntaxNode {
+    /// Get the source text this node was parsed from.
+    #[must_use]
+    pub fn text<'input>(&self, input: &'input str) -> &'input str {
+        &input[self.span]
+    }
+
+    /// The child nodes, skipping tokens.
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The tokens directly inside this node, skipping child nodes.
+    pub fn child_tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(*token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    /// All tokens in this node and its descendants, in source order.
+    #[must_use]
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    /// This node and all nodes below it in pre-order, indexed by their
+    /// [`NodeId`] in the tree rooted at this node.
+    #[must_use]
+    pub fn descendants(&self) -> Vec<&SyntaxNode> {
+        let mut nodes = Vec::new();
+        self.collect_nodes(&mut nodes);
+        nodes
+    }
+
+    fn collect_nodes<'a>(&'a self, nodes: &mut Vec<&'a SyntaxNode>) {
+        nodes.push(self);
+        for child in self.child_nodes() {
+            child.collect_nodes(nodes);
+        }
+    }
+
+    fn collect_tokens(&self, tokens: &mut Vec<Token>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(*token),
+            }
+        }
+    }
+}
+
+/// Builds a [`SyntaxNode`] from a processed event stream.
+///
+/// The parser never sees whitespace and comments, so the builder lexes the
+/// input again and puts the trivia back in between the tokens it receives.
+/// Trivia in front of a node is attached to the node's parent.
+pub struct CstBuilder {
+    tokens: Vec<Token>,
+    cursor: usize,
+    stack: Vec<SyntaxNode>,
+    result: Option<SyntaxNode>,
+    errors: Vec<ParseError>,
+}
+
+impl CstBuilder {
+    #[must_use]
+    pub fn new(input: &str) -> Self {
+        Self {
+            tokens: Lexer::new(input).tokenize(),
+            cursor: 0,
+            stack: Vec::new(),
+            result: None,
+            errors: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn finish(mut self) -> Parse {
+        let mut root = self.result.take().unwrap_or_else(|| SyntaxNode {
+            kind: NodeKind::Root,
+            span: Span::default(),
+            children: Vec::new(),
+        });
+
+        // Whatever trivia is left trails the root.
+        while let Some(&token) = self.tokens.get(self.cursor) {
+            self.cursor += 1;
+            if token.kind != T![EOF] {
+                root.span.end = token.span.end;
+                root.children.push(SyntaxElement::Token(token));
+            }
+        }
+
+        Parse {
+            root,
+            errors: self.errors,
+        }
+    }
+
+    /// Add trivia up to the next non-trivia token to the current node.
+    fn eat_trivia(&mut self) {
+        while let Some(&token) = self.tokens.get(self.cursor) {
+            if !token.kind.is_trivia() {
+                break;
+            }
+            self.cursor += 1;
+            self.push(SyntaxElement::Token(token));
+        }
+    }
+
+    fn push(&mut self, element: SyntaxElement) {
+        if let Some(node) = self.stack.last_mut() {
+            if node.children.is_empty() {
+                node.span.start = element.span().start;
+            }
+            node.span.end = element.span().end;
+            node.children.push(element);
+        }
+    }
+}
+
+impl Sink for CstBuilder {
+    fn start_node(&mut self, kind: NodeKind) {
+        if !self.stack.is_empty() {
+            self.eat_trivia();
+        }
+        let position = self
+            .tokens
+            .get(self.cursor)
+            .map_or(0, |token| token.span.start);
+        self.stack.push(SyntaxNode {
+            kind,
+            span: Span {
+                start: position,
+                end: position,
+            },
+            children: Vec::new(),
+        });
+    }
+
+    fn token(&mut self, token: Token) {
+        self.eat_trivia();
+        debug_assert_eq!(self.tokens.get(self.cursor), Some(&token));
+        self.cursor += 1;
+        self.push(SyntaxElement::Token(token));
+    }
+
+    fn finish_node(&mut self) {
+        let node = self.stack.pop().expect("unbalanced `finish_node`");
+        if self.stack.is_empty() {
+            self.result = Some(node);
+        } else {
+            self.push(SyntaxElement::Node(node));
+        }
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reconstruct(input: &str) -> String {
+        parse(input)
+            .root
+            .tokens()
+            .into_iter()
+            .map(|token| token.text(input))
+            .collect()
+    }
+
+    #[test]
+    fn lossless() {
+        for input in [
+            "",
+            "  42 ",
+            "// leading\n1 + 2 * 3 // trailing\n",
+            "min ( test + 4 , sin(2*PI ))",
+            "(1 + $$ ) ) garbage",
+            "-x! ^ 2",
+        ] {
+            assert_eq!(reconstruct(input), input);
+        }
+    }
+
+    #[test]
+    fn trivia_before_a_node_belongs_to_its_parent() {
+        let input = "1 + 2";
+        let root = parse(input).root;
+        let infix = root.child_nodes().next().unwrap();
+        assert_eq!(infix.kind, NodeKind::InfixOp);
+        assert_eq!(infix.span, (0..5).into());
+
+        let rhs = infix.child_nodes().nth(1).unwrap();
+        assert_eq!(rhs.kind, NodeKind::Literal);
+        assert_eq!(rhs.text(input), "2");
+    }
+
+    #[test]
+    fn leftover_input_is_an_error_node() {
+        let parse = parse("a ) b");
+        assert_eq!(parse.errors.len(), 1);
+        assert_eq!(parse.errors[0].message, "Unexpected `)` after expression");
+        let kinds: Vec<_> = parse.root.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(kinds, [NodeKind::Ident, NodeKind::Error]);
+    }
+}
@@ -0,0 +1,251 @@
+use super::{ParseError, Parser};
+use crate::lexer::Token;
+use std::mem;
+
+/// The kinds of nodes the grammar groups tokens into.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    /// The whole input, including anything left over after the expression.
+    Root,
+    Literal,
+    Ident,
+    FnCall,
+    /// A parenthesized expression. The AST has no node for these,
+    /// but a lossless tree needs to keep the parentheses somewhere.
+    Paren,
+    PrefixOp,
+    InfixOp,
+    PostfixOp,
+    /// Tokens the grammar could not make sense of.
+    Error,
+}
+
+/// A flat, tree-construction-agnostic record of what the grammar saw.
+///
+/// The parser only ever appends events. A [`Sink`] turns them into
+/// whatever structure it likes after they have been [`process`]ed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartNode {
+        kind: NodeKind,
+        /// Distance to the `StartNode` of a node that was started later,
+        /// but must become the parent of this one. This is how operators
+        /// wrap an operand they have only seen after it was parsed.
+        forward_parent: Option<usize>,
+    },
+    Token(Token),
+    FinishNode,
+    Error(ParseError),
+    /// A placeholder for a node that was started but not (yet) completed.
+    Tombstone,
+}
+
+/// Consumer of a processed event stream.
+///
+/// Calls are properly nested: every `start_node` is matched
+/// by exactly one later `finish_node`.
+pub trait Sink {
+    fn start_node(&mut self, kind: NodeKind);
+    fn token(&mut self, token: Token);
+    fn finish_node(&mut self);
+    fn error(&mut self, error: ParseError);
+}
+
+/// Replay `events` into `sink`, resolving forward parents
+/// so that the sink sees nodes in tree order.
+pub fn process<S: Sink>(mut events: Vec<Event>, sink: &mut S) {
+    let mut kinds = Vec::new();
+
+    for i in 0..events.len() {
+        match mem::replace(&mut events[i], Event::Tombstone) {
+            Event::StartNode {
+                kind,
+                forward_parent,
+            } => {
+                // Collect the chain of forward parents, which all start
+                // before `kind` does. The outermost parent comes last.
+                kinds.push(kind);
+                let mut idx = i;
+                let mut forward_parent = forward_parent;
+                while let Some(distance) = forward_parent {
+                    idx += distance;
+                    forward_parent = match mem::replace(&mut events[idx], Event::Tombstone) {
+                        Event::StartNode {
+                            kind,
+                            forward_parent,
+                        } => {
+                            kinds.push(kind);
+                            forward_parent
+                        }
+                        _ => unreachable!("forward parent must point at a `StartNode`"),
+                    };
+                }
+
+                for kind in kinds.drain(..).rev() {
+                    sink.start_node(kind);
+                }
+            }
+            Event::Token(token) => sink.token(token),
+            Event::FinishNode => sink.finish_node(),
+            Event::Error(error) => sink.error(error),
+            Event::Tombstone => {}
+        }
+    }
+}
+
+/// A node that has been started, but whose kind is not known yet.
+pub(crate) struct Marker {
+    pos: usize,
+}
+
+impl Marker {
+    /// Finish the node, which contains all events since the marker was created.
+    pub(crate) fn complete<I>(self, parser: &mut Parser<'_, I>, kind: NodeKind) -> CompletedMarker
+    where
+        I: Iterator<Item = Token>,
+    {
+        parser.events[self.pos] = Event::StartNode {
+            kind,
+            forward_parent: None,
+        };
+        parser.events.push(Event::FinishNode);
+        CompletedMarker { pos: self.pos }
+    }
+
+    /// Give up on the node. Events recorded since then are kept,
+    /// they are just not wrapped in a node of their own.
+    pub(crate) fn abandon<I>(self, parser: &mut Parser<'_, I>)
+    where
+        I: Iterator<Item = Token>,
+    {
+        if self.pos == parser.events.len() - 1 {
+            parser.events.pop();
+        }
+    }
+}
+
+/// A node that has been finished.
+pub(crate) struct CompletedMarker {
+    pos: usize,
+}
+
+impl CompletedMarker {
+    /// Start a new node that will become the parent of this one.
+    pub(crate) fn precede<I>(self, parser: &mut Parser<'_, I>) -> Marker
+    where
+        I: Iterator<Item = Token>,
+    {
+        let parent = parser.start();
+        match &mut parser.events[self.pos] {
+            Event::StartNode { forward_parent, .. } => {
+                *forward_parent = Some(parent.pos - self.pos);
+            }
+            _ => unreachable!("completed marker must point at a `StartNode`"),
+        }
+        parent
+    }
+}
+
+impl<I> Parser<'_, I>
+where
+    I: Iterator<Item = Token>,
+{
+    /// Start a new node at the current position.
+    pub(crate) fn start(&mut self) -> Marker {
+        let pos = self.events.len();
+        self.events.push(Event::Tombstone);
+        Marker { pos }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the sink calls as an indented outline.
+    #[derive(Default)]
+    struct Outline(Vec<String>, usize);
+
+    impl Sink for Outline {
+        fn start_node(&mut self, kind: NodeKind) {
+            self.0.push(format!("{}{kind:?}", "  ".repeat(self.1)));
+            self.1 += 1;
+        }
+
+        fn token(&mut self, token: Token) {
+            self.0
+                .push(format!("{}{}", "  ".repeat(self.1), token.kind));
+        }
+
+        fn finish_node(&mut self) {
+            self.1 -= 1;
+        }
+
+        fn error(&mut self, error: ParseError) {
+            self.0
+                .push(format!("{}error: {}", "  ".repeat(self.1), error.message));
+        }
+    }
+
+    fn outline(input: &str) -> Vec<String> {
+        let mut parser = Parser::new(input);
+        let mut sink = Outline::default();
+        process(parser.expression_events(0), &mut sink);
+        sink.0
+    }
+
+    #[test]
+    fn forward_parents_become_outer_nodes() {
+        assert_eq!(
+            outline("1 - 2 - 3"),
+            [
+                "InfixOp",
+                "  InfixOp",
+                "    Literal",
+                "      Int",
+                "    -",
+                "    Literal",
+                "      Int",
+                "  -",
+                "  Literal",
+                "    Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn postfix_wraps_prefix_operand() {
+        assert_eq!(
+            outline("-x!"),
+            [
+                "PrefixOp",
+                "  -",
+                "  PostfixOp",
+                "    Ident",
+                "      Identifier",
+                "    !"
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_are_events() {
+        let mut parser = Parser::new("(1 + ");
+        let errors: Vec<_> = parser
+            .expression_events(0)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Error(error) => Some(error.message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "Unknown start of expression: `<EOF>`",
+                "Expected `)`, but found `<EOF>`",
+            ]
+        );
+    }
+}
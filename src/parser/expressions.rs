@@ -1,94 +1,75 @@
-use super::{ast, Parser};
-use crate::{lexer::{Token, TokenKind}, T};
+use super::{
+    ast,
+    event::{self, CompletedMarker, Event, NodeKind},
+    sink::{literal, AstBuilder},
+    ParseError, Parser,
+};
+use crate::{
+    lexer::{Token, TokenKind},
+    T,
+};
 
 impl<'input, I> Parser<'input, I>
 where
     I: Iterator<Item = Token>,
 {
+    /// Parse an expression into an AST.
+    ///
+    /// # Panics
+    /// If the input is not a valid expression.
+    /// Use `try_parse_expression` to get the errors instead.
     pub fn parse_expression(&mut self, binding_power: u8) -> ast::Expr {
-        let mut lhs = match self.peek() {
-            lit @ T![int] | lit @ T![float] | lit @ T![string] => {
-                let literal_text = {
-                    // the calls on `self` need to be split, because `next` takes
-                    // `&mut self` if `peek` is not `T![EOF]`, then there must be
-                    // a next token
-                    let literal_token = self.next().unwrap();
-                    self.text(literal_token)
-                };
-
-                let lit = match lit {
-                    T![int] => ast::Lit::Int(
-                        literal_text
-                            .parse()
-                            .expect(&format!("invalid integer literal: `{}`", literal_text)),
-                    ),
-
-                    T![float] => ast::Lit::Float(literal_text.parse().expect(&format!(
-                        "invalid floating point literal: `{}`",
-                        literal_text
-                    ))),
-
-                    T![string] => ast::Lit::Str(
-                        // trim the quotation marks
-                        literal_text[1..(literal_text.len() - 1)].to_string(),
-                    ),
-                    _ => unreachable!(),
-                };
-
-                ast::Expr::Literal(lit)
-            }
+        match self.try_parse_expression(binding_power) {
+            Ok(expr) => expr,
+            Err(errors) => panic!("{}", errors[0].message),
+        }
+    }
 
-            T![ident] => {
-                let name = {
-                    let ident_token = self.next().unwrap();
-                    self.text(ident_token).to_string()
-                };
+    /// Parse an expression into an AST, or return every error found along the way.
+    ///
+    /// # Errors
+    /// If the input is not a valid expression.
+    pub fn try_parse_expression(
+        &mut self,
+        binding_power: u8,
+    ) -> Result<ast::Expr, Vec<ParseError>> {
+        let events = self.expression_events(binding_power);
+        let mut builder = AstBuilder::new(self.input);
+        event::process(events, &mut builder);
+        builder.finish()
+    }
 
-                if !self.at(T!['(']) {
-                    // plain identifier
-                    ast::Expr::Ident(name)
-                } else {
-                    // function call
-                    let mut args = Vec::new();
-                    self.consume(T!['(']);
+    /// Run the expression grammar and return the raw events it produced.
+    /// Input after the end of the expression is left untouched.
+    pub fn expression_events(&mut self, binding_power: u8) -> Vec<Event> {
+        self.expr(binding_power);
+        std::mem::take(&mut self.events)
+    }
 
-                    while !self.at(T![')']) {
-                        args.push(self.parse_expression(0));
-                        if self.at(T![,]) {
-                            self.consume(T![,]);
-                        }
-                    }
+    /// Run the expression grammar over the entire input, wrapped in a
+    /// `Root` node. Any input after the expression becomes an `Error` node.
+    pub fn source_events(&mut self) -> Vec<Event> {
+        let root = self.start();
+        self.expr(0);
 
-                    self.consume(T![')']);
-                    ast::Expr::FnCall {
-                        fn_name: name,
-                        args,
-                    }
-                }
+        if !self.at(T![EOF]) {
+            if !self.reported_at_next_token() {
+                let found = self.peek();
+                self.error(format!("Unexpected `{found}` after expression"));
             }
-
-            T!['('] => {
-                // Ther is no AST node for grouped expressions.
-                // Parentheses just influence the tree structure.
-                self.consume(T!['(']);
-                let expr = self.parse_expression(0);
-                self.consume(T![')']);
-                expr
+            let garbage = self.start();
+            while !self.at(T![EOF]) {
+                self.next();
             }
+            garbage.complete(self, NodeKind::Error);
+        }
 
-            op @ T![+] | op @ T![-] | op @ T![!] => {
-                self.consume(op);
-                let ((), right_binding_power) = op.prefix_binding_power();
-                ast::Expr::PrefixOp {
-                    op,
-                    expr: Box::new(self.parse_expression(right_binding_power)),
-                }
-            }
+        root.complete(self, NodeKind::Root);
+        std::mem::take(&mut self.events)
+    }
 
-            kind => {
-                panic!("Unknown start of expression: `{}`", kind);
-            }
-        };
+    fn expr(&mut self, binding_power: u8) -> Option<CompletedMarker> {
+        let mut lhs = self.expr_lhs()?;
 
         loop {
             let op = match self.peek() {
@@ -108,32 +89,126 @@ where
                 | op @ T![!] => op,
                 T![EOF] => break,
                 T![')'] | T!['}'] | T![,] | T![;] => break,
-                kind => panic!("Unknown operator: `{}`", kind),
+                kind => {
+                    self.error(format!("Unknown operator: `{kind}`"));
+                    break;
+                }
             };
 
+            if let Some((left_binding_power, ())) = op.postfix_binding_power() {
+                if left_binding_power < binding_power {
+                    break;
+                }
+
+                let m = lhs.precede(self);
+                self.consume(op);
+                lhs = m.complete(self, NodeKind::PostfixOp);
+
+                continue;
+            }
+
             if let Some((left_binding_power, right_binding_power)) = op.infix_binding_power() {
                 if left_binding_power < binding_power {
                     break;
                 }
 
+                let m = lhs.precede(self);
                 self.consume(op);
-                let rhs = self.parse_expression(right_binding_power);
-                lhs = ast::Expr::InfixOp {
-                    op,
-                    lhs: Box::new(lhs),
-                    rhs: Box::new(rhs),
-                };
+                self.expr(right_binding_power);
+                lhs = m.complete(self, NodeKind::InfixOp);
 
                 continue;
             }
             break;
         }
 
-        lhs
+        Some(lhs)
+    }
+
+    fn expr_lhs(&mut self) -> Option<CompletedMarker> {
+        let m = self.start();
+        let kind = match self.peek() {
+            T![int] | T![float] | T![string] => {
+                let token = self.next().expect("peeked a literal");
+                // Checked while parsing, so that every sink reports it.
+                if let Err(error) = literal(token, self.input) {
+                    self.events.push(Event::Error(error));
+                }
+                NodeKind::Literal
+            }
+
+            T![ident] => {
+                self.next();
+
+                if self.at(T!['(']) {
+                    // function call
+                    self.consume(T!['(']);
+
+                    while !self.at(T![')']) && !self.at(T![EOF]) {
+                        if self.expr(0).is_none() {
+                            break;
+                        }
+                        if self.at(T![,]) {
+                            self.consume(T![,]);
+                        } else {
+                            break;
+                        }
+                    }
+
+                    self.expect(T![')']);
+                    NodeKind::FnCall
+                } else {
+                    // plain identifier
+                    NodeKind::Ident
+                }
+            }
+
+            T!['('] => {
+                // The AST has no node for grouped expressions, but
+                // the parentheses still belong somewhere in the events.
+                self.consume(T!['(']);
+                self.expr(0);
+                self.expect(T![')']);
+                NodeKind::Paren
+            }
+
+            op @ (T![+] | T![-] | T![!]) => {
+                self.consume(op);
+                let ((), right_binding_power) = op.prefix_binding_power();
+                self.expr(right_binding_power);
+                NodeKind::PrefixOp
+            }
+
+            kind => {
+                self.error(format!("Unknown start of expression: `{kind}`"));
+                if matches!(kind, T![EOF] | T![')'] | T!['}'] | T![,] | T![;]) {
+                    // leave these for whoever is waiting for them
+                    m.abandon(self);
+                    return None;
+                }
+                self.next();
+                NodeKind::Error
+            }
+        };
+
+        Some(m.complete(self, kind))
+    }
+
+    /// Whether the last error already points at the next token.
+    fn reported_at_next_token(&mut self) -> bool {
+        let span = self.peek_span();
+        self.events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                Event::Error(error) => Some(error.span == span),
+                _ => None,
+            })
+            .unwrap_or(false)
     }
 }
 
-trait Operator {
+pub(crate) trait Operator {
     /// Prefix operators bind their operands to the right.
     fn prefix_binding_power(&self) -> ((), u8);
 
@@ -0,0 +1,153 @@
+//! Tree rewriting by value.
+//!
+//! A [`Fold`] takes ownership of each node and returns its replacement.
+//! The `fold_*` methods for the different kinds of expressions return an
+//! [`Expr`], so a rewrite is free to turn one kind of node into another.
+//! Their default implementations fold the children through the matching
+//! `walk_*` function and rebuild the node unchanged otherwise.
+//!
+//! As with the visitors, `walk_expr` matches exhaustively so that new
+//! node kinds cannot be forgotten.
+
+use super::ast::{Expr, Lit};
+use crate::lexer::TokenKind;
+
+pub trait Fold {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+
+    fn fold_lit(&mut self, lit: Lit) -> Lit {
+        lit
+    }
+
+    fn fold_ident(&mut self, name: String) -> Expr {
+        Expr::Ident(name)
+    }
+
+    fn fold_fn_call(&mut self, fn_name: String, args: Vec<Expr>) -> Expr {
+        walk_fn_call(self, fn_name, args)
+    }
+
+    fn fold_prefix_op(&mut self, op: TokenKind, expr: Expr) -> Expr {
+        walk_prefix_op(self, op, expr)
+    }
+
+    fn fold_infix_op(&mut self, op: TokenKind, lhs: Expr, rhs: Expr) -> Expr {
+        walk_infix_op(self, op, lhs, rhs)
+    }
+
+    fn fold_postfix_op(&mut self, op: TokenKind, expr: Expr) -> Expr {
+        walk_postfix_op(self, op, expr)
+    }
+}
+
+pub fn walk_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal(lit) => Expr::Literal(folder.fold_lit(lit)),
+        Expr::Ident(name) => folder.fold_ident(name),
+        Expr::FnCall { fn_name, args } => folder.fold_fn_call(fn_name, args),
+        Expr::PrefixOp { op, expr } => folder.fold_prefix_op(op, *expr),
+        Expr::InfixOp { op, lhs, rhs } => folder.fold_infix_op(op, *lhs, *rhs),
+        Expr::PostfixOp { op, expr } => folder.fold_postfix_op(op, *expr),
+    }
+}
+
+pub fn walk_fn_call<F: Fold + ?Sized>(folder: &mut F, fn_name: String, args: Vec<Expr>) -> Expr {
+    Expr::FnCall {
+        fn_name,
+        args: args.into_iter().map(|arg| folder.fold_expr(arg)).collect(),
+    }
+}
+
+pub fn walk_prefix_op<F: Fold + ?Sized>(folder: &mut F, op: TokenKind, expr: Expr) -> Expr {
+    Expr::PrefixOp {
+        op,
+        expr: Box::new(folder.fold_expr(expr)),
+    }
+}
+
+pub fn walk_infix_op<F: Fold + ?Sized>(
+    folder: &mut F,
+    op: TokenKind,
+    lhs: Expr,
+    rhs: Expr,
+) -> Expr {
+    Expr::InfixOp {
+        op,
+        lhs: Box::new(folder.fold_expr(lhs)),
+        rhs: Box::new(folder.fold_expr(rhs)),
+    }
+}
+
+pub fn walk_postfix_op<F: Fold + ?Sized>(folder: &mut F, op: TokenKind, expr: Expr) -> Expr {
+    Expr::PostfixOp {
+        op,
+        expr: Box::new(folder.fold_expr(expr)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, T};
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    #[test]
+    fn identity_fold() {
+        struct Identity;
+        impl Fold for Identity {}
+
+        let expr = parse(r#"-a! + f(1, 2.5, "s") ^ 2"#);
+        assert_eq!(Identity.fold_expr(expr.clone()), expr);
+    }
+
+    #[test]
+    fn substitute_idents() {
+        /// Replaces `x` with `(y + 1)`.
+        struct Substitute;
+
+        impl Fold for Substitute {
+            fn fold_ident(&mut self, name: String) -> Expr {
+                if name == "x" {
+                    parse("y + 1")
+                } else {
+                    Expr::Ident(name)
+                }
+            }
+        }
+
+        assert_eq!(
+            Substitute.fold_expr(parse("2 * x - f(x)")),
+            parse("2 * (y + 1) - f(y + 1)"),
+        );
+    }
+
+    #[test]
+    fn rewrite_node_kind() {
+        /// Turns `a - b` into `a + -b`.
+        struct NegateSubtraction;
+
+        impl Fold for NegateSubtraction {
+            fn fold_infix_op(&mut self, op: TokenKind, lhs: Expr, rhs: Expr) -> Expr {
+                if op == T![-] {
+                    let rhs = Expr::PrefixOp {
+                        op: T![-],
+                        expr: Box::new(rhs),
+                    };
+                    walk_infix_op(self, T![+], lhs, rhs)
+                } else {
+                    walk_infix_op(self, op, lhs, rhs)
+                }
+            }
+        }
+
+        assert_eq!(
+            NegateSubtraction.fold_expr(parse("a - b - c")),
+            parse("(a + -b) + -c"),
+        );
+    }
+}
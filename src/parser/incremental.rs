@@ -0,0 +1,335 @@
+//! Reparsing only the part of a tree that an edit affected.
+//!
+//! A parenthesized expression or the arguments of a call are parsed the same
+//! way wherever they appear: the parser enters them at the `(` and leaves at
+//! the matching `)`, whatever came before. An edit strictly between the
+//! parentheses of such a node therefore only changes that node, as long as
+//! the new text in between still is a complete expression. A [`Document`]
+//! parses only the innermost such node again, and falls back to parsing
+//! everything if there is none, or if the node had or gets syntax errors.
+//!
+//! The new node replaces the old one in place. The rest of the tree is kept,
+//! and only the spans of the nodes around it and of what follows the edit
+//! are moved by the change in length.
+//!
+//! The grammar has no items or blocks, so parentheses are the only balanced
+//! regions that can be reparsed on their own.
+
+use super::{
+    cst::{self, Parse, SyntaxElement, SyntaxNode},
+    event::NodeKind,
+    ParseError,
+};
+use crate::{
+    lexer::{relex, Lexer, Span, TextEdit, Token},
+    T,
+};
+
+/// An input together with its tokens and tree, which are kept up to date
+/// across edits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    input: String,
+    tokens: Vec<Token>,
+    parse: Parse,
+}
+
+impl Document {
+    pub fn new(input: impl Into<String>) -> Self {
+        let input = input.into();
+        Self {
+            tokens: Lexer::new(&input).tokenize(),
+            parse: cst::parse(&input),
+            input,
+        }
+    }
+
+    #[must_use]
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    #[must_use]
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    #[must_use]
+    pub fn parse(&self) -> &Parse {
+        &self.parse
+    }
+
+    /// Apply `edit` to the input and update the tokens and the tree.
+    /// Returns the span of the new input that was parsed again.
+    pub fn edit(&mut self, edit: &TextEdit) -> Span {
+        let input = edit.apply(&self.input);
+        let relexed = relex(&self.tokens, &input, edit);
+
+        // Relexing starts early, to be safe. The tokens that came out the
+        // same do not count as changed.
+        let mut removed = &self.tokens[relexed.removed.clone()];
+        let mut inserted = &relexed.tokens[relexed.inserted.clone()];
+        while let ([old, rest @ ..], [new, new_rest @ ..]) = (removed, inserted) {
+            if old != new || old.span.end > edit.range.start {
+                break;
+            }
+            (removed, inserted) = (rest, new_rest);
+        }
+        while let ([rest @ .., old], [new_rest @ .., new]) = (removed, inserted) {
+            let moved = old.span.start >= edit.range.end
+                && old.kind == new.kind
+                && edit.shift(old.span.start) == new.span.start
+                && edit.shift(old.span.end) == new.span.end;
+            if !moved {
+                break;
+            }
+            (removed, inserted) = (rest, new_rest);
+        }
+        let changed = Span {
+            start: removed.first().map_or(edit.range.start, |token| {
+                token.span.start.min(edit.range.start)
+            }),
+            end: removed
+                .last()
+                .map_or(edit.range.end, |token| token.span.end.max(edit.range.end)),
+        };
+
+        let reparsed = if let Some(span) = self.reparse(&input, edit, changed) {
+            span
+        } else {
+            self.parse = cst::parse(&input);
+            #[allow(clippy::cast_possible_truncation)]
+            let whole = Span {
+                start: 0,
+                end: input.len() as u32,
+            };
+            whole
+        };
+        self.input = input;
+        self.tokens = relexed.tokens;
+        reparsed
+    }
+
+    /// Parse the innermost parenthesized node around `changed` again and
+    /// put it into the tree, or return `None` if there is no such node.
+    fn reparse(&mut self, input: &str, edit: &TextEdit, changed: Span) -> Option<Span> {
+        let mut path = Vec::new();
+        let target = enclosing(&self.parse.root, changed, &mut path)?;
+        let (old_span, kind) = (target.span, target.kind);
+        let overlaps = |error: &ParseError| {
+            error.span.start <= old_span.end && error.span.end >= old_span.start
+        };
+        if self.parse.errors.iter().any(overlaps) {
+            return None;
+        }
+
+        let span = Span {
+            start: old_span.start,
+            end: edit.shift(old_span.end),
+        };
+        let parse = cst::parse(&input[span]);
+        let node = match parse.root.children.as_slice() {
+            [SyntaxElement::Node(node)] if parse.errors.is_empty() && node.kind == kind => {
+                node.clone()
+            }
+            _ => return None,
+        };
+
+        let mover = Mover { edit, changed };
+        let mut replaced = &mut self.parse.root;
+        for index in path {
+            // The nodes around the new one end behind the edit, and the
+            // elements after it start there. Whatever is in front of it
+            // stays where it is.
+            mover.span(&mut replaced.span);
+            for sibling in &mut replaced.children[index + 1..] {
+                mover.element(sibling);
+            }
+            let SyntaxElement::Node(child) = &mut replaced.children[index] else {
+                unreachable!("the path only leads through nodes");
+            };
+            replaced = child;
+        }
+        *replaced = node;
+        offset(replaced, span.start);
+
+        for error in &mut self.parse.errors {
+            mover.span(&mut error.span);
+        }
+        Some(span)
+    }
+}
+
+/// The innermost `Paren` or `FnCall` node whose parentheses enclose
+/// `changed`, with the indices of the children that lead to it.
+fn enclosing<'a>(
+    node: &'a SyntaxNode,
+    changed: Span,
+    path: &mut Vec<usize>,
+) -> Option<&'a SyntaxNode> {
+    for (index, child) in node.children.iter().enumerate() {
+        let SyntaxElement::Node(child) = child else {
+            continue;
+        };
+        if child.span.start <= changed.start && changed.end <= child.span.end {
+            path.push(index);
+            if let Some(inner) = enclosing(child, changed, path) {
+                return Some(inner);
+            }
+            if is_enclosed(child, changed) {
+                return Some(child);
+            }
+            path.pop();
+        }
+    }
+    None
+}
+
+fn is_enclosed(node: &SyntaxNode, changed: Span) -> bool {
+    if !matches!(node.kind, NodeKind::Paren | NodeKind::FnCall) {
+        return false;
+    }
+    let open = node.child_tokens().find(|token| token.kind == T!['(']);
+    let close = node.child_tokens().last();
+    match (open, close) {
+        (Some(open), Some(close)) if close.kind == T![')'] => {
+            open.span.end <= changed.start && changed.end <= close.span.start
+        }
+        _ => false,
+    }
+}
+
+/// Moves the parts of the tree behind a change by the change in length.
+struct Mover<'a> {
+    edit: &'a TextEdit,
+    /// The changed part of the old input, which includes the edited range.
+    changed: Span,
+}
+
+impl Mover<'_> {
+    fn span(&self, span: &mut Span) {
+        // Nothing ends in the changed part, except for what starts behind it
+        // when it is empty.
+        let moves_end = span.end > self.changed.end
+            || (span.end == self.changed.end && span.start >= self.changed.end);
+        if span.start >= self.changed.end {
+            span.start = self.edit.shift(span.start);
+        }
+        if moves_end {
+            span.end = self.edit.shift(span.end);
+        }
+    }
+
+    fn element(&self, element: &mut SyntaxElement) {
+        match element {
+            SyntaxElement::Node(node) => {
+                self.span(&mut node.span);
+                for child in &mut node.children {
+                    self.element(child);
+                }
+            }
+            SyntaxElement::Token(token) => self.span(&mut token.span),
+        }
+    }
+}
+
+/// Move a tree parsed on its own to `start` in the whole input.
+fn offset(node: &mut SyntaxNode, start: u32) {
+    node.span.start += start;
+    node.span.end += start;
+    for child in &mut node.children {
+        match child {
+            SyntaxElement::Node(node) => offset(node, start),
+            SyntaxElement::Token(token) => {
+                token.span.start += start;
+                token.span.end += start;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, edit: &TextEdit) -> Span {
+        let mut document = Document::new(input);
+        let reparsed = document.edit(edit);
+        let expected = Document::new(edit.apply(input));
+        assert_eq!(document, expected, "{input:?} after {edit:?}");
+        reparsed
+    }
+
+    #[test]
+    fn reparses_the_innermost_parentheses() {
+        let input = "1 + f(2, (x * 3)) - g(y)";
+        // Only the parentheses around `x * 3`, now `x * 3 + 4`.
+        assert_eq!(check(input, &TextEdit::new(15..15, " + 4")), (9..20).into());
+        // A new argument changes the call.
+        assert_eq!(check(input, &TextEdit::new(16..16, ", z")), (4..20).into());
+        assert_eq!(check(input, &TextEdit::new(22..23, "")), (20..23).into());
+    }
+
+    #[test]
+    fn falls_back_to_parsing_everything() {
+        // Outside of any parentheses.
+        assert_eq!(check("a + (b)", &TextEdit::new(0..1, "c")), (0..7).into());
+        // Removing the `)` changes what the parentheses enclose.
+        assert_eq!(check("(a) + b", &TextEdit::new(2..3, "")), (0..6).into());
+        // The new text is not a complete expression.
+        assert_eq!(check("f(a, b)", &TextEdit::new(5..6, "")), (0..6).into());
+        // Errors inside the parentheses before the edit.
+        assert_eq!(
+            check("f(a b) + 1", &TextEdit::new(4..5, "+ c")),
+            (0..12).into()
+        );
+    }
+
+    #[test]
+    fn agrees_with_parsing_everything() {
+        // Mostly valid inputs, so that there are trees to reuse parts of.
+        fn expression(below: &mut impl FnMut(usize) -> usize, depth: u32) -> String {
+            match if depth == 0 { below(2) } else { below(6) } {
+                0 => "x".to_string(),
+                1 => "2".to_string(),
+                2 => format!("({})", expression(below, depth - 1)),
+                3 => format!(
+                    "f({}, {})",
+                    expression(below, depth - 1),
+                    expression(below, depth - 1)
+                ),
+                4 => format!("-{}", expression(below, depth - 1)),
+                _ => format!(
+                    "{} * {}",
+                    expression(below, depth - 1),
+                    expression(below, depth - 1)
+                ),
+            }
+        }
+
+        const PIECES: [&str; 12] = [
+            "a", "1", "f(", "(", ")", ",", " + ", "*", "!", " ", "\"s\"", "// c\n",
+        ];
+        let mut rng = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut below = |n: usize| {
+            rng = rng
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (rng >> 33) as usize % n
+        };
+
+        let mut reused = 0;
+        for _ in 0..3000 {
+            let input = expression(&mut below, 4);
+            let start = below(input.len() + 1);
+            let end = start + below((input.len() - start).min(4) + 1);
+            let text: String = (0..below(3)).map(|_| PIECES[below(PIECES.len())]).collect();
+            let edit = TextEdit::new(start..end, text);
+            if check(&input, &edit).end as usize != edit.apply(&input).len() {
+                reused += 1;
+            }
+        }
+        // Make sure that the random edits cover reparsing parts of the tree.
+        assert!(reused > 100, "only {reused} edits reused parts of the tree");
+    }
+}
@@ -1,11 +1,25 @@
 pub mod ast;
+pub mod cst;
+mod error;
+pub mod event;
 mod expressions;
+pub mod fold;
+pub mod incremental;
+mod number;
+pub mod pretty;
+pub mod sexpr;
+pub mod sink;
 mod token_iter;
+pub mod visit;
+
+pub use error::ParseError;
+pub(crate) use expressions::Operator;
 
 use crate::{
-    lexer::{Token, TokenKind},
+    lexer::{Span, Token, TokenKind},
     T,
 };
+use event::Event;
 use std::iter::Peekable;
 use token_iter::TokenIter;
 
@@ -15,6 +29,10 @@ where
 {
     input: &'input str,
     tokens: Peekable<I>,
+    events: Vec<Event>,
+    /// An unterminated block comment took the rest of the input and has
+    /// been reported. Whatever else is missing, that is the problem.
+    unterminated_comment: bool,
 }
 
 impl<'input> Parser<'input, TokenIter<'input>> {
@@ -22,6 +40,8 @@ impl<'input> Parser<'input, TokenIter<'input>> {
         Self {
             input,
             tokens: TokenIter::new(input).peekable(),
+            events: Vec::new(),
+            unterminated_comment: false,
         }
     }
 }
@@ -37,6 +57,7 @@ where
 
     /// Look-ahead one token and see what kind of token it is.
     pub(crate) fn peek(&mut self) -> TokenKind {
+        self.skip_unterminated_comment();
         self.tokens.peek().map(|t| t.kind).unwrap_or(T![EOF])
     }
 
@@ -45,9 +66,12 @@ where
         self.peek() == kind
     }
 
-    /// Get the next token.
+    /// Get the next token and record it in the current node.
     pub(crate) fn next(&mut self) -> Option<Token> {
-        self.tokens.next()
+        self.skip_unterminated_comment();
+        let token = self.tokens.next()?;
+        self.events.push(Event::Token(token));
+        Some(token)
     }
 
     /// Move forward one token in the input and check
@@ -64,4 +88,51 @@ where
             expected, token.kind
         );
     }
+
+    /// Like `consume`, but reports an error instead of
+    /// panicking if the next token is not of the `expected` kind.
+    pub(crate) fn expect(&mut self, expected: TokenKind) {
+        if self.at(expected) {
+            self.consume(expected);
+        } else {
+            let found = self.peek();
+            self.error(format!("Expected `{expected}`, but found `{found}`"));
+        }
+    }
+
+    /// Report an error at the next token.
+    pub(crate) fn error(&mut self, message: String) {
+        if self.unterminated_comment {
+            return;
+        }
+        let span = self.peek_span();
+        self.events.push(Event::Error(ParseError { message, span }));
+    }
+
+    /// Report an unterminated block comment, which the token iterator
+    /// passes on unlike other trivia, and skip it.
+    fn skip_unterminated_comment(&mut self) {
+        if let Some(token) = self
+            .tokens
+            .next_if(|token| token.kind == T![unterminated_comment])
+        {
+            self.events.push(Event::Error(ParseError {
+                message: "Unterminated block comment".to_string(),
+                span: token.span,
+            }));
+            self.unterminated_comment = true;
+        }
+    }
+
+    /// The span of the next token, or an empty span at the end of the input.
+    fn peek_span(&mut self) -> Span {
+        self.skip_unterminated_comment();
+        if let Some(token) = self.tokens.peek() {
+            token.span
+        } else {
+            #[allow(clippy::cast_possible_truncation)]
+            let end = self.input.len() as u32;
+            Span { start: end, end }
+        }
+    }
 }
@@ -0,0 +1,187 @@
+//! The values of number literals.
+//!
+//! Integers are decimal, or hexadecimal, octal or binary after a `0x`, `0o`
+//! or `0b`. Numbers may separate their digits with `_`, and end in a suffix
+//! that names their type, like `10u8` or `3.0f32`. The language itself has
+//! one integer and one float type: an integer suffix checks that the value
+//! fits into its type, and a float suffix makes an integer literal a float.
+//! Integers without a suffix that do not fit into an `i128` are big integers.
+
+use super::ast::Lit;
+use num_bigint::BigInt;
+
+/// The integer suffixes with the largest value of their type.
+const INT_SUFFIXES: [(&str, u128); 12] = [
+    ("u8", u8::MAX as u128),
+    ("u16", u16::MAX as u128),
+    ("u32", u32::MAX as u128),
+    ("u64", u64::MAX as u128),
+    ("u128", u128::MAX),
+    ("usize", usize::MAX as u128),
+    ("i8", i8::MAX as u128),
+    ("i16", i16::MAX as u128),
+    ("i32", i32::MAX as u128),
+    ("i64", i64::MAX as u128),
+    ("i128", i128::MAX as u128),
+    ("isize", isize::MAX as u128),
+];
+
+const FLOAT_SUFFIXES: [&str; 2] = ["f32", "f64"];
+
+/// The value of an integer literal, which is a float with a float suffix.
+pub(crate) fn int(text: &str) -> Result<Lit, String> {
+    let (radix, name, rest) = match text.get(..2) {
+        Some("0x") => (16, "hexadecimal", &text[2..]),
+        Some("0o") => (8, "octal", &text[2..]),
+        Some("0b") => (2, "binary", &text[2..]),
+        _ => (10, "decimal", text),
+    };
+    // Digits that are too large for the radix are reported below,
+    // instead of being taken as the start of a suffix.
+    let digits_end = rest
+        .find(|c: char| !(c == '_' || c.is_ascii_digit() || (radix == 16 && c.is_ascii_hexdigit())))
+        .unwrap_or(rest.len());
+    let (digits, suffix) = rest.split_at(digits_end);
+
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    if digits.is_empty() {
+        return Err(format!("missing digits in {name} literal: `{text}`"));
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(format!(
+            "invalid digit `{digit}` in {name} literal: `{text}`"
+        ));
+    }
+
+    if FLOAT_SUFFIXES.contains(&suffix) && radix == 10 {
+        return float(text);
+    }
+    let max = match INT_SUFFIXES.iter().find(|(name, _)| *name == suffix) {
+        Some((_, max)) => Some(*max),
+        None if suffix.is_empty() => None,
+        None => return Err(invalid_suffix(suffix, text)),
+    };
+    let out_of_range = |ty: &str| format!("integer literal out of range for `{ty}`: `{text}`");
+    let Ok(value) = u128::from_str_radix(&digits, radix) else {
+        // Only literals without a suffix may be larger than any type.
+        return match max {
+            Some(_) => Err(out_of_range(suffix)),
+            None => Ok(Lit::BigInt(
+                BigInt::parse_bytes(digits.as_bytes(), radix).unwrap(),
+            )),
+        };
+    };
+    if max.is_some_and(|max| value > max) {
+        return Err(out_of_range(suffix));
+    }
+    // The AST stores integers as an `i128`, whatever their suffix.
+    Ok(match i128::try_from(value) {
+        Ok(value) => Lit::Int(value),
+        Err(_) => Lit::BigInt(value.into()),
+    })
+}
+
+/// The value of a float literal, rounded to `f32` for an `f32` suffix.
+pub(crate) fn float(text: &str) -> Result<Lit, String> {
+    // Suffixes never start with an `e`, which is the exponent.
+    let suffix_start = text
+        .find(|c: char| c.is_ascii_alphabetic() && !matches!(c, 'e' | 'E'))
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(suffix_start);
+    let number: String = number.chars().filter(|c| *c != '_').collect();
+
+    let value = match suffix {
+        "f32" => number.parse::<f32>().map(f64::from),
+        "" | "f64" => number.parse::<f64>(),
+        _ => return Err(invalid_suffix(suffix, text)),
+    }
+    .map_err(|_| format!("invalid floating point literal: `{text}`"))?;
+    if value.is_infinite() {
+        let ty = if suffix.is_empty() { "f64" } else { suffix };
+        return Err(format!("float literal out of range for `{ty}`: `{text}`"));
+    }
+    Ok(Lit::Float(value))
+}
+
+fn invalid_suffix(suffix: &str, text: &str) -> String {
+    format!("invalid suffix `{suffix}` for number literal: `{text}`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(int("1_000_000"), Ok(Lit::Int(1_000_000)));
+        assert_eq!(int("0xFF"), Ok(Lit::Int(255)));
+        assert_eq!(int("0o17"), Ok(Lit::Int(15)));
+        assert_eq!(int("0b1010"), Ok(Lit::Int(10)));
+        assert_eq!(int("0xffu8"), Ok(Lit::Int(255)));
+        assert_eq!(int("10_u16"), Ok(Lit::Int(10)));
+        assert_eq!(int("10f32"), Ok(Lit::Float(10.0)));
+        assert_eq!(
+            int("0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffffu128"),
+            Ok(Lit::BigInt(u128::MAX.into()))
+        );
+        assert_eq!(
+            int("1_000_000_000_000_000_000_000_000_000_000_000_000_000"),
+            Ok(Lit::BigInt(BigInt::from(10).pow(39)))
+        );
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(float("2.5e1_0"), Ok(Lit::Float(2.5e10)));
+        assert_eq!(float("3.0f32"), Ok(Lit::Float(3.0)));
+        assert_eq!(float("0.1f32"), Ok(Lit::Float(f64::from(0.1_f32))));
+        assert_eq!(float("1E5f64"), Ok(Lit::Float(1e5)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            int("300u8"),
+            Err("integer literal out of range for `u8`: `300u8`".to_string())
+        );
+        assert_eq!(
+            int("128i8"),
+            Err("integer literal out of range for `i8`: `128i8`".to_string())
+        );
+        assert_eq!(
+            int("0x1_0000_0000_0000_0000_0000_0000_0000_0000u128"),
+            Err("integer literal out of range for `u128`: \
+                 `0x1_0000_0000_0000_0000_0000_0000_0000_0000u128`"
+                .to_string())
+        );
+        assert_eq!(
+            int("0b102"),
+            Err("invalid digit `2` in binary literal: `0b102`".to_string())
+        );
+        assert_eq!(
+            int("0o8"),
+            Err("invalid digit `8` in octal literal: `0o8`".to_string())
+        );
+        assert_eq!(
+            int("0x_"),
+            Err("missing digits in hexadecimal literal: `0x_`".to_string())
+        );
+        assert_eq!(
+            int("2x"),
+            Err("invalid suffix `x` for number literal: `2x`".to_string())
+        );
+        assert_eq!(
+            int("0x1f64"),
+            Ok(Lit::Int(0x1f64)),
+            "hexadecimal digits are not a float suffix"
+        );
+        assert_eq!(
+            float("1e39f32"),
+            Err("float literal out of range for `f32`: `1e39f32`".to_string())
+        );
+        assert_eq!(
+            float("1.5u8"),
+            Err("invalid suffix `u8` for number literal: `1.5u8`".to_string())
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Print an AST back to source code.
+//!
+//! In contrast to `Display`, which parenthesizes every operation, [`print`]
+//! only adds the parentheses that the binding powers of the operators make
+//! necessary. Parsing the output again gives back the same tree.
+//!
+//! The exceptions are literals the parser never produces, which are printed
+//! as the expression they evaluate to. A negative number is read back as a
+//! `-` in front of its absolute value, infinity as `1.0 / 0.0` and NaN as
+//! `0.0 / 0.0`. A `BigInt` that fits into an `i128` is read back as an
+//! `Int`.
+
+use super::{
+    ast::{Expr, Lit},
+    expressions::Operator,
+};
+use crate::T;
+use num_bigint::{BigInt, Sign};
+use std::fmt::Write;
+
+/// Print `expr` as source code with as few parentheses as possible.
+#[must_use]
+pub fn print(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, expr);
+    out
+}
+
+fn write_expr(out: &mut String, expr: &Expr) {
+    match expr {
+        Expr::Literal(lit) => match read_back(lit) {
+            Some(expr) => write_expr(out, &expr),
+            None => write_lit(out, lit),
+        },
+        Expr::Ident(name) => out.push_str(name),
+        Expr::FnCall { fn_name, args } => {
+            out.push_str(fn_name);
+            out.push('(');
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_expr(out, arg);
+            }
+            out.push(')');
+        }
+        Expr::PrefixOp { op, expr } => {
+            let ((), right_binding_power) = op.prefix_binding_power();
+            write!(out, "{op}").unwrap();
+            write_right_operand(out, expr, right_binding_power);
+        }
+        Expr::InfixOp { op, lhs, rhs } => {
+            let (left_binding_power, right_binding_power) = op
+                .infix_binding_power()
+                .expect("infix operation with a non-infix operator");
+            write_left_operand(out, lhs, left_binding_power);
+            write!(out, " {op} ").unwrap();
+            write_right_operand(out, rhs, right_binding_power);
+        }
+        Expr::PostfixOp { op, expr } => {
+            let (left_binding_power, ()) = op
+                .postfix_binding_power()
+                .expect("postfix operation with a non-postfix operator");
+            write_left_operand(out, expr, left_binding_power);
+            write!(out, "{op}").unwrap();
+        }
+    }
+}
+
+/// The expression the printed `lit` is read back as, if that is not `lit`
+/// itself.
+fn read_back(lit: &Lit) -> Option<Expr> {
+    let negative = |lit: Lit| Expr::PrefixOp {
+        op: T![-],
+        expr: Box::new(Expr::Literal(lit)),
+    };
+    let divide = |lhs: f64, rhs: f64| Expr::InfixOp {
+        op: T![/],
+        lhs: Box::new(Expr::Literal(Lit::Float(lhs))),
+        rhs: Box::new(Expr::Literal(Lit::Float(rhs))),
+    };
+
+    match lit {
+        Lit::Int(i) if *i < 0 => Some(negative(BigInt::from(i.unsigned_abs()).into())),
+        Lit::BigInt(i) if i.sign() == Sign::Minus => Some(negative((-i).into())),
+        Lit::BigInt(i) => i128::try_from(i).ok().map(|i| Expr::Literal(Lit::Int(i))),
+        Lit::Float(fl) if fl.is_nan() => Some(divide(0.0, 0.0)),
+        // Also covers `-0.0` and negative infinity.
+        Lit::Float(fl) if fl.is_sign_negative() => Some(negative(Lit::Float(-fl))),
+        Lit::Float(fl) if fl.is_infinite() => Some(divide(1.0, 0.0)),
+        Lit::Int(_) | Lit::Float(_) | Lit::Str(_) => None,
+    }
+}
+
+fn write_lit(out: &mut String, lit: &Lit) {
+    match lit {
+        Lit::Int(i) => write!(out, "{i}").unwrap(),
+        Lit::BigInt(i) => write!(out, "{i}").unwrap(),
+        // `Debug` always includes a fractional part or an exponent,
+        // so the literal is read back as a float and not an int.
+        Lit::Float(fl) => write!(out, "{fl:?}").unwrap(),
+        Lit::Str(s) => {
+            out.push('"');
+            for c in s.chars() {
+                if matches!(c, '"' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+    }
+}
+
+/// Write an operand that appears to the left of an operator
+/// which binds it with `binding_power`.
+fn write_left_operand(out: &mut String, operand: &Expr, binding_power: u8) {
+    // The operator would steal the operand's rightmost part,
+    // if that part does not bind stronger than the operator.
+    let parens = right_binding_power(operand).is_some_and(|bp| bp <= binding_power);
+    write_operand(out, operand, parens);
+}
+
+/// Write an operand that appears to the right of an operator
+/// which binds it with `binding_power`.
+fn write_right_operand(out: &mut String, operand: &Expr, binding_power: u8) {
+    // The operand's leftmost operator must bind at least as strongly as the
+    // operator in front of it, otherwise the parser stops right before it.
+    let parens = left_binding_power(operand).is_some_and(|bp| bp < binding_power);
+    write_operand(out, operand, parens);
+}
+
+fn write_operand(out: &mut String, operand: &Expr, parens: bool) {
+    if parens {
+        out.push('(');
+        write_expr(out, operand);
+        out.push(')');
+    } else {
+        write_expr(out, operand);
+    }
+}
+
+/// How strongly the outermost operator of `expr` binds what is to its left,
+/// if that operator is not closed off to the left.
+fn left_binding_power(expr: &Expr) -> Option<u8> {
+    match expr {
+        Expr::InfixOp { op, .. } => op.infix_binding_power().map(|(left, _)| left),
+        Expr::PostfixOp { op, .. } => op.postfix_binding_power().map(|(left, ())| left),
+        Expr::Literal(lit) => read_back(lit).and_then(|expr| left_binding_power(&expr)),
+        Expr::Ident(_) | Expr::FnCall { .. } | Expr::PrefixOp { .. } => None,
+    }
+}
+
+/// How strongly the outermost operator of `expr` binds what is to its right,
+/// if that operator is not closed off to the right.
+fn right_binding_power(expr: &Expr) -> Option<u8> {
+    match expr {
+        Expr::InfixOp { op, .. } => op.infix_binding_power().map(|(_, right)| right),
+        Expr::PrefixOp { op, .. } => Some(op.prefix_binding_power().1),
+        Expr::Literal(lit) => read_back(lit).and_then(|expr| right_binding_power(&expr)),
+        Expr::Ident(_) | Expr::FnCall { .. } | Expr::PostfixOp { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lexer::TokenKind,
+        parser::{
+            fold::{walk_expr, Fold},
+            Parser,
+        },
+    };
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    fn reprint(input: &str) -> String {
+        print(&parse(input))
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        assert_eq!(reprint("4 + (2 * 3)"), "4 + 2 * 3");
+        assert_eq!(reprint("(4 + 2) * 3"), "(4 + 2) * 3");
+        assert_eq!(reprint("(4 - 2) - 3"), "4 - 2 - 3");
+        assert_eq!(reprint("4 - (2 - 3)"), "4 - (2 - 3)");
+        assert_eq!(reprint("4 ^ (2 ^ 3)"), "4 ^ 2 ^ 3");
+        assert_eq!(reprint("(4 ^ 2) ^ 3"), "(4 ^ 2) ^ 3");
+        assert_eq!(reprint("-(x!)"), "-x!");
+        assert_eq!(reprint("(-x)!"), "(-x)!");
+        assert_eq!(reprint("-(a + b)"), "-(a + b)");
+        assert_eq!(reprint("(-a) ^ b"), "-a ^ b");
+        assert_eq!(reprint("!(a == b) || c"), "!(a == b) || c");
+    }
+
+    #[test]
+    fn source_syntax() {
+        assert_eq!(
+            reprint("min ( test + 4 , sin(2*PI ))"),
+            "min(test + 4, sin(2 * PI))"
+        );
+        assert_eq!(
+            reprint(r#"2.0 / ((3.0 + 4.0) * (5.0 - 6.0)) * 7.0 == "x""#),
+            r#"2.0 / ((3.0 + 4.0) * (5.0 - 6.0)) * 7.0 == "x""#
+        );
+        assert_eq!(reprint("f()"), "f()");
+        assert_eq!(reprint(r#""say \"hi\" \\ bye""#), r#""say \"hi\" \\ bye""#);
+    }
+
+    #[test]
+    fn literals_without_source() {
+        let lit = |lit| print(&Expr::Literal(lit));
+        assert_eq!(lit(Lit::Str(r#"a"b\"#.to_string())), r#""a\"b\\""#);
+        assert_eq!(lit(Lit::Int(-5)), "-5");
+        assert_eq!(lit(Lit::Float(-0.0)), "-0.0");
+        assert_eq!(lit(Lit::Float(f64::INFINITY)), "1.0 / 0.0");
+        assert_eq!(lit(Lit::Float(f64::NEG_INFINITY)), "-(1.0 / 0.0)");
+        assert_eq!(lit(Lit::Float(f64::NAN)), "0.0 / 0.0");
+
+        let factorial = |lit: Lit| Expr::PostfixOp {
+            op: T![!],
+            expr: Box::new(Expr::Literal(lit)),
+        };
+        assert_eq!(print(&factorial(Lit::Int(-2))), "(-2)!");
+        assert_eq!(print(&factorial(Lit::Float(f64::NAN))), "(0.0 / 0.0)!");
+        assert_eq!(
+            print(&factorial(Lit::BigInt(BigInt::from(3)))),
+            "3!",
+            "small big integers are printed like an `Int`"
+        );
+    }
+
+    /// Small deterministic pseudo-random generator, enough to make up trees.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    fn random_expr(rng: &mut Lcg, depth: usize) -> Expr {
+        const INFIX: [TokenKind; 13] = [
+            T![+],
+            T![-],
+            T![*],
+            T![/],
+            T![^],
+            T![==],
+            T![!=],
+            T![&&],
+            T![||],
+            T![<],
+            T![<=],
+            T![>],
+            T![>=],
+        ];
+        const PREFIX: [TokenKind; 3] = [T![+], T![-], T![!]];
+
+        let leaf = depth == 0 || rng.below(4) == 0;
+        if leaf {
+            const INTS: [i128; 4] = [0, 7, -7, i128::MIN];
+            const FLOATS: [f64; 8] = [
+                0.5,
+                -2.25,
+                -0.0,
+                1e300,
+                -1e-300,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NAN,
+            ];
+            const STRS: [&str; 4] = ["s", r#"say "hi""#, r"a\b", r#"\""#];
+            return match rng.below(6) {
+                0 => Expr::Literal(Lit::Int(INTS[rng.below(INTS.len())])),
+                1 => Expr::Literal(Lit::Float(FLOATS[rng.below(FLOATS.len())])),
+                2 => Expr::Literal(Lit::Str(STRS[rng.below(STRS.len())].to_string())),
+                3 => {
+                    let big = BigInt::from(u128::MAX) * [-2, -1, 0, 1, 2][rng.below(5)];
+                    Expr::Literal(Lit::BigInt(big))
+                }
+                _ => Expr::Ident("x".to_string()),
+            };
+        }
+
+        match rng.below(4) {
+            0 => Expr::PrefixOp {
+                op: PREFIX[rng.below(PREFIX.len())],
+                expr: Box::new(random_expr(rng, depth - 1)),
+            },
+            1 => Expr::PostfixOp {
+                op: T![!],
+                expr: Box::new(random_expr(rng, depth - 1)),
+            },
+            2 => Expr::FnCall {
+                fn_name: "f".to_string(),
+                args: (0..rng.below(3))
+                    .map(|_| random_expr(rng, depth - 1))
+                    .collect(),
+            },
+            _ => Expr::InfixOp {
+                op: INFIX[rng.below(INFIX.len())],
+                lhs: Box::new(random_expr(rng, depth - 1)),
+                rhs: Box::new(random_expr(rng, depth - 1)),
+            },
+        }
+    }
+
+    /// Replaces the literals the parser never produces
+    /// by what their printed form is read back as.
+    struct ReadBack;
+
+    impl Fold for ReadBack {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            match &expr {
+                Expr::Literal(lit) => match read_back(lit) {
+                    Some(expr) => self.fold_expr(expr),
+                    None => expr,
+                },
+                _ => walk_expr(self, expr),
+            }
+        }
+    }
+
+    #[test]
+    fn print_then_parse_round_trips() {
+        let mut rng = Lcg(42);
+        for _ in 0..5000 {
+            let expr = random_expr(&mut rng, 5);
+            let printed = print(&expr);
+            assert_eq!(
+                parse(&printed),
+                ReadBack.fold_expr(expr),
+                "printed as `{printed}`"
+            );
+        }
+    }
+}
@@ -0,0 +1,440 @@
+//! A precise S-expression format for the AST, which can be read back.
+//!
+//! Every expression is a list whose first element names the kind of node:
+//!
+//! ```text
+//! (int 42)
+//! (float 2.5)                  ; always has a `.` or an exponent
+//! (str "a \"quoted\" \\ text") ; `"` and `\` are escaped with `\`
+//! (ident foo)
+//! (call min (ident a) (int 2)) ; function name, then the arguments
+//! (prefix Minus (int 13))      ; operators are `TokenKind` names
+//! (infix Plus (int 4) (int 2))
+//! (postfix Bang (int 3))
+//! ```
+//!
+//! The head of a list may carry a span, as in `(int@0..2 42)`. The AST has
+//! no spans itself, so [`write`] never produces them, but [`write_spanned`]
+//! annotates the tree taken from a CST. [`read`] accepts both forms and
+//! ignores the spans, so the same text can state the expected tree and the
+//! expected spans of a golden test.
+//!
+//! Whitespace between elements is insignificant.
+
+use super::{
+    ast::{Expr, Lit},
+    cst::SyntaxNode,
+    event::NodeKind,
+    sink::literal,
+};
+use crate::{
+    lexer::{Span, TokenKind},
+    T,
+};
+use num_bigint::BigInt;
+use std::fmt::{self, Write};
+
+/// The operators that can appear in the AST.
+const OPERATORS: [TokenKind; 14] = [
+    T![+],
+    T![-],
+    T![*],
+    T![/],
+    T![^],
+    T![!],
+    T![==],
+    T![!=],
+    T![&&],
+    T![||],
+    T![<],
+    T![<=],
+    T![>],
+    T![>=],
+];
+
+/// Write `expr` on a single line.
+#[must_use]
+pub fn write(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, expr);
+    out
+}
+
+fn write_expr(out: &mut String, expr: &Expr) {
+    match expr {
+        Expr::Literal(lit) => write_lit(out, lit, None),
+        Expr::Ident(name) => write!(out, "(ident {name})").unwrap(),
+        Expr::FnCall { fn_name, args } => {
+            write!(out, "(call {fn_name}").unwrap();
+            for arg in args {
+                out.push(' ');
+                write_expr(out, arg);
+            }
+            out.push(')');
+        }
+        Expr::PrefixOp { op, expr } => {
+            write!(out, "(prefix {op:?} ").unwrap();
+            write_expr(out, expr);
+            out.push(')');
+        }
+        Expr::InfixOp { op, lhs, rhs } => {
+            write!(out, "(infix {op:?} ").unwrap();
+            write_expr(out, lhs);
+            out.push(' ');
+            write_expr(out, rhs);
+            out.push(')');
+        }
+        Expr::PostfixOp { op, expr } => {
+            write!(out, "(postfix {op:?} ").unwrap();
+            write_expr(out, expr);
+            out.push(')');
+        }
+    }
+}
+
+fn write_lit(out: &mut String, lit: &Lit, span: Option<Span>) {
+    let span = SpanSuffix(span);
+    match lit {
+        Lit::Int(i) => write!(out, "(int{span} {i})").unwrap(),
+        Lit::BigInt(i) => write!(out, "(int{span} {i})").unwrap(),
+        // `Debug` always includes a `.` or an exponent.
+        Lit::Float(fl) => write!(out, "(float{span} {fl:?})").unwrap(),
+        Lit::Str(s) => {
+            write!(out, "(str{span} \"").unwrap();
+            for c in s.chars() {
+                if matches!(c, '"' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push_str("\")");
+        }
+    }
+}
+
+/// Formats as `@start..end`, or not at all.
+struct SpanSuffix(Option<Span>);
+
+impl fmt::Display for SpanSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(span) => write!(f, "@{}..{}", span.start, span.end),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write the expression in `node` with the span of every expression.
+///
+/// Returns `None` if the tree contains errors.
+#[must_use]
+pub fn write_spanned(node: &SyntaxNode, input: &str) -> Option<String> {
+    let mut out = String::new();
+    write_node(&mut out, node, input)?;
+    Some(out)
+}
+
+fn write_node(out: &mut String, node: &SyntaxNode, input: &str) -> Option<()> {
+    let span = SpanSuffix(Some(node.span));
+    let token = node.child_tokens().find(|token| !token.kind.is_trivia());
+    let mut operands = node.child_nodes();
+
+    match node.kind {
+        NodeKind::Root | NodeKind::Paren => {
+            let expr = operands.next()?;
+            if operands.next().is_some() {
+                // only errors follow an expression
+                return None;
+            }
+            write_node(out, expr, input)?;
+        }
+        NodeKind::Literal => write_lit(out, &literal(token?, input).ok()?, span.0),
+        NodeKind::Ident => write!(out, "(ident{} {})", span, token?.text(input)).unwrap(),
+        NodeKind::FnCall => {
+            write!(out, "(call{} {}", span, token?.text(input)).unwrap();
+            for arg in operands {
+                out.push(' ');
+                write_node(out, arg, input)?;
+            }
+            out.push(')');
+        }
+        NodeKind::PrefixOp => {
+            write!(out, "(prefix{} {:?} ", span, token?.kind).unwrap();
+            write_node(out, operands.next()?, input)?;
+            out.push(')');
+        }
+        NodeKind::InfixOp => {
+            write!(out, "(infix{} {:?} ", span, token?.kind).unwrap();
+            write_node(out, operands.next()?, input)?;
+            out.push(' ');
+            write_node(out, operands.next()?, input)?;
+            out.push(')');
+        }
+        NodeKind::PostfixOp => {
+            write!(out, "(postfix{} {:?} ", span, token?.kind).unwrap();
+            write_node(out, operands.next()?, input)?;
+            out.push(')');
+        }
+        NodeKind::Error => return None,
+    }
+    Some(())
+}
+
+/// An error in S-expression text, at byte `position`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// Read an expression written in the format described in the module docs.
+///
+/// # Errors
+/// If `text` is not a single expression in that format.
+pub fn read(text: &str) -> Result<Expr, ReadError> {
+    let mut reader = Reader { text, position: 0 };
+    let expr = reader.expr()?;
+    reader.skip_whitespace();
+    if reader.position < text.len() {
+        return Err(reader.error("expected the end of the input"));
+    }
+    Ok(expr)
+}
+
+struct Reader<'text> {
+    text: &'text str,
+    position: usize,
+}
+
+impl<'text> Reader<'text> {
+    fn error(&self, message: impl Into<String>) -> ReadError {
+        ReadError {
+            message: message.into(),
+            position: self.position,
+        }
+    }
+
+    fn rest(&self) -> &'text str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> Result<(), ReadError> {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{c}`")))
+        }
+    }
+
+    fn at(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.rest().starts_with(c)
+    }
+
+    /// Anything up to the next whitespace or parenthesis.
+    fn atom(&mut self) -> Result<&'text str, ReadError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected an atom"));
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    fn string(&mut self) -> Result<String, ReadError> {
+        self.eat('"')?;
+        let mut value = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            let c = chars
+                .next()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = chars
+                        .next()
+                        .filter(|c| matches!(c, '"' | '\\'))
+                        .ok_or_else(|| self.error("expected `\"` or `\\` after `\\`"))?;
+                    self.position += 1;
+                    value.push(escaped);
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// The head of a list, without its optional span.
+    fn head(&mut self) -> Result<&'text str, ReadError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let atom = self.atom()?;
+        match atom.split_once('@') {
+            None => Ok(atom),
+            Some((head, span)) => {
+                let valid = span
+                    .split_once("..")
+                    .is_some_and(|(s, e)| s.parse::<u32>().is_ok() && e.parse::<u32>().is_ok());
+                if !valid {
+                    return Err(ReadError {
+                        message: format!("invalid span `{span}`"),
+                        position: start,
+                    });
+                }
+                Ok(head)
+            }
+        }
+    }
+
+    fn operator(&mut self) -> Result<TokenKind, ReadError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let name = self.atom()?;
+        OPERATORS
+            .into_iter()
+            .find(|op| format!("{op:?}") == name)
+            .ok_or_else(|| ReadError {
+                message: format!("unknown operator `{name}`"),
+                position: start,
+            })
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, ReadError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let atom = self.atom()?;
+        atom.parse().map_err(|_| ReadError {
+            message: format!("invalid {what} `{atom}`"),
+            position: start,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, ReadError> {
+        self.eat('(')?;
+        let head_position = self.position;
+        let expr = match self.head()? {
+            "int" => {
+                let i: BigInt = self.number("integer")?;
+                Expr::Literal(i.into())
+            }
+            "float" => Expr::Literal(Lit::Float(self.number("float")?)),
+            "str" => Expr::Literal(Lit::Str(self.string()?)),
+            "ident" => Expr::Ident(self.atom()?.to_string()),
+            "call" => {
+                let fn_name = self.atom()?.to_string();
+                let mut args = Vec::new();
+                while !self.at(')') {
+                    args.push(self.expr()?);
+                }
+                Expr::FnCall { fn_name, args }
+            }
+            "prefix" => Expr::PrefixOp {
+                op: self.operator()?,
+                expr: Box::new(self.expr()?),
+            },
+            "infix" => Expr::InfixOp {
+                op: self.operator()?,
+                lhs: Box::new(self.expr()?),
+                rhs: Box::new(self.expr()?),
+            },
+            "postfix" => Expr::PostfixOp {
+                op: self.operator()?,
+                expr: Box::new(self.expr()?),
+            },
+            head => {
+                return Err(ReadError {
+                    message: format!("unknown node `{head}`"),
+                    position: head_position,
+                })
+            }
+        };
+        self.eat(')')?;
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{cst, Parser};
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    #[test]
+    fn write_expressions() {
+        assert_eq!(
+            write(&parse("min(test + 4, sin(2 * PI))")),
+            "(call min (infix Plus (ident test) (int 4)) (call sin (infix Times (int 2) (ident PI))))"
+        );
+        assert_eq!(
+            write(&parse(r#"-3! != 2.0 || "a \" \\ b""#)),
+            r#"(infix Or (infix Neq (prefix Minus (postfix Bang (int 3))) (float 2.0)) (str "a \" \\ b"))"#
+        );
+    }
+
+    #[test]
+    fn read_back() {
+        for input in [
+            "42",
+            "1e300",
+            r#""String content \" test""#,
+            "f()",
+            "min(test + 4, sin(2 * PI))",
+            "45.7 + 3 + 5 * 4^8^9 / 6 > 4 && test - 7 / 4 == !x!",
+        ] {
+            let expr = parse(input);
+            assert_eq!(read(&write(&expr)), Ok(expr));
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let input = "(1 + x) * f(2)";
+        let spanned = write_spanned(&cst::parse(input).root, input).unwrap();
+        assert_eq!(
+            spanned,
+            "(infix@0..14 Times (infix@1..6 Plus (int@1..2 1) (ident@5..6 x)) \
+             (call@10..14 f (int@12..13 2)))"
+        );
+        assert_eq!(read(&spanned), Ok(parse(input)));
+    }
+
+    #[test]
+    fn read_errors() {
+        let error = |text| read(text).unwrap_err().to_string();
+        assert_eq!(error("(int x)"), "invalid integer `x` at position 5");
+        assert_eq!(
+            error("(infix Dot (int 1) (int 2))"),
+            "unknown operator `Dot` at position 7"
+        );
+        assert_eq!(error("(list 1)"), "unknown node `list` at position 1");
+        assert_eq!(
+            error("(ident a) b"),
+            "expected the end of the input at position 10"
+        );
+        assert_eq!(error("(str \"a)"), "unterminated string at position 8");
+        assert_eq!(error("(int@1.2 1)"), "invalid span `1.2` at position 1");
+    }
+}
@@ -0,0 +1,177 @@
+use super::{ast, event::NodeKind, event::Sink, number, ParseError};
+use crate::{
+    lexer::{Span, Token},
+    T,
+};
+
+/// Builds the owned [`ast::Expr`] from a processed event stream.
+pub struct AstBuilder<'input> {
+    input: &'input str,
+    stack: Vec<Frame>,
+    result: Option<ast::Expr>,
+    errors: Vec<ParseError>,
+}
+
+/// A node whose children are still being collected.
+struct Frame {
+    kind: NodeKind,
+    tokens: Vec<Token>,
+    children: Vec<ast::Expr>,
+}
+
+impl<'input> AstBuilder<'input> {
+    #[must_use]
+    pub fn new(input: &'input str) -> Self {
+        Self {
+            input,
+            stack: Vec::new(),
+            result: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// The finished expression.
+    ///
+    /// # Errors
+    /// Every error reported by the parser and while building the tree.
+    pub fn finish(self) -> Result<ast::Expr, Vec<ParseError>> {
+        match self.result {
+            Some(expr) if self.errors.is_empty() => Ok(expr),
+            None if self.errors.is_empty() => Err(vec![ParseError {
+                message: "Expected an expression".to_string(),
+                span: Span::default(),
+            }]),
+            _ => Err(self.errors),
+        }
+    }
+
+    /// Returns `None` if the node is incomplete or has an invalid literal.
+    /// The parser has already reported an error in that case.
+    fn build(&mut self, frame: Frame) -> Option<ast::Expr> {
+        let mut children = frame.children.into_iter();
+        let expr = match frame.kind {
+            NodeKind::Literal => {
+                let token = *frame.tokens.first()?;
+                ast::Expr::Literal(literal(token, self.input).ok()?)
+            }
+            NodeKind::Ident => ast::Expr::Ident(frame.tokens.first()?.text(self.input).to_string()),
+            NodeKind::FnCall => ast::Expr::FnCall {
+                fn_name: frame.tokens.first()?.text(self.input).to_string(),
+                args: children.collect(),
+            },
+            NodeKind::PrefixOp => ast::Expr::PrefixOp {
+                op: frame.tokens.first()?.kind,
+                expr: Box::new(children.next()?),
+            },
+            NodeKind::InfixOp => ast::Expr::InfixOp {
+                op: frame.tokens.first()?.kind,
+                lhs: Box::new(children.next()?),
+                rhs: Box::new(children.next()?),
+            },
+            NodeKind::PostfixOp => ast::Expr::PostfixOp {
+                op: frame.tokens.first()?.kind,
+                expr: Box::new(children.next()?),
+            },
+            // Parentheses just influence the tree structure.
+            NodeKind::Paren | NodeKind::Root => children.next()?,
+            NodeKind::Error => return None,
+        };
+        Some(expr)
+    }
+}
+
+impl Sink for AstBuilder<'_> {
+    fn start_node(&mut self, kind: NodeKind) {
+        self.stack.push(Frame {
+            kind,
+            tokens: Vec::new(),
+            children: Vec::new(),
+        });
+    }
+
+    fn token(&mut self, token: Token) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.tokens.push(token);
+        }
+    }
+
+    fn finish_node(&mut self) {
+        let frame = self.stack.pop().expect("unbalanced `finish_node`");
+        if let Some(expr) = self.build(frame) {
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(expr),
+                None => self.result = Some(expr),
+            }
+        }
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
+}
+
+/// Convert the text of a literal token into its value.
+pub(crate) fn literal(token: Token, input: &str) -> Result<ast::Lit, ParseError> {
+    let text = token.text(input);
+    let lit = match token.kind {
+        T![int] | T![float] => {
+            let value = if token.kind == T![int] {
+                number::int(text)
+            } else {
+                number::float(text)
+            };
+            value.map_err(|message| ParseError {
+                message,
+                span: token.span,
+            })?
+        }
+        // trim the quotation marks
+        T![string] => ast::Lit::Str(unescape(&text[1..(text.len() - 1)])),
+        _ => unreachable!("`{}` is not a literal", token.kind),
+    };
+    Ok(lit)
+}
+
+/// Resolve the `\"` and `\\` escapes the lexer allows in string literals.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            out.extend(chars.next());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Only collects errors, without building any tree.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<ParseError>,
+}
+
+impl Validator {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn finish(self) -> Vec<ParseError> {
+        self.errors
+    }
+}
+
+impl Sink for Validator {
+    fn start_node(&mut self, _kind: NodeKind) {}
+
+    fn token(&mut self, _token: Token) {}
+
+    fn finish_node(&mut self) {}
+
+    fn error(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
+}
@@ -21,7 +21,8 @@ impl<'input> Iterator for TokenIter<'input> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let next_token = self.lexer.next()?;
-            if !matches!(next_token.kind, T![ws] | T![comment]) {
+            // The parser reports unterminated comments.
+            if !next_token.kind.is_trivia() || next_token.kind == T![unterminated_comment] {
                 return Some(next_token);
             }
         }
@@ -0,0 +1,208 @@
+//! Read-only and in-place traversal of the AST.
+//!
+//! Override the `visit_*` methods for the nodes you are interested in.
+//! The default implementations call the matching `walk_*` function, which
+//! visits the node's children. Call it yourself from an override to keep
+//! descending into the tree.
+//!
+//! The `walk_*` functions match exhaustively, so adding a node kind to the
+//! AST fails to compile until both traits know about it.
+
+use super::ast::{Expr, Lit};
+use crate::lexer::TokenKind;
+
+pub trait Visitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_lit(&mut self, _lit: &Lit) {}
+
+    fn visit_ident(&mut self, _name: &str) {}
+
+    fn visit_fn_call(&mut self, fn_name: &str, args: &[Expr]) {
+        walk_fn_call(self, fn_name, args);
+    }
+
+    fn visit_prefix_op(&mut self, op: TokenKind, expr: &Expr) {
+        walk_prefix_op(self, op, expr);
+    }
+
+    fn visit_infix_op(&mut self, op: TokenKind, lhs: &Expr, rhs: &Expr) {
+        walk_infix_op(self, op, lhs, rhs);
+    }
+
+    fn visit_postfix_op(&mut self, op: TokenKind, expr: &Expr) {
+        walk_postfix_op(self, op, expr);
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Literal(lit) => visitor.visit_lit(lit),
+        Expr::Ident(name) => visitor.visit_ident(name),
+        Expr::FnCall { fn_name, args } => visitor.visit_fn_call(fn_name, args),
+        Expr::PrefixOp { op, expr } => visitor.visit_prefix_op(*op, expr),
+        Expr::InfixOp { op, lhs, rhs } => visitor.visit_infix_op(*op, lhs, rhs),
+        Expr::PostfixOp { op, expr } => visitor.visit_postfix_op(*op, expr),
+    }
+}
+
+pub fn walk_fn_call<V: Visitor + ?Sized>(visitor: &mut V, _fn_name: &str, args: &[Expr]) {
+    for arg in args {
+        visitor.visit_expr(arg);
+    }
+}
+
+pub fn walk_prefix_op<V: Visitor + ?Sized>(visitor: &mut V, _op: TokenKind, expr: &Expr) {
+    visitor.visit_expr(expr);
+}
+
+pub fn walk_infix_op<V: Visitor + ?Sized>(visitor: &mut V, _op: TokenKind, lhs: &Expr, rhs: &Expr) {
+    visitor.visit_expr(lhs);
+    visitor.visit_expr(rhs);
+}
+
+pub fn walk_postfix_op<V: Visitor + ?Sized>(visitor: &mut V, _op: TokenKind, expr: &Expr) {
+    visitor.visit_expr(expr);
+}
+
+/// Like [`Visitor`], but may modify the nodes it visits.
+pub trait VisitorMut {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_lit_mut(&mut self, _lit: &mut Lit) {}
+
+    fn visit_ident_mut(&mut self, _name: &mut String) {}
+
+    fn visit_fn_call_mut(&mut self, fn_name: &mut String, args: &mut Vec<Expr>) {
+        walk_fn_call_mut(self, fn_name, args);
+    }
+
+    fn visit_prefix_op_mut(&mut self, op: &mut TokenKind, expr: &mut Expr) {
+        walk_prefix_op_mut(self, op, expr);
+    }
+
+    fn visit_infix_op_mut(&mut self, op: &mut TokenKind, lhs: &mut Expr, rhs: &mut Expr) {
+        walk_infix_op_mut(self, op, lhs, rhs);
+    }
+
+    fn visit_postfix_op_mut(&mut self, op: &mut TokenKind, expr: &mut Expr) {
+        walk_postfix_op_mut(self, op, expr);
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Literal(lit) => visitor.visit_lit_mut(lit),
+        Expr::Ident(name) => visitor.visit_ident_mut(name),
+        Expr::FnCall { fn_name, args } => visitor.visit_fn_call_mut(fn_name, args),
+        Expr::PrefixOp { op, expr } => visitor.visit_prefix_op_mut(op, expr),
+        Expr::InfixOp { op, lhs, rhs } => visitor.visit_infix_op_mut(op, lhs, rhs),
+        Expr::PostfixOp { op, expr } => visitor.visit_postfix_op_mut(op, expr),
+    }
+}
+
+pub fn walk_fn_call_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _fn_name: &mut String,
+    args: &mut Vec<Expr>,
+) {
+    for arg in args {
+        visitor.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_prefix_op_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut TokenKind,
+    expr: &mut Expr,
+) {
+    visitor.visit_expr_mut(expr);
+}
+
+pub fn walk_infix_op_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut TokenKind,
+    lhs: &mut Expr,
+    rhs: &mut Expr,
+) {
+    visitor.visit_expr_mut(lhs);
+    visitor.visit_expr_mut(rhs);
+}
+
+pub fn walk_postfix_op_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut TokenKind,
+    expr: &mut Expr,
+) {
+    visitor.visit_expr_mut(expr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    #[test]
+    fn visitor_sees_every_ident() {
+        #[derive(Default)]
+        struct Idents(Vec<String>);
+
+        impl Visitor for Idents {
+            fn visit_ident(&mut self, name: &str) {
+                self.0.push(name.to_string());
+            }
+        }
+
+        let mut idents = Idents::default();
+        idents.visit_expr(&parse("min(test + 4, sin(2 * -PI!))"));
+        assert_eq!(idents.0, ["test", "PI"]);
+    }
+
+    #[test]
+    fn visitor_can_stop_descending() {
+        /// Counts operators, but not inside function arguments.
+        #[derive(Default)]
+        struct Operators(usize);
+
+        impl Visitor for Operators {
+            fn visit_fn_call(&mut self, _fn_name: &str, _args: &[Expr]) {}
+
+            fn visit_infix_op(&mut self, op: TokenKind, lhs: &Expr, rhs: &Expr) {
+                self.0 += 1;
+                walk_infix_op(self, op, lhs, rhs);
+            }
+        }
+
+        let mut operators = Operators::default();
+        operators.visit_expr(&parse("1 + 2 * f(3 - 4)"));
+        assert_eq!(operators.0, 2);
+    }
+
+    #[test]
+    fn visitor_mut_renames() {
+        struct Rename;
+
+        impl VisitorMut for Rename {
+            fn visit_ident_mut(&mut self, name: &mut String) {
+                name.make_ascii_uppercase();
+            }
+
+            fn visit_fn_call_mut(&mut self, fn_name: &mut String, args: &mut Vec<Expr>) {
+                fn_name.make_ascii_uppercase();
+                walk_fn_call_mut(self, fn_name, args);
+            }
+        }
+
+        let mut expr = parse("max(a, b) + c");
+        Rename.visit_expr_mut(&mut expr);
+        assert_eq!(expr, parse("MAX(A, B) + C"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Name resolution: what each identifier and call in a CST refers to.
+//!
+//! The grammar has no declarations yet, neither `let` nor parameters,
+//! functions or structs, so every name is defined by the [`Env`] that an
+//! expression is evaluated with, and there is a single scope. [`resolve`]
+//! looks up each use there and reports the names the environment does not
+//! define. Duplicate definitions and uses before a declaration cannot occur
+//! until the grammar can declare names.
+//!
+//! The results are side tables keyed by [`NodeId`], so that later passes can
+//! look up what a node refers to without walking the tree again.
+
+use crate::{
+    eval::Env,
+    lexer::Span,
+    parser::{
+        cst::{NodeId, SyntaxNode},
+        event::NodeKind,
+    },
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Definition {
+    /// A variable of the [`Env`], which may change between evaluations.
+    Variable,
+    /// A constant of the [`Env`].
+    Constant,
+    /// A host function, with any number of overloads.
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveError {
+    pub message: String,
+    /// The name that could not be resolved.
+    pub span: Span,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at <{}, {}>",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// The result of [`resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    /// What each resolved `Ident` and `FnCall` node refers to.
+    pub definitions: HashMap<NodeId, Definition>,
+    /// The `Ident` and `FnCall` nodes that use each name, resolved or not,
+    /// in source order.
+    pub uses: BTreeMap<String, Vec<NodeId>>,
+    /// The names that could not be resolved, in source order.
+    pub errors: Vec<ResolveError>,
+}
+
+/// Resolve the names used in `root`, which was parsed from `input`,
+/// against the variables, constants and functions of `env`.
+#[must_use]
+pub fn resolve(root: &SyntaxNode, input: &str, env: &Env) -> Resolution {
+    let mut resolution = Resolution::default();
+    for (id, node) in root.descendants().into_iter().enumerate() {
+        let id = NodeId(id);
+        if !matches!(node.kind, NodeKind::Ident | NodeKind::FnCall) {
+            continue;
+        }
+        // The name is the first token of both identifiers and calls.
+        let Some(token) = node.child_tokens().find(|token| !token.kind.is_trivia()) else {
+            continue;
+        };
+        let name = token.text(input);
+        resolution
+            .uses
+            .entry(name.to_string())
+            .or_default()
+            .push(id);
+
+        let is_function = !env.functions(name).is_empty();
+        let definition = match node.kind {
+            NodeKind::FnCall if is_function => Ok(Definition::Function),
+            NodeKind::FnCall if env.get(name).is_some() => {
+                Err(format!("`{name}` is not a function"))
+            }
+            NodeKind::FnCall => Err(format!("undefined function `{name}`")),
+            _ if env.is_const(name) => Ok(Definition::Constant),
+            _ if env.get(name).is_some() => Ok(Definition::Variable),
+            _ if is_function => Err(format!("`{name}` is a function, call it with `()`")),
+            _ => Err(format!("undefined variable `{name}`")),
+        };
+        match definition {
+            Ok(definition) => {
+                resolution.definitions.insert(id, definition);
+            }
+            Err(message) => resolution.errors.push(ResolveError {
+                message,
+                span: token.span,
+            }),
+        }
+    }
+    resolution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::cst;
+
+    fn span(start: u32, end: u32) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn resolves_against_the_env() {
+        let mut env = Env::with_prelude();
+        env.set("x", 2);
+        let input = "sqrt(x) * PI + x";
+        let root = cst::parse(input).root;
+        let resolution = resolve(&root, input, &env);
+        assert_eq!(resolution.errors, []);
+
+        let nodes = root.descendants();
+        let definition = |text: &str| {
+            let id = nodes
+                .iter()
+                .position(|node| {
+                    matches!(node.kind, NodeKind::Ident | NodeKind::FnCall)
+                        && node.text(input) == text
+                })
+                .unwrap();
+            resolution.definitions[&NodeId(id)]
+        };
+        assert_eq!(definition("sqrt(x)"), Definition::Function);
+        assert_eq!(definition("PI"), Definition::Constant);
+        assert_eq!(definition("x"), Definition::Variable);
+
+        let uses = &resolution.uses["x"];
+        assert_eq!(uses.len(), 2);
+        assert!(uses.iter().all(|id| nodes[id.0].text(input) == "x"));
+    }
+
+    #[test]
+    fn reports_undefined_names() {
+        let mut env = Env::with_prelude();
+        env.set("x", 2);
+        let input = "f(y) + x(1) - sqrt * z";
+        let resolution = resolve(&cst::parse(input).root, input, &env);
+        let errors: Vec<String> = resolution.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "undefined function `f` at <0, 1>",
+                "undefined variable `y` at <2, 3>",
+                "`x` is not a function at <7, 8>",
+                "`sqrt` is a function, call it with `()` at <14, 18>",
+                "undefined variable `z` at <21, 22>",
+            ]
+        );
+        assert_eq!(resolution.errors[0].span, span(0, 1));
+        assert_eq!(resolution.definitions.len(), 0);
+    }
+
+    #[test]
+    fn node_ids_are_preorder_positions() {
+        let input = "(a + b) * c";
+        let root = cst::parse(input).root;
+        let nodes = root.descendants();
+        let kinds: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| (id, node.kind, node.text(input)))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0, NodeKind::Root, "(a + b) * c"),
+                (1, NodeKind::InfixOp, "(a + b) * c"),
+                (2, NodeKind::Paren, "(a + b)"),
+                (3, NodeKind::InfixOp, "a + b"),
+                (4, NodeKind::Ident, "a"),
+                (5, NodeKind::Ident, "b"),
+                (6, NodeKind::Ident, "c"),
+            ]
+        );
+        assert_eq!(nodes[NodeId(5).0].text(input), "b");
+        assert_eq!(nodes.get(NodeId(7).0), None);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    lexer::TokenKind,
+    parser::ast::{Expr, Lit},
+    T,
+};
+use std::fmt;
+
+/// Why an expression could not be differentiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffError {
+    /// A call to a function whose derivative is unknown,
+    /// with an argument that depends on the variable.
+    UnknownFunction(String),
+    /// An operator that is not differentiable, like a comparison
+    /// or the factorial, applied to something that depends on the variable.
+    NotDifferentiable(TokenKind),
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::UnknownFunction(name) => {
+                write!(f, "cannot differentiate unknown function `{name}`")
+            }
+            DiffError::NotDifferentiable(op) => write!(f, "cannot differentiate `{op}`"),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+/// The derivative of `expr` with respect to `var`.
+///
+/// The result is not simplified, pass it through
+/// [`simplify`](super::simplify) for a readable form.
+///
+/// # Panics
+/// If the expression is not differentiable, see [`try_diff`].
+#[must_use]
+pub fn diff(expr: &Expr, var: &str) -> Expr {
+    match try_diff(expr, var) {
+        Ok(derivative) => derivative,
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// The derivative of `expr` with respect to `var`, or why it has none.
+///
+/// Subexpressions that do not mention `var` have the derivative `0`,
+/// whatever they contain. Calls to `sin`, `cos`, `tan`, `asin`, `acos`,
+/// `atan`, `sqrt`, `exp`, `ln`, `log10`, `log2`, `abs` and `pow` are
+/// differentiated with the chain rule.
+///
+/// # Errors
+/// If a function whose derivative is unknown, or an operator that is not
+/// differentiable, is applied to something that depends on `var`.
+pub fn try_diff(expr: &Expr, var: &str) -> Result<Expr, DiffError> {
+    if !mentions(expr, var) {
+        return Ok(int(0));
+    }
+
+    Ok(match expr {
+        Expr::Literal(_) => unreachable!("literals do not mention variables"),
+        Expr::Ident(_) => int(1),
+        Expr::FnCall { fn_name, args } => call(fn_name, args, var)?,
+        Expr::PrefixOp { op: T![+], expr } => try_diff(expr, var)?,
+        Expr::PrefixOp { op: T![-], expr } => neg(try_diff(expr, var)?),
+        Expr::InfixOp {
+            op: op @ (T![+] | T![-]),
+            lhs,
+            rhs,
+        } => infix(*op, try_diff(lhs, var)?, try_diff(rhs, var)?),
+        // (u * v)' = u' * v + u * v'
+        Expr::InfixOp {
+            op: T![*],
+            lhs,
+            rhs,
+        } => add(
+            mul(try_diff(lhs, var)?, (**rhs).clone()),
+            mul((**lhs).clone(), try_diff(rhs, var)?),
+        ),
+        // (u / v)' = (u' * v - u * v') / v ^ 2
+        Expr::InfixOp {
+            op: T![/],
+            lhs,
+            rhs,
+        } => div(
+            sub(
+                mul(try_diff(lhs, var)?, (**rhs).clone()),
+                mul((**lhs).clone(), try_diff(rhs, var)?),
+            ),
+            pow((**rhs).clone(), int(2)),
+        ),
+        Expr::InfixOp {
+            op: T![^],
+            lhs,
+            rhs,
+        } => power(lhs, rhs, var)?,
+        Expr::PrefixOp { op, .. } | Expr::InfixOp { op, .. } | Expr::PostfixOp { op, .. } => {
+            return Err(DiffError::NotDifferentiable(*op))
+        }
+    })
+}
+
+/// Whether `var` occurs in `expr`.
+fn mentions(expr: &Expr, var: &str) -> bool {
+    match expr {
+        Expr::Literal(_) => false,
+        Expr::Ident(name) => name == var,
+        Expr::FnCall { args, .. } => args.iter().any(|arg| mentions(arg, var)),
+        Expr::PrefixOp { expr, .. } | Expr::PostfixOp { expr, .. } => mentions(expr, var),
+        Expr::InfixOp { lhs, rhs, .. } => mentions(lhs, var) || mentions(rhs, var),
+    }
+}
+
+fn power(base: &Expr, exponent: &Expr, var: &str) -> Result<Expr, DiffError> {
+    let (u, v) = (base.clone(), exponent.clone());
+    Ok(if !mentions(exponent, var) {
+        // (u ^ c)' = c * u ^ (c - 1) * u'
+        mul(mul(v.clone(), pow(u, sub(v, int(1)))), try_diff(base, var)?)
+    } else if !mentions(base, var) {
+        // (c ^ v)' = c ^ v * ln(c) * v'
+        mul(mul(pow(u.clone(), v), ln(u)), try_diff(exponent, var)?)
+    } else {
+        // (u ^ v)' = u ^ v * (v' * ln(u) + v * u' / u)
+        mul(
+            pow(u.clone(), v.clone()),
+            add(
+                mul(try_diff(exponent, var)?, ln(u.clone())),
+                div(mul(v, try_diff(base, var)?), u),
+            ),
+        )
+    })
+}
+
+fn call(name: &str, args: &[Expr], var: &str) -> Result<Expr, DiffError> {
+    let unknown = || DiffError::UnknownFunction(name.to_string());
+    if name == "pow" {
+        return match args {
+            [base, exponent] => power(base, exponent, var),
+            _ => Err(unknown()),
+        };
+    }
+    let [u] = args else {
+        return Err(unknown());
+    };
+    let u = u.clone();
+
+    // The derivative of the outer function at `u`.
+    let outer = match name {
+        "sin" => call1("cos", u),
+        "cos" => neg(call1("sin", u)),
+        // 1 / cos(u) ^ 2
+        "tan" => div(int(1), pow(call1("cos", u), int(2))),
+        // ±1 / sqrt(1 - u ^ 2)
+        "asin" => div(int(1), call1("sqrt", sub(int(1), pow(u, int(2))))),
+        "acos" => neg(div(int(1), call1("sqrt", sub(int(1), pow(u, int(2)))))),
+        // 1 / (1 + u ^ 2)
+        "atan" => div(int(1), add(int(1), pow(u, int(2)))),
+        // 1 / (2 * sqrt(u))
+        "sqrt" => div(int(1), mul(int(2), call1("sqrt", u))),
+        "exp" => call1("exp", u),
+        "ln" => div(int(1), u),
+        // 1 / (u * ln(base))
+        "log10" => div(int(1), mul(u, ln(int(10)))),
+        "log2" => div(int(1), mul(u, ln(int(2)))),
+        // u / abs(u)
+        "abs" => div(u.clone(), call1("abs", u)),
+        _ => return Err(unknown()),
+    };
+    Ok(mul(outer, try_diff(&args[0], var)?))
+}
+
+fn int(i: i128) -> Expr {
+    Expr::Literal(Lit::Int(i))
+}
+
+fn call1(name: &str, arg: Expr) -> Expr {
+    Expr::FnCall {
+        fn_name: name.to_string(),
+        args: vec![arg],
+    }
+}
+
+fn ln(arg: Expr) -> Expr {
+    call1("ln", arg)
+}
+
+fn neg(expr: Expr) -> Expr {
+    Expr::PrefixOp {
+        op: T![-],
+        expr: Box::new(expr),
+    }
+}
+
+fn infix(op: TokenKind, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::InfixOp {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    }
+}
+
+fn add(lhs: Expr, rhs: Expr) -> Expr {
+    infix(T![+], lhs, rhs)
+}
+
+fn sub(lhs: Expr, rhs: Expr) -> Expr {
+    infix(T![-], lhs, rhs)
+}
+
+fn mul(lhs: Expr, rhs: Expr) -> Expr {
+    infix(T![*], lhs, rhs)
+}
+
+fn div(lhs: Expr, rhs: Expr) -> Expr {
+    infix(T![/], lhs, rhs)
+}
+
+fn pow(lhs: Expr, rhs: Expr) -> Expr {
+    infix(T![^], lhs, rhs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::{Env, Interpreter, Value},
+        parser::{pretty, Parser},
+        symbolic::simplify,
+    };
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).parse_expression(0)
+    }
+
+    fn derivative(input: &str) -> String {
+        pretty::print(&simplify(&diff(&parse(input), "x")))
+    }
+
+    #[test]
+    fn polynomials() {
+        assert_eq!(derivative("42"), "0");
+        assert_eq!(derivative("y"), "0");
+        assert_eq!(derivative("x"), "1");
+        assert_eq!(derivative("3 * x ^ 2 - 2 * x + 1"), "6 * x - 2");
+        assert_eq!(derivative("x ^ 3 * y"), "3 * x ^ 2 * y");
+        assert_eq!(derivative("1 / x"), "-1 / x ^ 2");
+        assert_eq!(derivative("(x + 1) * (x - 1)"), "2 * x");
+        assert_eq!(derivative("x ^ 0.5"), "0.5 / x ^ 0.5");
+        assert_eq!(derivative("x / 2"), "0.5");
+    }
+
+    #[test]
+    fn chain_rule() {
+        assert_eq!(derivative("sin(x ^ 2)"), "2 * x * cos(x ^ 2)");
+        assert_eq!(derivative("cos(2 * x)"), "-2 * sin(2 * x)");
+        assert_eq!(derivative("exp(3 * x)"), "3 * exp(3 * x)");
+        assert_eq!(derivative("ln(x ^ 2 + 1)"), "2 * x / (x ^ 2 + 1)");
+        assert_eq!(derivative("sqrt(x)"), "1 / (2 * sqrt(x))");
+        assert_eq!(derivative("2 ^ x"), "ln(2) * 2 ^ x");
+        assert_eq!(derivative("f(y)"), "0");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            try_diff(&parse("f(x)"), "x"),
+            Err(DiffError::UnknownFunction("f".to_string()))
+        );
+        assert_eq!(
+            try_diff(&parse("x!"), "x"),
+            Err(DiffError::NotDifferentiable(T![!]))
+        );
+        assert_eq!(
+            try_diff(&parse("x < 1"), "x"),
+            Err(DiffError::NotDifferentiable(T![<]))
+        );
+        assert_eq!(try_diff(&parse("y!"), "x"), Ok(int(0)));
+    }
+
+    /// Compares the derivative with a central difference quotient.
+    #[test]
+    fn matches_numeric_derivative() {
+        let interpreter = Interpreter::new();
+        let at = |expr: &Expr, x: f64| {
+            let mut env = Env::with_prelude();
+            env.set("x", x);
+            env.set("y", 1.5);
+            match interpreter.eval(expr, &env) {
+                Ok(Value::Float(fl)) => fl,
+                result => panic!("`{}` evaluated to {:?}", pretty::print(expr), result),
+            }
+        };
+
+        for input in [
+            "x ^ 3 - 4 * x / y",
+            "sin(x) * cos(x) ^ 2",
+            "tan(x / 2) + atan(x)",
+            "asin(x / 2) - acos(x / 3)",
+            "sqrt(x ^ 2 + 1) / exp(x)",
+            "log10(x) + log2(x) + ln(x)",
+            "x ^ x",
+            "pow(x, y) * abs(x - 2)",
+        ] {
+            let expr = parse(input);
+            let derivative = simplify(&diff(&expr, "x"));
+            for x in [0.3, 0.7, 1.1] {
+                let h = 1e-6;
+                let numeric = (at(&expr, x + h) - at(&expr, x - h)) / (2.0 * h);
+                let symbolic = at(&derivative, x);
+                assert!(
+                    (numeric - symbolic).abs() < 1e-5 * numeric.abs().max(1.0),
+                    "d/dx `{}` = `{}` at {}: {} != {}",
+                    input,
+                    pretty::print(&derivative),
+                    x,
+                    symbolic,
+                    numeric
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Symbolic manipulation of expressions.
+//!
+//! [`diff`] differentiates an [`Expr`](crate::parser::ast::Expr) with
+//! respect to a variable and [`simplify`] normalises the result, or any
+//! other expression, into a canonical form:
+//!
+//! ```text
+//! diff(sin(x ^ 2), x)  =>  cos(x ^ 2) * (2 * x ^ (2 - 1) * 1)
+//! simplify(...)        =>  2 * x * cos(x ^ 2)
+//! ```
+
+mod diff;
+mod num;
+mod simplify;
+
+pub use diff::{diff, try_diff, DiffError};
+pub use simplify::simplify;
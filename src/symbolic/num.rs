@@ -0,0 +1,142 @@
+use crate::parser::ast::{Expr, Lit};
+use std::cmp::Ordering;
+
+/// A number of the simplifier: an exact fraction while integer arithmetic
+/// suffices, a float as soon as a float is involved or a fraction overflows.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Num {
+    /// Numerator and a positive denominator, in lowest terms.
+    Ratio(i64, i64),
+    Float(f64),
+}
+
+impl Num {
+    pub(super) const ZERO: Num = Num::Ratio(0, 1);
+    pub(super) const ONE: Num = Num::Ratio(1, 1);
+
+    pub(super) fn int(i: i64) -> Num {
+        Num::Ratio(i, 1)
+    }
+
+    fn ratio(numer: i64, denom: i64) -> Option<Num> {
+        if denom == 0 {
+            return None;
+        }
+        let divisor = gcd(numer, denom);
+        let (numer, denom) = (numer / divisor, denom / divisor);
+        if denom < 0 {
+            Some(Num::Ratio(numer.checked_neg()?, denom.checked_neg()?))
+        } else {
+            Some(Num::Ratio(numer, denom))
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub(super) fn to_f64(self) -> f64 {
+        match self {
+            Num::Ratio(numer, denom) => numer as f64 / denom as f64,
+            Num::Float(fl) => fl,
+        }
+    }
+
+    #[allow(clippy::float_cmp)]
+    pub(super) fn is_zero(self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    #[allow(clippy::float_cmp)]
+    pub(super) fn is_one(self) -> bool {
+        self.to_f64() == 1.0
+    }
+
+    pub(super) fn is_negative(self) -> bool {
+        self.to_f64() < 0.0
+    }
+
+    pub(super) fn is_integer(self) -> bool {
+        matches!(self, Num::Ratio(_, 1))
+    }
+
+    pub(super) fn add(self, other: Num) -> Num {
+        if let (Num::Ratio(a, b), Num::Ratio(c, d)) = (self, other) {
+            let exact = (|| {
+                let numer = a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?;
+                Num::ratio(numer, b.checked_mul(d)?)
+            })();
+            if let Some(exact) = exact {
+                return exact;
+            }
+        }
+        Num::Float(self.to_f64() + other.to_f64())
+    }
+
+    pub(super) fn mul(self, other: Num) -> Num {
+        if let (Num::Ratio(a, b), Num::Ratio(c, d)) = (self, other) {
+            if let Some(exact) = a
+                .checked_mul(c)
+                .zip(b.checked_mul(d))
+                .and_then(|(numer, denom)| Num::ratio(numer, denom))
+            {
+                return exact;
+            }
+        }
+        Num::Float(self.to_f64() * other.to_f64())
+    }
+
+    pub(super) fn neg(self) -> Num {
+        self.mul(Num::int(-1))
+    }
+
+    /// `self ^ exponent`, if the result is a finite number.
+    pub(super) fn pow(self, exponent: Num) -> Option<Num> {
+        if let (Num::Ratio(numer, denom), Num::Ratio(exponent, 1)) = (self, exponent) {
+            let exact = u32::try_from(exponent.unsigned_abs())
+                .ok()
+                .and_then(|e| Some((numer.checked_pow(e)?, denom.checked_pow(e)?)))
+                .and_then(|(numer, denom)| {
+                    if exponent < 0 {
+                        Num::ratio(denom, numer)
+                    } else {
+                        Num::ratio(numer, denom)
+                    }
+                });
+            if exact.is_some() {
+                return exact;
+            }
+        }
+        let result = self.to_f64().powf(exponent.to_f64());
+        result.is_finite().then_some(Num::Float(result))
+    }
+
+    pub(super) fn abs(self) -> Num {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self
+        }
+    }
+
+    pub(super) fn cmp(self, other: Num) -> Ordering {
+        self.to_f64().total_cmp(&other.to_f64())
+    }
+
+    /// The number as a literal. A fraction that is not an integer becomes
+    /// a float, since dividing integers would round it.
+    pub(super) fn to_expr(self) -> Expr {
+        Expr::Literal(match self {
+            Num::Ratio(numer, 1) => Lit::Int(numer.into()),
+            Num::Ratio(..) | Num::Float(_) => Lit::Float(self.to_f64()),
+        })
+    }
+}
+
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    if a == 0 {
+        1
+    } else {
+        a.abs()
+    }
+}
@@ -0,0 +1,644 @@
+use super::num::Num;
+use crate::{
+    eval::{infix, postfix, prefix, OverflowPolicy, RuntimeError, Value},
+    parser::{
+        ast::{Expr, Lit},
+        pretty,
+    },
+    T,
+};
+use std::cmp::Ordering;
+
+/// Operations on constants are computed like the interpreter computes
+/// them by default.
+const OVERFLOW: OverflowPolicy = OverflowPolicy::Error;
+
+/// Bring `expr` into a canonical form.
+///
+/// Sums and products are flattened, numbers are combined exactly where
+/// possible, and like terms and like factors are collected, so `x * x + x`
+/// becomes `x ^ 2 + x`. Products of sums are multiplied out. Terms are
+/// ordered by descending degree, with the constant last, and negative
+/// exponents are written as a division.
+///
+/// Operations on constants alone are computed like the interpreter does,
+/// so `7 / 2` becomes `3`. Those that fail, like `0 / 0` or `2 ^ 100`,
+/// are kept together with everything that contains them, so that the
+/// result fails in the same way. Otherwise numbers are treated as real
+/// numbers: `x / x` simplifies to `1` without regard for `x` being zero,
+/// and integer division of variables is not preserved. A fraction that is
+/// left on its own is written as a float, like `0.5`.
+///
+/// Operators other than arithmetic ones are kept, with their operands
+/// simplified on their own.
+#[must_use]
+pub fn simplify(expr: &Expr) -> Expr {
+    Sum::from_expr(expr).to_expr()
+}
+
+/// An expression that is not a sum or product of other expressions,
+/// such as a variable or a call.
+#[derive(Debug, Clone)]
+struct Atom {
+    /// Variables sort before calls, which sort before everything else.
+    rank: u8,
+    /// The printed expression, to compare and sort atoms by.
+    key: String,
+    expr: Expr,
+}
+
+impl Atom {
+    fn new(expr: Expr) -> Atom {
+        let rank = match expr {
+            Expr::Ident(_) => 0,
+            Expr::FnCall { .. } => 1,
+            _ => 2,
+        };
+        Atom {
+            rank,
+            key: pretty::print(&expr),
+            expr,
+        }
+    }
+
+    fn cmp(&self, other: &Atom) -> Ordering {
+        (self.rank, &self.key).cmp(&(other.rank, &other.key))
+    }
+}
+
+/// A product of atoms raised to numeric powers, sorted by atom.
+type Factors = Vec<(Atom, Num)>;
+
+fn degree(factors: &Factors) -> f64 {
+    factors.iter().map(|(_, exponent)| exponent.to_f64()).sum()
+}
+
+fn cmp_factors(a: &Factors, b: &Factors) -> Ordering {
+    degree(b).total_cmp(&degree(a)).then_with(|| {
+        for ((a, a_exponent), (b, b_exponent)) in a.iter().zip(b) {
+            let ordering = a.cmp(b).then_with(|| b_exponent.cmp(*a_exponent));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        b.len().cmp(&a.len())
+    })
+}
+
+fn multiply_factors(a: &Factors, b: &Factors) -> Factors {
+    let mut product = a.clone();
+    for (atom, exponent) in b {
+        match product.iter_mut().find(|(other, _)| other.key == atom.key) {
+            Some((_, other)) => *other = other.add(*exponent),
+            None => product.push((atom.clone(), *exponent)),
+        }
+    }
+    product.retain(|(_, exponent)| !exponent.is_zero());
+    product.sort_by(|(a, _), (b, _)| a.cmp(b));
+    product
+}
+
+/// A constant plus a sum of products with coefficients.
+#[derive(Debug, Clone)]
+struct Sum {
+    constant: Num,
+    terms: Vec<(Factors, Num)>,
+    /// The sum is the value of constants alone, as the interpreter
+    /// computes it, rather than a number in the sense of the real numbers.
+    exact: bool,
+    /// The sum is a single atom that may fail to evaluate, which must not
+    /// be combined with anything, lest it cancels out.
+    fails: bool,
+}
+
+impl Sum {
+    fn constant(constant: Num) -> Sum {
+        Sum {
+            constant,
+            terms: Vec::new(),
+            exact: false,
+            fails: false,
+        }
+    }
+
+    /// An expression that may fail, with its operands already simplified.
+    fn failing(expr: Expr) -> Sum {
+        Sum {
+            fails: true,
+            ..Sum::atom(expr)
+        }
+    }
+
+    fn atom(expr: Expr) -> Sum {
+        Sum::power(Atom::new(expr), Num::ONE)
+    }
+
+    fn power(atom: Atom, exponent: Num) -> Sum {
+        Sum {
+            constant: Num::ZERO,
+            terms: vec![(vec![(atom, exponent)], Num::ONE)],
+            exact: false,
+            fails: false,
+        }
+    }
+
+    fn as_constant(&self) -> Option<Num> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+
+    /// The only term, if there is exactly one and no constant.
+    fn as_term(&self) -> Option<&(Factors, Num)> {
+        match self.terms.as_slice() {
+            [term] if self.constant.is_zero() => Some(term),
+            _ => None,
+        }
+    }
+
+    /// A value of the interpreter, if it is a number or a string.
+    fn value(value: Value) -> Option<Sum> {
+        let sum = match value {
+            Value::Int(i) => Sum::constant(Num::int(i)),
+            Value::Float(fl) => Sum::constant(Num::Float(fl)),
+            Value::Str(s) => Sum::atom(Expr::Literal(Lit::Str(s))),
+            Value::BigInt(_) | Value::Bool(_) => return None,
+        };
+        Some(Sum { exact: true, ..sum })
+    }
+
+    /// The value of an exact sum.
+    fn as_value(&self) -> Option<Value> {
+        if !self.exact {
+            return None;
+        }
+        if let Some(constant) = self.as_constant() {
+            return match constant {
+                Num::Ratio(i, 1) => Some(Value::Int(i)),
+                Num::Float(fl) => Some(Value::Float(fl)),
+                Num::Ratio(..) => None,
+            };
+        }
+        match self.as_term()? {
+            (factors, coefficient) if coefficient.is_one() => match factors.as_slice() {
+                [(atom, exponent)] if exponent.is_one() => match &atom.expr {
+                    Expr::Literal(Lit::Str(s)) => Some(Value::Str(s.clone())),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The result of an operation on constants, or the operation itself
+    /// if it fails.
+    fn evaluated(result: Result<Value, RuntimeError>, operation: impl FnOnce() -> Expr) -> Sum {
+        result
+            .ok()
+            .and_then(Sum::value)
+            .unwrap_or_else(|| Sum::failing(operation()))
+    }
+
+    fn from_expr(expr: &Expr) -> Sum {
+        match expr {
+            Expr::Literal(lit) => {
+                let value = match lit {
+                    Lit::Int(i) => i64::try_from(*i).ok().map(Value::Int),
+                    Lit::BigInt(_) => None,
+                    Lit::Float(fl) => Some(Value::Float(*fl)),
+                    Lit::Str(s) => Some(Value::Str(s.clone())),
+                };
+                // Integers beyond `i64` fail to evaluate.
+                value
+                    .and_then(Sum::value)
+                    .unwrap_or_else(|| Sum::failing(expr.clone()))
+            }
+            Expr::Ident(_) => Sum::atom(expr.clone()),
+            Expr::FnCall { fn_name, args } => {
+                let args: Vec<Sum> = args.iter().map(Sum::from_expr).collect();
+                let call = Expr::FnCall {
+                    fn_name: fn_name.clone(),
+                    args: args.iter().map(Sum::to_expr).collect(),
+                };
+                if args.iter().any(|arg| arg.fails) {
+                    Sum::failing(call)
+                } else {
+                    Sum::atom(call)
+                }
+            }
+            Expr::PrefixOp { op, expr } => {
+                let operand = Sum::from_expr(expr);
+                let operation = || Expr::PrefixOp {
+                    op: *op,
+                    expr: Box::new(operand.to_expr()),
+                };
+                if operand.fails {
+                    return Sum::failing(operation());
+                }
+                match (op, operand.as_value()) {
+                    (T![-] | T![+], Some(value)) => {
+                        Sum::evaluated(prefix(*op, value, OVERFLOW), operation)
+                    }
+                    (T![-], None) => operand.scale(Num::int(-1)),
+                    (T![+], None) => operand,
+                    _ => Sum::atom(operation()),
+                }
+            }
+            Expr::InfixOp { op, lhs, rhs } => {
+                let lhs = Sum::from_expr(lhs);
+                let rhs = Sum::from_expr(rhs);
+                let operation = || Expr::InfixOp {
+                    op: *op,
+                    lhs: Box::new(lhs.to_expr()),
+                    rhs: Box::new(rhs.to_expr()),
+                };
+                if lhs.fails || rhs.fails {
+                    return Sum::failing(operation());
+                }
+                if !matches!(op, T![+] | T![-] | T![*] | T![/] | T![^]) {
+                    return Sum::atom(operation());
+                }
+                if let (Some(a), Some(b)) = (lhs.as_value(), rhs.as_value()) {
+                    return Sum::evaluated(infix(*op, &a, &b, OVERFLOW), operation);
+                }
+                match op {
+                    T![+] => lhs.add(rhs),
+                    T![-] => lhs.add(rhs.scale(Num::int(-1))),
+                    T![*] => lhs.mul(&rhs),
+                    T![/] if rhs.as_constant().is_some_and(Num::is_zero) => {
+                        Sum::failing(operation())
+                    }
+                    T![/] => lhs.mul(&rhs.pow(&Sum::constant(Num::int(-1)))),
+                    _ => lhs.pow(&rhs),
+                }
+            }
+            Expr::PostfixOp { op, expr } => {
+                let operand = Sum::from_expr(expr);
+                let operation = || Expr::PostfixOp {
+                    op: *op,
+                    expr: Box::new(operand.to_expr()),
+                };
+                if operand.fails {
+                    return Sum::failing(operation());
+                }
+                match operand.as_value() {
+                    Some(value) => Sum::evaluated(postfix(*op, value, OVERFLOW), operation),
+                    None => Sum::atom(operation()),
+                }
+            }
+        }
+    }
+
+    fn add(mut self, other: Sum) -> Sum {
+        self.exact = false;
+        self.constant = self.constant.add(other.constant);
+        for (factors, coefficient) in other.terms {
+            self.add_term(factors, coefficient);
+        }
+        self
+    }
+
+    fn add_term(&mut self, factors: Factors, coefficient: Num) {
+        if factors.is_empty() {
+            self.constant = self.constant.add(coefficient);
+            return;
+        }
+        match self
+            .terms
+            .iter_mut()
+            .find(|(other, _)| cmp_factors(other, &factors) == Ordering::Equal)
+        {
+            Some((_, other)) => *other = other.add(coefficient),
+            None => self.terms.push((factors, coefficient)),
+        }
+        self.terms.retain(|(_, coefficient)| !coefficient.is_zero());
+    }
+
+    fn scale(self, factor: Num) -> Sum {
+        let mut scaled = Sum::constant(self.constant.mul(factor));
+        for (factors, coefficient) in self.terms {
+            scaled.add_term(factors, coefficient.mul(factor));
+        }
+        scaled
+    }
+
+    fn mul(&self, other: &Sum) -> Sum {
+        let mut product = Sum::constant(self.constant.mul(other.constant));
+        for (factors, coefficient) in &self.terms {
+            product.add_term(factors.clone(), coefficient.mul(other.constant));
+        }
+        for (factors, coefficient) in &other.terms {
+            product.add_term(factors.clone(), coefficient.mul(self.constant));
+        }
+        for (a, a_coefficient) in &self.terms {
+            for (b, b_coefficient) in &other.terms {
+                product.add_term(multiply_factors(a, b), a_coefficient.mul(*b_coefficient));
+            }
+        }
+        product
+    }
+
+    fn pow(self, exponent: &Sum) -> Sum {
+        let Some(n) = exponent.as_constant() else {
+            let power = Expr::InfixOp {
+                op: T![^],
+                lhs: Box::new(self.to_expr()),
+                rhs: Box::new(exponent.to_expr()),
+            };
+            return Sum::atom(power);
+        };
+
+        if n.is_zero() {
+            return Sum::constant(Num::ONE);
+        }
+        if let Some(base) = self.as_constant() {
+            return match base.pow(n) {
+                Some(result) => Sum::constant(result),
+                None => Sum::power(Atom::new(self.to_expr()), n),
+            };
+        }
+        if let Some((factors, coefficient)) = self.as_term() {
+            // `(x ^ 2) ^ 0.5` is `|x|`, not `x`. Only integer powers
+            // distribute over the factors of a product.
+            let single = factors.len() == 1 && factors[0].1.is_one() && coefficient.is_one();
+            if n.is_integer() || single {
+                if let Some(coefficient) = coefficient.pow(n) {
+                    let factors = factors
+                        .iter()
+                        .map(|(atom, exponent)| (atom.clone(), exponent.mul(n)))
+                        .collect();
+                    return Sum {
+                        constant: Num::ZERO,
+                        terms: vec![(factors, coefficient)],
+                        exact: false,
+                        fails: false,
+                    };
+                }
+            }
+        }
+        Sum::power(Atom::new(self.to_expr()), n)
+    }
+
+    fn to_expr(&self) -> Expr {
+        if let Some(constant) = self.as_constant() {
+            // Including a zero, which keeps its type and sign.
+            return constant.to_expr();
+        }
+        let mut terms: Vec<&(Factors, Num)> = self.terms.iter().collect();
+        terms.sort_by(|(a, _), (b, _)| cmp_factors(a, b));
+
+        let mut sum: Option<Expr> = None;
+        let mut push = |coefficient: Num, term: Expr| {
+            sum = Some(match sum.take() {
+                None if coefficient.is_negative() => negate_leftmost(term),
+                None => term,
+                Some(lhs) => Expr::InfixOp {
+                    op: if coefficient.is_negative() {
+                        T![-]
+                    } else {
+                        T![+]
+                    },
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(term),
+                },
+            });
+        };
+        for (factors, coefficient) in terms {
+            push(*coefficient, term_expr(coefficient.abs(), factors));
+        }
+        if !self.constant.is_zero() {
+            push(self.constant, self.constant.abs().to_expr());
+        }
+        sum.unwrap_or(Expr::Literal(Lit::Int(0)))
+    }
+}
+
+/// `coefficient * factors`, with the negative powers in a denominator.
+fn term_expr(coefficient: Num, factors: &Factors) -> Expr {
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+
+    match coefficient {
+        Num::Ratio(numer, denom) => {
+            if numer != 1 {
+                numerator.push(Num::int(numer).to_expr());
+            }
+            if denom != 1 {
+                denominator.push(Num::int(denom).to_expr());
+            }
+        }
+        Num::Float(_) if coefficient.is_one() => {}
+        Num::Float(_) => numerator.push(coefficient.to_expr()),
+    }
+    for (atom, exponent) in factors {
+        let (list, exponent) = if exponent.is_negative() {
+            (&mut denominator, exponent.neg())
+        } else {
+            (&mut numerator, *exponent)
+        };
+        list.push(if exponent.is_one() {
+            atom.expr.clone()
+        } else {
+            Expr::InfixOp {
+                op: T![^],
+                lhs: Box::new(atom.expr.clone()),
+                rhs: Box::new(exponent.to_expr()),
+            }
+        });
+    }
+
+    let numerator = product(numerator);
+    if denominator.is_empty() {
+        numerator
+    } else {
+        Expr::InfixOp {
+            op: T![/],
+            lhs: Box::new(numerator),
+            rhs: Box::new(product(denominator)),
+        }
+    }
+}
+
+fn product(factors: Vec<Expr>) -> Expr {
+    factors
+        .into_iter()
+        .reduce(|lhs, rhs| Expr::InfixOp {
+            op: T![*],
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+        .unwrap_or(Expr::Literal(Lit::Int(1)))
+}
+
+/// Negate a product by negating its leftmost factor,
+/// which reads as `-2 * x` rather than `-(2 * x)`.
+fn negate_leftmost(expr: Expr) -> Expr {
+    match expr {
+        Expr::InfixOp {
+            op: op @ (T![*] | T![/]),
+            lhs,
+            rhs,
+        } => Expr::InfixOp {
+            op,
+            lhs: Box::new(negate_leftmost(*lhs)),
+            rhs,
+        },
+        expr => Expr::PrefixOp {
+            op: T![-],
+            expr: Box::new(expr),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::{Env, Interpreter},
+        parser::Parser,
+    };
+
+    fn simplified(input: &str) -> String {
+        pretty::print(&simplify(&Parser::new(input).parse_expression(0)))
+    }
+
+    #[test]
+    fn combines_numbers() {
+        assert_eq!(simplified("1 + 2 * 3"), "7");
+        assert_eq!(simplified("0.5 + 1"), "1.5");
+        assert_eq!(simplified("x * 0 + 0 * y"), "0");
+        // Like the interpreter: integer division truncates and literals
+        // keep their types.
+        assert_eq!(simplified("7 / 2"), "3");
+        assert_eq!(simplified("1 / 3 + 1 / 6"), "0");
+        assert_eq!(simplified("-0.0"), "-0.0");
+        assert_eq!(simplified("1.0 * 2"), "2.0");
+        assert_eq!(simplified("x / 2 + x / 2"), "x");
+        assert_eq!(simplified("(x + 1) / 2 - x / 2"), "0.5");
+    }
+
+    #[test]
+    fn keeps_failing_operations() {
+        assert_eq!(simplified("0 / 0"), "0 / 0");
+        assert_eq!(simplified("2 ^ -2"), "2 ^ -2");
+        assert_eq!(simplified("2 ^ 100"), "2 ^ 100");
+        assert_eq!(simplified("x / 0"), "x / 0");
+        assert_eq!(simplified("1 / 0 - 1 / 0"), "1 / 0 - 1 / 0");
+        assert_eq!(simplified("(1 + 1) / (1 - 1) * 0"), "2 / 0 * 0");
+    }
+
+    #[test]
+    fn collects_like_terms_and_factors() {
+        assert_eq!(simplified("x + x"), "2 * x");
+        assert_eq!(simplified("x * x * 3 * x"), "3 * x ^ 3");
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("x / x"), "1");
+        assert_eq!(simplified("y * x + x * y"), "2 * x * y");
+        assert_eq!(simplified("x ^ 2 / x"), "x");
+        assert_eq!(simplified("sin(x) * x"), "x * sin(x)");
+    }
+
+    #[test]
+    fn canonical_order() {
+        assert_eq!(simplified("1 + x + x ^ 2"), "x ^ 2 + x + 1");
+        assert_eq!(simplified("(x + 1) * (x - 1)"), "x ^ 2 - 1");
+        assert_eq!(simplified("(x + 1) * (x + 1)"), "x ^ 2 + 2 * x + 1");
+        assert_eq!(simplified("b + a - c"), "a + b - c");
+        assert_eq!(simplified("3 - x"), "-x + 3");
+        assert_eq!(simplified("-(2 * x) / 3"), "-2 * x / 3");
+        assert_eq!(simplified("1 / (2 * x ^ 2)"), "1 / (2 * x ^ 2)");
+    }
+
+    #[test]
+    fn powers() {
+        assert_eq!(simplified("(x ^ 2) ^ 3"), "x ^ 6");
+        assert_eq!(simplified("(2 * x) ^ 2"), "4 * x ^ 2");
+        assert_eq!(simplified("(x ^ 2) ^ 0.5"), "(x ^ 2) ^ 0.5");
+        assert_eq!(simplified("x ^ 0.5 * x ^ 0.5"), "x");
+        assert_eq!(simplified("(x + 1) ^ 2 / (x + 1)"), "x + 1");
+        assert_eq!(simplified("x ^ y * x ^ y"), "(x ^ y) ^ 2");
+        assert_eq!(simplified("x ^ 0"), "1");
+    }
+
+    #[test]
+    fn simplifies_inside_other_operators() {
+        assert_eq!(simplified("sin(x + x) < 1 + 1"), "sin(2 * x) < 2");
+        assert_eq!(simplified(r#"f("a") + f("a")"#), r#"2 * f("a")"#);
+    }
+
+    #[test]
+    fn simplifying_twice_changes_nothing() {
+        for input in [
+            "(x + 1) * (x - 2) / x",
+            "-x ^ 2 * 3 / y + y / 3",
+            "sin(x) ^ 2 + cos(x) ^ 2 - 1",
+            "1 / (x ^ 2 + 1) ^ 2",
+            "2 ^ x * 2 ^ x",
+        ] {
+            let once = simplify(&Parser::new(input).parse_expression(0));
+            assert_eq!(simplify(&once), once, "simplifying `{input}`");
+        }
+    }
+
+    /// Small deterministic pseudo-random generator, enough to make up trees.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    fn random_source(rng: &mut Lcg, depth: usize) -> String {
+        const LEAVES: [&str; 9] = ["0", "1", "2", "3", "100", "0.0", "0.5", "-0.0", r#""a""#];
+        const INFIX: [&str; 5] = ["+", "-", "*", "/", "^"];
+
+        if depth == 0 || rng.below(3) == 0 {
+            return LEAVES[rng.below(LEAVES.len())].to_string();
+        }
+        match rng.below(5) {
+            0 => format!(
+                "{}({})",
+                ["+", "-"][rng.below(2)],
+                random_source(rng, depth - 1)
+            ),
+            _ => format!(
+                "({}) {} ({})",
+                random_source(rng, depth - 1),
+                INFIX[rng.below(INFIX.len())],
+                random_source(rng, depth - 1)
+            ),
+        }
+    }
+
+    #[test]
+    fn simplifying_preserves_results() {
+        let mut rng = Lcg(37);
+        for _ in 0..5000 {
+            let source = random_source(&mut rng, 4);
+            let expr = Parser::new(&source).parse_expression(0);
+            let simplified = simplify(&expr);
+
+            let expected = Interpreter::new().eval(&expr, &Env::new());
+            let actual = Interpreter::new().eval(&simplified, &Env::new());
+            let same = match (&expected, &actual) {
+                // NaN is not equal to itself, and `-0.0` equals `0.0`.
+                (Ok(Value::Float(a)), Ok(Value::Float(b))) => {
+                    a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())
+                }
+                _ => expected == actual,
+            };
+            assert!(
+                same,
+                "`{}` simplified to `{}`: {:?} != {:?}",
+                source,
+                pretty::print(&simplified),
+                expected,
+                actual
+            );
+        }
+    }
+}
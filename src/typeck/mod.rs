@@ -0,0 +1,698 @@
+//! Static type checking of expressions.
+//!
+//! [`check`] infers the type of every node of a CST without evaluating it.
+//! Variables and functions that the [`Env`] knows have the types of their
+//! values and signatures. Any other variable starts out with an unknown type,
+//! which is inferred from its uses by unification: in `x * 2 > y`, `x` and
+//! `y` are both `int`. An unknown operand of an arithmetic or comparison
+//! operator takes the type of the other operand, rather than being left open
+//! between `int` and `float`.
+//!
+//! A mismatch is reported at the expression with the unexpected type, and
+//! points to the place the expected type comes from: the other operand, an
+//! earlier use of the same variable, an operator or a called function.
+//!
+//! The grammar has no declarations yet, so there are no annotations to check
+//! and nothing to generalise. Every variable has exactly one type.
+
+use crate::{
+    eval::{Env, Type},
+    lexer::{Span, Token},
+    parser::{
+        ast::Lit,
+        cst::{NodeId, SyntaxNode},
+        event::NodeKind,
+        sink::literal,
+    },
+    T,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, ptr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub message: String,
+    /// The expression with the unexpected type.
+    pub span: Span,
+    /// What the expected type was derived from, if there is such a place.
+    pub expected_span: Option<Span>,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at <{}, {}>",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// The result of [`check`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Typing {
+    /// The type of the whole expression, unless it could not be inferred.
+    pub ty: Option<Type>,
+    /// The inferred types of the variables that are not bound in the [`Env`].
+    pub variables: BTreeMap<String, Type>,
+    /// The types of all nodes whose type could be inferred, by their id in
+    /// the checked tree.
+    pub node_types: HashMap<NodeId, Type>,
+    /// The mismatches, in the order of their positions.
+    pub errors: Vec<TypeError>,
+}
+
+/// Infer the types of the expression in `root`, which was parsed from
+/// `input`, with the variables, constants and functions of `env`.
+#[must_use]
+pub fn check(root: &SyntaxNode, input: &str, env: &Env) -> Typing {
+    let mut checker = Checker {
+        input,
+        env,
+        slots: Vec::new(),
+        variables: BTreeMap::new(),
+        nodes: Vec::new(),
+        errors: Vec::new(),
+    };
+    let ty = checker.expr(root);
+
+    let mut typing = Typing {
+        ty: checker.resolved(ty).map(|(ty, _)| ty),
+        ..Typing::default()
+    };
+    for (name, slot) in std::mem::take(&mut checker.variables) {
+        if let Some((ty, _)) = checker.resolved(slot) {
+            typing.variables.insert(name, ty);
+        }
+    }
+    // The checker skips some nodes, so it cannot count them to know their
+    // ids. It remembers which node each slot belongs to instead.
+    let ids: HashMap<*const SyntaxNode, NodeId> = root
+        .descendants()
+        .into_iter()
+        .enumerate()
+        .map(|(id, node)| (ptr::from_ref(node), NodeId(id)))
+        .collect();
+    for (node, slot) in std::mem::take(&mut checker.nodes) {
+        if let Some((ty, _)) = checker.resolved(slot) {
+            typing.node_types.insert(ids[&node], ty);
+        }
+    }
+    typing.errors = checker.errors;
+    typing.errors.sort_by_key(|error| error.span.start);
+    typing
+}
+
+/// What an unknown type is known to be, from the most to the least specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Number,
+    NumberOrString,
+    Any,
+}
+
+impl Kind {
+    fn admits(self, ty: Type) -> bool {
+        match self {
+            Kind::Number => matches!(ty, Type::Int | Type::Float),
+            Kind::NumberOrString => matches!(ty, Type::Int | Type::Float | Type::Str),
+            Kind::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Kind::Number => "a number",
+                Kind::NumberOrString => "a number or string",
+                Kind::Any => "any type",
+            }
+        )
+    }
+}
+
+/// A type in the union-find structure. Only the representative
+/// of a set, the slot that is its own parent, has meaningful fields.
+struct Slot {
+    parent: usize,
+    /// The type, once known, and where it was first determined.
+    ty: Option<(Type, Span)>,
+    /// The constraint on a still unknown type, and where it comes from.
+    kind: (Kind, Option<Span>),
+}
+
+struct Checker<'a> {
+    input: &'a str,
+    env: &'a Env,
+    slots: Vec<Slot>,
+    variables: BTreeMap<String, usize>,
+    /// The slot of each checked node.
+    nodes: Vec<(*const SyntaxNode, usize)>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    fn fresh(&mut self) -> usize {
+        self.slots.push(Slot {
+            parent: self.slots.len(),
+            ty: None,
+            kind: (Kind::Any, None),
+        });
+        self.slots.len() - 1
+    }
+
+    fn known(&mut self, ty: Type, span: Span) -> usize {
+        let slot = self.fresh();
+        self.slots[slot].ty = Some((ty, span));
+        slot
+    }
+
+    fn find(&mut self, slot: usize) -> usize {
+        let parent = self.slots[slot].parent;
+        if parent == slot {
+            return slot;
+        }
+        let root = self.find(parent);
+        self.slots[slot].parent = root;
+        root
+    }
+
+    fn resolved(&mut self, slot: usize) -> Option<(Type, Span)> {
+        let root = self.find(slot);
+        self.slots[root].ty
+    }
+
+    fn error(&mut self, message: String, span: Span, expected_span: Option<Span>) {
+        self.errors.push(TypeError {
+            message,
+            span,
+            expected_span,
+        });
+    }
+
+    /// Make `actual`, the type of the expression at `span`, the same
+    /// as `expected`. Reports and returns `false` if they differ.
+    fn unify(&mut self, expected: usize, actual: usize, span: Span) -> bool {
+        let (expected, actual) = (self.find(expected), self.find(actual));
+        if expected == actual {
+            return true;
+        }
+
+        let (expected_slot, actual_slot) = (&self.slots[expected], &self.slots[actual]);
+        match (expected_slot.ty, actual_slot.ty) {
+            (Some((expected_ty, origin)), Some((actual_ty, _))) if expected_ty != actual_ty => {
+                let message = format!("expected `{expected_ty}`, found `{actual_ty}`");
+                self.error(message, span, Some(origin));
+                return false;
+            }
+            (Some((expected_ty, origin)), None) if !actual_slot.kind.0.admits(expected_ty) => {
+                let message = format!("expected `{}`, found {}", expected_ty, actual_slot.kind.0);
+                self.error(message, span, Some(origin));
+                return false;
+            }
+            (None, Some((actual_ty, _))) if !expected_slot.kind.0.admits(actual_ty) => {
+                let (kind, origin) = expected_slot.kind;
+                let message = format!("expected {kind}, found `{actual_ty}`");
+                self.error(message, span, origin);
+                return false;
+            }
+            _ => {}
+        }
+
+        let ty = expected_slot.ty.or(actual_slot.ty);
+        let kind = std::cmp::min_by_key(expected_slot.kind, actual_slot.kind, |kind| kind.0);
+        self.slots[actual].parent = expected;
+        self.slots[expected].ty = ty;
+        self.slots[expected].kind = kind;
+        true
+    }
+
+    fn unify_with(&mut self, ty: Type, origin: Span, actual: usize, span: Span) -> bool {
+        let expected = self.known(ty, origin);
+        self.unify(expected, actual, span)
+    }
+
+    /// Require the type of the expression at `span` to be of `kind`,
+    /// because of the operator at `origin`.
+    fn constrain(&mut self, slot: usize, kind: Kind, origin: Span, span: Span) -> bool {
+        let root = self.find(slot);
+        match self.slots[root].ty {
+            Some((ty, _)) if !kind.admits(ty) => {
+                self.error(format!("expected {kind}, found `{ty}`"), span, Some(origin));
+                false
+            }
+            Some(_) => true,
+            None => {
+                let slot = &mut self.slots[root];
+                if kind < slot.kind.0 {
+                    slot.kind = (kind, Some(origin));
+                }
+                true
+            }
+        }
+    }
+
+    fn expr(&mut self, node: &SyntaxNode) -> usize {
+        let token = node.child_tokens().find(|token| !token.kind.is_trivia());
+        let operands: Vec<&SyntaxNode> = node.child_nodes().collect();
+
+        let slot = match (node.kind, token, operands.as_slice()) {
+            // Anything after the expression in the root is an error node.
+            (NodeKind::Root | NodeKind::Paren, _, [expr, ..]) => self.expr(expr),
+            // A suffix can make an integer literal a float.
+            (NodeKind::Literal, Some(token), []) => match literal(token, self.input) {
+                Ok(Lit::Int(_) | Lit::BigInt(_)) => self.known(Type::Int, node.span),
+                Ok(Lit::Float(_)) => self.known(Type::Float, node.span),
+                Ok(Lit::Str(_)) => self.known(Type::Str, node.span),
+                Err(_) => self.fresh(),
+            },
+            (NodeKind::Ident, Some(token), []) => self.ident(token, node.span),
+            (NodeKind::FnCall, Some(token), args) => self.call(node, token, args),
+            (NodeKind::PrefixOp, Some(token), [operand]) => self.prefix_op(node, token, operand),
+            (NodeKind::InfixOp, Some(token), [lhs, rhs]) => self.infix_op(node, token, lhs, rhs),
+            (NodeKind::PostfixOp, Some(token), [operand]) => {
+                let slot = self.expr(operand);
+                self.unify_with(Type::Int, token.span, slot, operand.span);
+                self.known(Type::Int, node.span)
+            }
+            _ => self.fresh(),
+        };
+        self.nodes.push((ptr::from_ref(node), slot));
+        slot
+    }
+
+    fn ident(&mut self, token: Token, span: Span) -> usize {
+        let name = token.text(self.input);
+        if let Some(value) = self.env.get(name) {
+            return self.known(value.ty(), span);
+        }
+        if let Some(slot) = self.variables.get(name) {
+            *slot
+        } else {
+            let slot = self.fresh();
+            self.variables.insert(name.to_string(), slot);
+            slot
+        }
+    }
+
+    fn prefix_op(&mut self, node: &SyntaxNode, token: Token, operand: &SyntaxNode) -> usize {
+        let slot = self.expr(operand);
+        if token.kind == T![!] {
+            self.unify_with(Type::Bool, token.span, slot, operand.span);
+            self.known(Type::Bool, node.span)
+        } else if self.constrain(slot, Kind::Number, token.span, operand.span) {
+            slot
+        } else {
+            self.fresh()
+        }
+    }
+
+    fn infix_op(
+        &mut self,
+        node: &SyntaxNode,
+        token: Token,
+        lhs: &SyntaxNode,
+        rhs: &SyntaxNode,
+    ) -> usize {
+        let lhs_slot = self.expr(lhs);
+        let rhs_slot = self.expr(rhs);
+
+        let kind = match token.kind {
+            T![&&] | T![||] => {
+                self.unify_with(Type::Bool, token.span, lhs_slot, lhs.span);
+                self.unify_with(Type::Bool, token.span, rhs_slot, rhs.span);
+                return self.known(Type::Bool, node.span);
+            }
+            T![==] | T![!=] => Kind::Any,
+            T![<] | T![<=] | T![>] | T![>=] | T![+] => Kind::NumberOrString,
+            _ => Kind::Number,
+        };
+
+        let valid = self.constrain(lhs_slot, kind, token.span, lhs.span)
+            & self.constrain(rhs_slot, kind, token.span, rhs.span);
+        let ty = match (self.resolved(lhs_slot), self.resolved(rhs_slot)) {
+            _ if !valid => None,
+            (Some((lhs_ty, _)), Some((rhs_ty, _))) => match (lhs_ty, rhs_ty) {
+                (a, b) if a == b => Some(a),
+                (Type::Int | Type::Float, Type::Int | Type::Float) => Some(Type::Float),
+                (lhs_ty, rhs_ty) => {
+                    let message = format!("expected `{lhs_ty}`, found `{rhs_ty}`");
+                    self.error(message, rhs.span, Some(lhs.span));
+                    None
+                }
+            },
+            _ if self.unify(lhs_slot, rhs_slot, rhs.span) => {
+                self.resolved(lhs_slot).map(|(ty, _)| ty)
+            }
+            _ => None,
+        };
+
+        match (token.kind, ty) {
+            (T![==] | T![!=] | T![<] | T![<=] | T![>] | T![>=], _) => {
+                self.known(Type::Bool, node.span)
+            }
+            (_, Some(ty)) => self.known(ty, node.span),
+            // Still unknown, but the same as both operands.
+            (_, None) if valid => lhs_slot,
+            (_, None) => self.fresh(),
+        }
+    }
+
+    fn call(&mut self, node: &SyntaxNode, token: Token, args: &[&SyntaxNode]) -> usize {
+        let name = token.text(self.input);
+        let args: Vec<(usize, Span)> = args.iter().map(|arg| (self.expr(arg), arg.span)).collect();
+
+        let overloads = self.env.functions(name);
+        if overloads.is_empty() {
+            self.error(format!("unknown function `{name}`"), token.span, None);
+            return self.fresh();
+        }
+        let signatures: Vec<_> = overloads
+            .iter()
+            .map(|function| function.signature().clone())
+            .filter(|signature| signature.params.len() == args.len())
+            .collect();
+        if signatures.is_empty() {
+            let expected = overloads[0].signature().params.len();
+            let message = format!(
+                "`{}` takes {} argument(s), but {} were given",
+                name,
+                expected,
+                args.len()
+            );
+            self.error(message, node.span, Some(token.span));
+            return self.fresh();
+        }
+
+        let arg_types: Vec<Option<Type>> = args
+            .iter()
+            .map(|(slot, _)| self.resolved(*slot).map(|(ty, _)| ty))
+            .collect();
+        let arg_kinds: Vec<Kind> = args
+            .iter()
+            .map(|(slot, _)| {
+                let root = self.find(*slot);
+                self.slots[root].kind.0
+            })
+            .collect();
+        let accepts = |param: &Type, i: usize| match arg_types[i] {
+            Some(ty) => ty == *param || (*param == Type::Float && ty == Type::Int),
+            None => arg_kinds[i].admits(*param),
+        };
+        let accepting: Vec<_> = signatures
+            .iter()
+            .filter(|signature| {
+                signature
+                    .params
+                    .iter()
+                    .enumerate()
+                    .all(|(i, param)| accepts(param, i))
+            })
+            .collect();
+
+        match accepting.as_slice() {
+            [] if signatures.len() == 1 => {
+                for (i, param) in signatures[0].params.iter().enumerate() {
+                    if !accepts(param, i) {
+                        let (slot, span) = args[i];
+                        self.unify_with(*param, token.span, slot, span);
+                    }
+                }
+                self.fresh()
+            }
+            [] => {
+                let types: Vec<String> = arg_types
+                    .iter()
+                    .map(|ty| ty.map_or("_".to_string(), |ty| ty.to_string()))
+                    .collect();
+                let message = format!("`{}` cannot be called with ({})", name, types.join(", "));
+                self.error(message, node.span, Some(token.span));
+                self.fresh()
+            }
+            [signature] => {
+                // Only now is it clear which types the unknown arguments have.
+                let signature = (*signature).clone();
+                for ((slot, span), param) in args.iter().zip(&signature.params) {
+                    if self.resolved(*slot).is_none() {
+                        self.unify_with(*param, token.span, *slot, *span);
+                    }
+                }
+                self.known(signature.ret, node.span)
+            }
+            signatures => {
+                let exact = signatures.iter().find(|signature| {
+                    signature
+                        .params
+                        .iter()
+                        .zip(&arg_types)
+                        .all(|(param, ty)| Some(*param) == *ty)
+                });
+                let ret = signatures[0].ret;
+                match exact {
+                    Some(signature) => self.known(signature.ret, node.span),
+                    None if signatures.iter().all(|signature| signature.ret == ret) => {
+                        self.known(ret, node.span)
+                    }
+                    None => self.fresh(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::cst;
+
+    fn check_with(input: &str, env: &Env) -> Typing {
+        let parse = cst::parse(input);
+        assert!(parse.errors.is_empty(), "{:?}", parse.errors);
+        check(&parse.root, input, env)
+    }
+
+    fn ty(input: &str) -> Option<Type> {
+        let typing = check_with(input, &Env::with_prelude());
+        assert_eq!(typing.errors, [], "checking `{input}`");
+        typing.ty
+    }
+
+    fn errors(input: &str) -> Vec<(String, Span, Option<Span>)> {
+        check_with(input, &Env::with_prelude())
+            .errors
+            .into_iter()
+            .map(|error| (error.message, error.span, error.expected_span))
+            .collect()
+    }
+
+    fn span(start: u32, end: u32) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn literals_and_operators() {
+        assert_eq!(ty("1 + 2 * 3"), Some(Type::Int));
+        assert_eq!(ty("1 + 2.5"), Some(Type::Float));
+        assert_eq!(ty(r#""a" + "b""#), Some(Type::Str));
+        assert_eq!(ty("2 ^ 3 ^ 2 / 4"), Some(Type::Int));
+        assert_eq!(ty("-(3!)"), Some(Type::Int));
+        assert_eq!(ty("1 < 2.5 && !(3 == 4)"), Some(Type::Bool));
+        assert_eq!(ty(r#""a" <= "b" || true"#), Some(Type::Bool));
+    }
+
+    #[test]
+    fn calls_use_host_signatures() {
+        assert_eq!(ty("sqrt(2)"), Some(Type::Float));
+        assert_eq!(ty("abs(-2)"), Some(Type::Int));
+        assert_eq!(ty("max(2, 3)"), Some(Type::Int));
+        assert_eq!(ty("min(test + 4, sin(2 * PI))"), Some(Type::Float));
+        // `min` of an unknown `x` could be either overload.
+        assert_eq!(ty("min(x, x)"), None);
+    }
+
+    #[test]
+    fn infers_variable_types() {
+        let env = Env::with_prelude();
+        let typing = check_with("x * 2 > y && b", &env);
+        assert_eq!(typing.errors, []);
+        assert_eq!(
+            typing.variables,
+            BTreeMap::from([
+                ("b".to_string(), Type::Bool),
+                ("x".to_string(), Type::Int),
+                ("y".to_string(), Type::Int),
+            ])
+        );
+
+        let typing = check_with(r#"sqrt(a) + len(s + "!")"#, &{
+            let mut env = Env::with_prelude();
+            env.define_fn("len", &[Type::Str], Type::Int, |_| unreachable!());
+            env
+        });
+        assert_eq!(typing.errors, []);
+        assert_eq!(typing.ty, Some(Type::Float));
+        assert_eq!(typing.variables["a"], Type::Float);
+        assert_eq!(typing.variables["s"], Type::Str);
+
+        // Bound variables have the types of their values.
+        let mut env = Env::new();
+        env.set("n", 2.5);
+        assert_eq!(check_with("n + 1", &env).ty, Some(Type::Float));
+    }
+
+    #[test]
+    fn records_node_types() {
+        let input = "(x + 1) * 2.0 + (-(y))";
+        let root = cst::parse(input).root;
+        let typing = check(&root, input, &Env::new());
+        let types: Vec<_> = root
+            .descendants()
+            .into_iter()
+            .enumerate()
+            .map(|(id, node)| {
+                (
+                    node.text(input),
+                    typing.node_types.get(&NodeId(id)).copied(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            types,
+            [
+                // The root and the expression in it have the same span,
+                // but each has an entry of its own.
+                (input, Some(Type::Float)),
+                (input, Some(Type::Float)),
+                ("(x + 1) * 2.0", Some(Type::Float)),
+                ("(x + 1)", Some(Type::Int)),
+                ("x + 1", Some(Type::Int)),
+                ("x", Some(Type::Int)),
+                ("1", Some(Type::Int)),
+                ("2.0", Some(Type::Float)),
+                ("(-(y))", Some(Type::Float)),
+                ("-(y)", Some(Type::Float)),
+                ("(y)", Some(Type::Float)),
+                ("y", Some(Type::Float)),
+            ]
+        );
+    }
+
+    #[test]
+    fn mismatches_point_to_the_expectation() {
+        assert_eq!(
+            errors(r#"1 + "a""#),
+            [(
+                "expected `int`, found `string`".to_string(),
+                span(4, 7),
+                Some(span(0, 1))
+            )]
+        );
+        // The parenthesized `&&` is a `bool` even though `x` is an `int`.
+        assert_eq!(
+            errors("x - 1 + (x && true)"),
+            [
+                (
+                    "expected a number or string, found `bool`".to_string(),
+                    span(8, 19),
+                    Some(span(6, 7))
+                ),
+                (
+                    "expected `bool`, found `int`".to_string(),
+                    span(9, 10),
+                    Some(span(11, 13))
+                ),
+            ]
+        );
+        assert_eq!(
+            errors(r#""a" + -y"#),
+            [(
+                "expected `string`, found a number".to_string(),
+                span(6, 8),
+                Some(span(0, 3))
+            )]
+        );
+        assert_eq!(
+            errors(r#""a" - 1"#),
+            [(
+                "expected a number, found `string`".to_string(),
+                span(0, 3),
+                Some(span(4, 5))
+            )]
+        );
+        assert_eq!(
+            errors("-x && x"),
+            [
+                (
+                    "expected `bool`, found a number".to_string(),
+                    span(0, 2),
+                    Some(span(3, 5))
+                ),
+                (
+                    "expected `bool`, found a number".to_string(),
+                    span(6, 7),
+                    Some(span(3, 5))
+                ),
+            ]
+        );
+        assert_eq!(
+            errors("1 && 2.5!"),
+            [
+                (
+                    "expected `bool`, found `int`".to_string(),
+                    span(0, 1),
+                    Some(span(2, 4))
+                ),
+                (
+                    "expected `int`, found `float`".to_string(),
+                    span(5, 8),
+                    Some(span(8, 9))
+                ),
+                (
+                    "expected `bool`, found `int`".to_string(),
+                    span(5, 9),
+                    Some(span(2, 4))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_errors() {
+        assert_eq!(
+            errors(r#"sqrt("4")"#),
+            [(
+                "expected `float`, found `string`".to_string(),
+                span(5, 8),
+                Some(span(0, 4))
+            )]
+        );
+        assert_eq!(
+            errors("sqrt(1, 2)"),
+            [(
+                "`sqrt` takes 1 argument(s), but 2 were given".to_string(),
+                span(0, 10),
+                Some(span(0, 4))
+            )]
+        );
+        assert_eq!(
+            errors(r#"max("a", x)"#),
+            [(
+                "`max` cannot be called with (string, _)".to_string(),
+                span(0, 11),
+                Some(span(0, 3))
+            )]
+        );
+        assert_eq!(
+            errors("f(1)"),
+            [("unknown function `f`".to_string(), span(0, 1), None)]
+        );
+    }
+}
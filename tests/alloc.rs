@@ -0,0 +1,61 @@
+//! Counts the allocations of evaluating compiled expressions.
+
+use parsing_basics::{
+    eval::{compile_with, Env, Value},
+    parser::Parser,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn compiled_numbers_and_calls_do_not_allocate() {
+    let expr = Parser::new("a * x ^ 2 + sqrt(abs(x)) * PI / (1 + x * x) + max(a, 2) - min(x, a)")
+        .parse_expression(0);
+    let mut env = Env::with_prelude();
+    env.set("a", 3);
+    env.set("x", 0.5);
+    let compiled = compile_with(&expr, &env);
+    let mut values = compiled.bind(&env).unwrap();
+    let x_slot = compiled.slot("x").unwrap();
+
+    let count = allocations(|| {
+        for i in 0..100_i32 {
+            // Switches between overloads of `abs` and `min`.
+            values[x_slot] = if i % 2 == 0 {
+                Value::Float(f64::from(i) / 8.0)
+            } else {
+                Value::Int(i64::from(i))
+            };
+            compiled.eval(&values).unwrap();
+        }
+    });
+    assert_eq!(count, 0);
+}
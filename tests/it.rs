@@ -31,6 +31,56 @@ fn unknown_input() {
     assert_tokens!(tokens, [T!['{'], T![error], T![+], T![EOF],]);
 }
 
+#[test]
+fn comments() {
+    let input = "/// doc\n//! inner\n//// plain\n/* a /* nested */ b */ x // at the end";
+    let tokens: Vec<_> = Lexer::new(input)
+        .tokenize()
+        .into_iter()
+        .filter(|t| t.kind != T![ws])
+        .collect();
+    assert_tokens!(tokens, [
+        T![doc_comment],
+        T![inner_doc_comment],
+        T![comment],
+        T![block_comment],
+        T![ident],
+        T![comment],
+        T![EOF],
+    ]);
+    assert_eq!(tokens[3].text(input), "/* a /* nested */ b */");
+    assert_eq!(tokens[5].text(input), "// at the end");
+
+    // An unterminated block comment takes the rest of the input.
+    let tokens = Lexer::new("1 /* a /* b */ + 2").tokenize();
+    assert_tokens!(tokens, [T![int], T![ws], T![unterminated_comment], T![EOF],]);
+}
+
+#[test]
+fn number_literals() {
+    let input = "0xFF 0o17 0b1010 1_000_000 10u8 3.0f32 2.5e-3 1e5f64 0b102";
+    let tokens: Vec<_> = Lexer::new(input)
+        .tokenize()
+        .into_iter()
+        .filter(|t| t.kind != T![ws])
+        .collect();
+    assert_tokens!(tokens, [
+        T![int], T![int], T![int], T![int], T![int],
+        T![float], T![float], T![float], T![int], T![EOF],
+    ]);
+    let texts: Vec<_> = tokens.iter().map(|t| t.text(input)).collect();
+    assert_eq!(
+        texts,
+        ["0xFF", "0o17", "0b1010", "1_000_000", "10u8", "3.0f32", "2.5e-3", "1e5f64", "0b102", ""]
+    );
+
+    let mut parser = Parser::new("0xFF + 10f32");
+    assert_eq!(
+        parser.parse_expression(0).to_string(),
+        "(255 + 10)"
+    );
+}
+
 #[test]
 fn token_spans() {
     {
@@ -271,3 +321,104 @@ fn parse_binary_expressions() {
         "min((test + 4),sin((2 * PI),),)",
     );
 }
+
+#[test]
+fn parse_postfix_expressions() {
+    fn parse(input: &str) -> ast::Expr {
+        let mut parser = Parser::new(input);
+        parser.parse_expression(0)
+    }
+
+    assert_eq!(parse("3!").to_string(), "(3 !)");
+    assert_eq!(parse("-3!").to_string(), "(- (3 !))");
+    assert_eq!(parse("2 * 3! ^ 2").to_string(), "(2 * ((3 !) ^ 2))");
+}
+
+#[test]
+fn parse_errors() {
+    fn errors(input: &str) -> Vec<String> {
+        let mut parser = Parser::new(input);
+        parser
+            .try_parse_expression(0)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    assert_eq!(errors("4 +"), ["Unknown start of expression: `<EOF>` at <3, 3>"]);
+    assert_eq!(errors("4 $ 2"), ["Unknown operator: `<?>` at <2, 3>"]);
+    assert_eq!(
+        errors("min(1, 2"),
+        ["Expected `)`, but found `<EOF>` at <8, 8>"],
+    );
+    assert_eq!(
+        errors("(1 /* a */ + 2 /* b"),
+        ["Unterminated block comment at <15, 19>"],
+    );
+    assert_eq!(
+        errors("99999999999999999999999i64"),
+        ["integer literal out of range for `i64`: `99999999999999999999999i64` at <0, 26>"],
+    );
+    assert_eq!(
+        errors("1 + 300u8"),
+        ["integer literal out of range for `u8`: `300u8` at <4, 9>"],
+    );
+    assert_eq!(
+        errors("0b102 * 2"),
+        ["invalid digit `2` in binary literal: `0b102` at <0, 5>"],
+    );
+}
+
+#[test]
+fn evaluate_with_host_data() {
+    use parsing_basics::eval::{Env, Interpreter, RuntimeError, Type, Value};
+
+    let mut env = Env::with_prelude();
+    env.set("test", 2);
+    env.define_fn("clamp01", &[Type::Float], Type::Float, |args| match args[0] {
+        Value::Float(x) => Ok(Value::Float(x.clamp(0.0, 1.0))),
+        _ => unreachable!(),
+    });
+
+    let eval = |input: &str| {
+        let expr = Parser::new(input).parse_expression(0);
+        Interpreter::new().eval(&expr, &env)
+    };
+    assert_eq!(
+        eval("min ( test + 4 , sin(2*PI ))"),
+        Ok(Value::Float((2.0 * std::f64::consts::PI).sin())),
+    );
+    assert_eq!(eval("clamp01(test)"), Ok(Value::Float(1.0)));
+    assert_eq!(
+        eval("clamp01()"),
+        Err(RuntimeError::ArityMismatch {
+            name: "clamp01".to_string(),
+            expected: 1,
+            found: 0,
+        })
+    );
+}
+
+/// Each block in the golden file is an input line,
+/// followed by the expected tree as an S-expression.
+/// If the expected tree has spans, they are checked as well.
+#[test]
+fn golden_expressions() {
+    use parsing_basics::parser::{cst, sexpr};
+
+    let golden = include_str!("golden/expressions.txt");
+    for block in golden.split("\n\n") {
+        let (input, expected) = block.split_once('\n').expect("block without a tree");
+
+        let mut parser = Parser::new(input);
+        let expected_tree = sexpr::read(expected).unwrap();
+        assert_eq!(parser.parse_expression(0), expected_tree, "for `{}`", input);
+
+        if expected.contains('@') {
+            let expected = expected.split_whitespace().collect::<Vec<_>>().join(" ");
+            let spanned = sexpr::write_spanned(&cst::parse(input).root, input);
+            assert_eq!(spanned.as_deref(), Some(expected.as_str()), "for `{}`", input);
+        }
+    }
+}
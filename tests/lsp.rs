@@ -0,0 +1,201 @@
+#![cfg(feature = "lsp")]
+
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+struct Client {
+    server: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = server.stdin.take().unwrap();
+        let stdout = BufReader::new(server.stdout.take().unwrap());
+        Self {
+            server,
+            stdin,
+            stdout,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(
+            self.stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                _ if line.trim_end().is_empty() => break,
+                _ => {}
+            }
+        }
+        let mut content = vec![0; length];
+        self.stdout.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn request(&mut self, id: u32, method: &str, params: Value) -> Value {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let response = self.receive();
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+}
+
+#[test]
+fn session() {
+    let mut client = Client::start();
+    let uri = "file:///main.expr";
+    let document = json!({ "textDocument": { "uri": uri } });
+
+    let response = client.request(1, "initialize", json!({ "capabilities": {} }));
+    let capabilities = &response["result"]["capabilities"];
+    assert_eq!(capabilities["documentFormattingProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(
+        capabilities["semanticTokensProvider"]["legend"]["tokenTypes"][6],
+        "function"
+    );
+    client.notify("initialized", json!({}));
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "expr", "version": 1, "text": "min(1,\n  2" } }),
+    );
+    let diagnostics = client.receive();
+    assert_eq!(diagnostics["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["range"],
+        json!({ "start": { "line": 1, "character": 3 }, "end": { "line": 1, "character": 3 } })
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "x +\n  256u8" }],
+        }),
+    );
+    let diagnostics = client.receive();
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["message"],
+        "integer literal out of range for `u8`: `256u8`"
+    );
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["range"],
+        json!({ "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 7 } })
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 3 },
+            "contentChanges": [{ "text": "min(1,2)*x" }],
+        }),
+    );
+    let diagnostics = client.receive();
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    let tokens = client.request(2, "textDocument/semanticTokens/full", document.clone());
+    assert_eq!(
+        tokens["result"]["data"],
+        json!([0, 0, 3, 6, 0, 0, 4, 1, 1, 0, 0, 2, 1, 1, 0, 0, 2, 1, 4, 0, 0, 1, 1, 5, 0])
+    );
+
+    let edits = client.request(
+        3,
+        "textDocument/formatting",
+        json!({ "textDocument": { "uri": uri }, "options": { "tabSize": 4, "insertSpaces": true } }),
+    );
+    assert_eq!(edits["result"][0]["newText"], "min(1, 2) * x\n");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 4 },
+            "contentChanges": [{ "text": "x + min(1, x)" }],
+        }),
+    );
+    client.receive();
+    let definition = |client: &mut Client, id, character| {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": character },
+        });
+        client.request(id, "textDocument/definition", params)["result"].clone()
+    };
+    assert_eq!(
+        definition(&mut client, 4, 12),
+        json!({
+            "uri": uri,
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+        })
+    );
+    // `min` comes from the prelude, and there is no name at the `,`.
+    assert_eq!(definition(&mut client, 5, 5), Value::Null);
+    assert_eq!(definition(&mut client, 6, 9), Value::Null);
+
+    // Changes without text to a document that was never opened are ignored.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///unknown.expr", "version": 1 },
+            "contentChanges": [],
+        }),
+    );
+
+    let unknown = client.request(7, "textDocument/hover", document.clone());
+    assert_eq!(unknown["error"]["code"], -32601);
+
+    // The grammar has no items, but the server lists them all the same.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 5 },
+            "contentChanges": [{ "text": "fn square(x)" }],
+        }),
+    );
+    client.receive();
+    let symbols = client.request(8, "textDocument/documentSymbol", document);
+    assert_eq!(
+        symbols["result"],
+        json!([{
+            "name": "square",
+            "kind": 12,
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 9 } },
+            "selectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 9 } },
+        }])
+    );
+
+    let shutdown = client.request(9, "shutdown", Value::Null);
+    assert_eq!(shutdown["result"], Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.server.wait().unwrap().success());
+}
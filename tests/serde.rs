@@ -0,0 +1,168 @@
+#![cfg(feature = "serde")]
+
+use parsing_basics::{lexer::*, parser::{ast, cst, Parser}, T};
+use serde_json::json;
+
+#[test]
+fn token_json() {
+    let mut lexer = Lexer::new("x <= 2");
+    let tokens = lexer.tokenize();
+
+    assert_eq!(
+        serde_json::to_value(tokens[2]).unwrap(),
+        json!({ "kind": "Leq", "span": { "start": 2, "end": 4 } }),
+    );
+
+    let json = serde_json::to_string(&tokens).unwrap();
+    let read: Vec<Token> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, tokens);
+}
+
+#[test]
+fn expr_json() {
+    let mut parser = Parser::new(r#"f(-x, 2.5) == "s""#);
+    let expr = parser.parse_expression(0);
+
+    assert_eq!(
+        serde_json::to_value(&expr).unwrap(),
+        json!({
+            "type": "InfixOp",
+            "value": {
+                "op": "Eqq",
+                "lhs": {
+                    "type": "FnCall",
+                    "value": {
+                        "fn_name": "f",
+                        "args": [
+                            {
+                                "type": "PrefixOp",
+                                "value": {
+                                    "op": "Minus",
+                                    "expr": { "type": "Ident", "value": "x" },
+                                },
+                            },
+                            {
+                                "type": "Literal",
+                                "value": { "type": "Float", "value": 2.5 },
+                            },
+                        ],
+                    },
+                },
+                "rhs": {
+                    "type": "Literal",
+                    "value": { "type": "Str", "value": "s" },
+                },
+            },
+        }),
+    );
+
+    let json = serde_json::to_string(&expr).unwrap();
+    let read: ast::Expr = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, expr);
+}
+
+#[test]
+fn cst_json() {
+    let parse = cst::parse("(x");
+    let token = |kind: &str, start: u32, end: u32| {
+        json!({ "type": "Token", "value": { "kind": kind, "span": { "start": start, "end": end } } })
+    };
+
+    assert_eq!(
+        serde_json::to_value(&parse).unwrap(),
+        json!({
+            "root": {
+                "kind": "Root",
+                "span": { "start": 0, "end": 2 },
+                "children": [{
+                    "type": "Node",
+                    "value": {
+                        "kind": "Paren",
+                        "span": { "start": 0, "end": 2 },
+                        "children": [
+                            token("LParen", 0, 1),
+                            {
+                                "type": "Node",
+                                "value": {
+                                    "kind": "Ident",
+                                    "span": { "start": 1, "end": 2 },
+                                    "children": [token("Identifier", 1, 2)],
+                                },
+                            },
+                        ],
+                    },
+                }],
+            },
+            "errors": [{ "message": "Expected `)`, but found `<EOF>`", "span": { "start": 2, "end": 2 } }],
+        }),
+    );
+
+    let json = serde_json::to_string(&parse).unwrap();
+    let read: cst::Parse = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, parse);
+}
+
+#[test]
+fn big_int_json() {
+    let expr = Parser::new("-340282366920938463463374607431768211456").parse_expression(0);
+    let literal = json!({ "type": "BigInt", "value": "340282366920938463463374607431768211456" });
+    assert_eq!(
+        serde_json::to_value(&expr).unwrap(),
+        json!({
+            "type": "PrefixOp",
+            "value": {
+                "op": "Minus",
+                "expr": { "type": "Literal", "value": literal },
+            },
+        }),
+    );
+
+    let json = serde_json::to_string(&expr).unwrap();
+    let read: ast::Expr = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, expr);
+
+    let negative = json!({ "type": "BigInt", "value": "-340282366920938463463374607431768211457" });
+    let lit: ast::Lit = serde_json::from_value(negative.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&lit).unwrap(), negative);
+    assert!(
+        serde_json::from_value::<ast::Lit>(json!({ "type": "BigInt", "value": "1e40" })).is_err()
+    );
+}
+
+#[test]
+fn token_kind_json() {
+    assert_eq!(serde_json::to_value(T![let]).unwrap(), json!("KeywordLet"));
+    assert_eq!(
+        serde_json::from_value::<TokenKind>(json!("Pow")).unwrap(),
+        T![^],
+    );
+}
+
+#[test]
+fn large_int_json() {
+    let large = i128::from(i64::MAX) + 1;
+    let expr = Parser::new(&large.to_string()).parse_expression(0);
+    let literal = json!({ "type": "Int", "value": "9223372036854775808" });
+    assert_eq!(expr, ast::Expr::Literal(ast::Lit::Int(large)));
+    assert_eq!(
+        serde_json::to_value(&expr).unwrap(),
+        json!({ "type": "Literal", "value": literal }),
+    );
+
+    let json = serde_json::to_string(&expr).unwrap();
+    let read: ast::Expr = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, expr);
+
+    // Up to 2^53 - 1, integers stay numbers.
+    let lit = ast::Lit::Int((1 << 53) - 1);
+    assert_eq!(
+        serde_json::to_value(&lit).unwrap(),
+        json!({ "type": "Int", "value": 9_007_199_254_740_991_i64 }),
+    );
+    assert_eq!(
+        serde_json::to_value(ast::Lit::Int(-(1 << 53))).unwrap(),
+        json!({ "type": "Int", "value": "-9007199254740992" }),
+    );
+    let read: ast::Lit = serde_json::from_value(json!({ "type": "Int", "value": -7 })).unwrap();
+    assert_eq!(read, ast::Lit::Int(-7));
+}